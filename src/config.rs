@@ -3,6 +3,18 @@
 
 use anyhow::{Context, Result};
 use std::env;
+use std::path::PathBuf;
+
+use crate::engine::discovery::NewTablePolicy;
+use crate::pipeline::projection::ColumnProjection;
+use crate::pipeline::table_selection::TableSelection;
+use crate::sink::masking::MaskingPolicies;
+use crate::sink::table_map::TableMapping;
+use crate::sink::timezone::TargetTimezone;
+use crate::sink::truncate::TruncatePolicies;
+use crate::sink::type_map::TypeMapping;
+use crate::source::geometry::GeometryFormat;
+use crate::source::origin::OriginFilter;
 
 /// Configuración central de dbmazz desde variables de entorno
 #[derive(Debug, Clone)]
@@ -12,17 +24,41 @@ pub struct Config {
     pub slot_name: String,
     pub publication_name: String,
     pub tables: Vec<String>,
+    pub table_selection: TableSelection,
+    pub table_discovery_interval_secs: u64,
+    pub column_projection: ColumnProjection,
+    pub two_phase: bool,
+    pub origin_filter: OriginFilter,
+    pub binary_tuples: bool,
+    
+    // Snapshot
+    pub initial_snapshot: bool,
+    pub recreate_orphan_slot: bool,
+    pub snapshot_chunk_size: usize,
     
     // StarRocks
     pub starrocks_url: String,
     pub starrocks_port: u16,
     pub starrocks_db: String,
+    pub table_mapping: TableMapping,
+    pub new_table_policy: NewTablePolicy,
     pub starrocks_user: String,
     pub starrocks_pass: String,
+    pub truncate_policy: TruncatePolicies,
+    pub type_mapping: TypeMapping,
+    pub target_timezone: TargetTimezone,
+    pub geometry_format: GeometryFormat,
+    pub masking: MaskingPolicies,
+    pub messages_table: Option<String>,
     
     // Pipeline
     pub flush_size: usize,
+    /// Eventos en tránsito entre el reader del WAL y el pipeline (backpressure)
+    pub channel_capacity: usize,
     pub flush_interval_ms: u64,
+    pub txn_split_size: usize,
+    pub stream_spill_threshold: usize,
+    pub stream_spill_dir: PathBuf,
     
     // gRPC
     pub grpc_port: u16,
@@ -31,6 +67,28 @@ pub struct Config {
 impl Config {
     /// Cargar configuración desde variables de entorno
     pub fn from_env() -> Result<Self> {
+        // Las tablas sin regla de mapeo van a STARROCKS_DB
+        let starrocks_db = env::var("STARROCKS_DB")
+            .context("STARROCKS_DB must be set")?;
+        let table_mapping = TableMapping::parse(&env::var("TABLE_MAPPING").unwrap_or_default(), &starrocks_db)
+            .context("Invalid TABLE_MAPPING")?
+            .with_fan_in(
+                &env::var("TABLE_FANIN").unwrap_or_default(),
+                &env::var("FANIN_SOURCE_COLUMN").unwrap_or_default(),
+            )
+            .context("Invalid TABLE_FANIN")?;
+        // Nombres explícitos en `tables`; los patrones se expanden al iniciar y
+        // con el descubrimiento de tablas nuevas
+        let table_selection = TableSelection::parse(
+            &env::var("TABLES").unwrap_or_else(|_| "orders,order_items".to_string()),
+            &env::var("TABLES_EXCLUDE").unwrap_or_default(),
+        )
+        .context("Invalid TABLES / TABLES_EXCLUDE")?;
+        let flush_size = env::var("FLUSH_SIZE")
+            .unwrap_or_else(|_| "10000".to_string())
+            .parse()
+            .unwrap_or(10000);
+
         Ok(Self {
             // PostgreSQL
             database_url: env::var("DATABASE_URL")
@@ -39,11 +97,42 @@ impl Config {
                 .unwrap_or_else(|_| "dbmazz_slot".to_string()),
             publication_name: env::var("PUBLICATION_NAME")
                 .unwrap_or_else(|_| "dbmazz_pub".to_string()),
-            tables: env::var("TABLES")
-                .unwrap_or_else(|_| "orders,order_items".to_string())
-                .split(',')
-                .map(|s| s.trim().to_string())
-                .collect(),
+            tables: table_selection.tables().to_vec(),
+            table_selection,
+            table_discovery_interval_secs: env::var("TABLE_DISCOVERY_INTERVAL_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .unwrap_or(60),
+            column_projection: ColumnProjection::parse(
+                &env::var("COLUMNS_INCLUDE").unwrap_or_default(),
+                &env::var("COLUMNS_EXCLUDE").unwrap_or_default(),
+            )
+            .context("Invalid COLUMNS_INCLUDE / COLUMNS_EXCLUDE")?,
+            two_phase: env::var("TWO_PHASE")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .unwrap_or(false),
+            origin_filter: OriginFilter::parse(
+                &env::var("ORIGIN_FILTER").unwrap_or_default(),
+            ),
+            binary_tuples: env::var("BINARY_TUPLES")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .unwrap_or(true),
+            
+            // Snapshot
+            initial_snapshot: env::var("INITIAL_SNAPSHOT")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .unwrap_or(true),
+            recreate_orphan_slot: env::var("RECREATE_ORPHAN_SLOT")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .unwrap_or(false),
+            snapshot_chunk_size: env::var("SNAPSHOT_CHUNK_SIZE")
+                .unwrap_or_else(|_| "10000".to_string())
+                .parse()
+                .unwrap_or(10000),
             
             // StarRocks
             starrocks_url: env::var("STARROCKS_URL")
@@ -52,22 +141,62 @@ impl Config {
                 .unwrap_or_else(|_| "9030".to_string())
                 .parse()
                 .unwrap_or(9030),
-            starrocks_db: env::var("STARROCKS_DB")
-                .context("STARROCKS_DB must be set")?,
+            starrocks_db,
+            table_mapping,
+            new_table_policy: env::var("NEW_TABLE_POLICY")
+                .unwrap_or_else(|_| "reject".to_string())
+                .parse()
+                .context("Invalid NEW_TABLE_POLICY")?,
             starrocks_user: env::var("STARROCKS_USER")
                 .unwrap_or_else(|_| "root".to_string()),
             starrocks_pass: env::var("STARROCKS_PASS")
                 .unwrap_or_else(|_| "".to_string()),
+            truncate_policy: TruncatePolicies::parse(
+                &env::var("TRUNCATE_POLICY").unwrap_or_else(|_| "ignore".to_string()),
+                &env::var("TRUNCATE_POLICY_TABLES").unwrap_or_default(),
+            )
+            .context("Invalid TRUNCATE_POLICY / TRUNCATE_POLICY_TABLES")?,
+            type_mapping: TypeMapping::parse(&env::var("TYPE_OVERRIDES").unwrap_or_default())
+                .context("Invalid TYPE_OVERRIDES")?,
+            target_timezone: TargetTimezone::parse(&env::var("TARGET_TIMEZONE").unwrap_or_else(|_| "UTC".to_string()))
+                .context("Invalid TARGET_TIMEZONE")?,
+            geometry_format: env::var("GEOMETRY_FORMAT")
+                .unwrap_or_else(|_| "wkt".to_string())
+                .parse()
+                .context("Invalid GEOMETRY_FORMAT")?,
+            masking: MaskingPolicies::load(
+                &env::var("COLUMN_MASKS").unwrap_or_default(),
+                env::var("MASKING_SALT_FILE").ok().filter(|f| !f.is_empty()).as_deref(),
+            )
+            .context("Invalid COLUMN_MASKS / MASKING_SALT_FILE")?,
+            messages_table: env::var("MESSAGES_TABLE")
+                .ok()
+                .filter(|t| !t.is_empty()),
             
             // Pipeline
-            flush_size: env::var("FLUSH_SIZE")
-                .unwrap_or_else(|_| "10000".to_string())
-                .parse()
-                .unwrap_or(10000),
+            flush_size,
+            // Sin default fijo: 2 × FLUSH_SIZE de arranque (ReloadConfig no la cambia)
+            channel_capacity: env::var("CHANNEL_CAPACITY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&c| c > 0)
+                .unwrap_or(flush_size * 2),
             flush_interval_ms: env::var("FLUSH_INTERVAL_MS")
                 .unwrap_or_else(|_| "5000".to_string())
                 .parse()
                 .unwrap_or(5000),
+            // 0 = nunca dividir: una transacción solo se ve completa en StarRocks
+            txn_split_size: env::var("TXN_SPLIT_SIZE")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .unwrap_or(0),
+            stream_spill_threshold: env::var("STREAM_SPILL_THRESHOLD")
+                .unwrap_or_else(|_| "100000".to_string())
+                .parse()
+                .unwrap_or(100000),
+            stream_spill_dir: env::var("STREAM_SPILL_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| env::temp_dir()),
             
             // gRPC
             grpc_port: env::var("GRPC_PORT")
@@ -82,9 +211,40 @@ impl Config {
         println!("Starting dbmazz (High Performance Mode)...");
         println!("Source: Postgres ({})", self.slot_name);
         println!("Target: StarRocks ({})", self.starrocks_db);
+        println!("Table mapping: {}", self.table_mapping);
         println!("Flush: {} msgs or {}ms interval", self.flush_size, self.flush_interval_ms);
+        println!("Channel capacity: {} events", self.channel_capacity);
+        if self.txn_split_size > 0 {
+            println!("Transaction split: {} events", self.txn_split_size);
+        } else {
+            println!("Transaction split: disabled");
+        }
+        println!("Stream spill: {} events per transaction ({})", self.stream_spill_threshold, self.stream_spill_dir.display());
         println!("gRPC: port {}", self.grpc_port);
         println!("Tables: {:?}", self.tables);
+        if self.table_selection.has_patterns() {
+            println!("Table selection: {}", self.table_selection);
+            if self.table_discovery_interval_secs > 0 {
+                println!("Table discovery: every {}s", self.table_discovery_interval_secs);
+            } else {
+                println!("Table discovery: at startup only");
+            }
+        }
+        println!("New table policy: {}", self.new_table_policy);
+        println!("Columns: {}", self.column_projection);
+        println!("Initial snapshot: {}", self.initial_snapshot);
+        println!("Recreate orphan slot: {}", self.recreate_orphan_slot);
+        println!("Two-phase commit: {}", self.two_phase);
+        println!("Origin filter: {}", self.origin_filter);
+        println!("Binary tuples: {}", self.binary_tuples);
+        println!("Truncate policy: {}", self.truncate_policy);
+        println!("Type mapping: {}", self.type_mapping);
+        println!("Target timezone: {}", self.target_timezone);
+        println!("Geometry format: {}", self.geometry_format);
+        println!("Column masks: {}", self.masking);
+        if let Some(ref table) = self.messages_table {
+            println!("Logical messages table: {}", table);
+        }
     }
 }
 
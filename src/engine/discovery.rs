@@ -0,0 +1,196 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Descubrimiento de tablas nuevas que coinciden con los patrones de `TABLES`.
+//!
+//! Una tabla se descubre por el escaneo periódico del catálogo o por su mensaje
+//! Relation si ya estaba en la publication (el pipeline la agrega antes de
+//! procesar sus cambios). La tabla pasa por el mismo setup que `ReloadConfig`,
+//! que la agrega a la publication; si no tiene destino en StarRocks se crea o se
+//! rechaza según `NEW_TABLE_POLICY`; una tabla rechazada se reintenta en el
+//! siguiente escaneo. Los patrones son los de la config compartida, así los
+//! cambios de `ReloadConfig` se aplican sin reiniciar.
+
+use anyhow::{anyhow, Result};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio_postgres::Client;
+
+use super::setup::{postgres::create_postgres_client, SetupManager};
+use crate::config::Config;
+use crate::grpc::state::SharedState;
+use crate::pipeline::table_selection::{qualify_table, TableSelection};
+
+/// Qué hacer cuando la tabla destino de una tabla no existe en StarRocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NewTablePolicy {
+    /// Error en el setup; una tabla descubierta no se agrega
+    Reject,
+    /// `CREATE TABLE` con las columnas y la PK de PostgreSQL
+    Create,
+}
+
+impl FromStr for NewTablePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "reject" => Ok(Self::Reject),
+            "create" => Ok(Self::Create),
+            other => Err(anyhow!("Invalid new table policy '{}' (expected reject or create)", other)),
+        }
+    }
+}
+
+impl fmt::Display for NewTablePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reject => write!(f, "reject"),
+            Self::Create => write!(f, "create"),
+        }
+    }
+}
+
+/// Agrega al pipeline las tablas que coinciden con los patrones de `TABLES`
+pub struct TableDiscovery {
+    config: Config,
+    shared_state: Arc<SharedState>,
+    // Tablas cuyo setup falló desde el último escaneo; serializa el setup del
+    // pipeline y del escaneo
+    rejected: Mutex<HashSet<String>>,
+}
+
+impl TableDiscovery {
+    pub fn new(config: Config, shared_state: Arc<SharedState>) -> Self {
+        Self {
+            config,
+            shared_state,
+            rejected: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Indica si la tabla coincide con algún patrón de `TABLES`
+    pub fn matches(&self, namespace: &str, table: &str) -> bool {
+        self.shared_state.config.borrow().table_selection.matches(namespace, table)
+    }
+
+    /// Setup de una tabla descubierta y alta en la config compartida. Con
+    /// `snapshot` se encola la copia de sus filas existentes. Retorna si la tabla
+    /// queda replicada.
+    pub async fn add_table(&self, table: &str, snapshot: bool) -> bool {
+        let mut rejected = self.rejected.lock().await;
+        if self.is_replicated(table) {
+            return true;
+        }
+        if rejected.contains(table) {
+            return false;
+        }
+
+        if let Err(e) = SetupManager::new(self.config.clone()).setup_table(table).await {
+            eprintln!(
+                "⚠️  Table {} matches TABLES but was not added (NEW_TABLE_POLICY={}): {}",
+                table, self.config.new_table_policy, e
+            );
+            rejected.insert(table.to_string());
+            return false;
+        }
+
+        self.shared_state.config.send_modify(|config| config.tables.push(table.to_string()));
+        println!("🆕 Table {} discovered and added to the pipeline", table);
+
+        if snapshot && self.config.initial_snapshot {
+            if let Err(e) = self.shared_state.queue_snapshots(&[table.to_string()], None) {
+                eprintln!("⚠️  Could not queue snapshot for discovered table {}: {}", table, e);
+            }
+        }
+        true
+    }
+
+    /// Buscar en el catálogo tablas que coinciden y todavía no se replican.
+    /// Retorna las tablas agregadas.
+    ///
+    /// Las filas de una tabla que no estaba en la publication se copian con un
+    /// snapshot incremental (sus cambios no llegaron al slot); si el slot todavía
+    /// no existe las incluye la carga inicial.
+    pub async fn scan(&self) -> Result<Vec<String>> {
+        let selection = self.shared_state.config.borrow().table_selection.clone();
+        if !selection.has_patterns() {
+            return Ok(Vec::new());
+        }
+        // Las tablas rechazadas se reintentan (pudo crearse su tabla destino)
+        self.rejected.lock().await.clear();
+
+        let client = create_postgres_client(&self.config.database_url).await?;
+        let matching = matching_tables(&client, &selection).await?;
+        let published: HashSet<String> = client
+            .query(
+                "SELECT schemaname, tablename FROM pg_publication_tables WHERE pubname = $1",
+                &[&self.config.publication_name],
+            )
+            .await?
+            .iter()
+            .map(|row| format!("{}.{}", row.get::<_, String>(0), row.get::<_, String>(1)))
+            .collect();
+        let slot_exists = client
+            .query_opt(
+                "SELECT 1 FROM pg_replication_slots WHERE slot_name = $1",
+                &[&self.config.slot_name],
+            )
+            .await?
+            .is_some();
+
+        let mut added = Vec::new();
+        for table in matching {
+            let snapshot = slot_exists && !published.contains(&table);
+            if !self.is_replicated(&table) && self.add_table(&table, snapshot).await {
+                added.push(table);
+            }
+        }
+        Ok(added)
+    }
+
+    /// Escaneo periódico del catálogo (el primero ya se hizo al iniciar)
+    pub async fn run(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.scan().await {
+                eprintln!("⚠️  Table discovery scan failed: {}", e);
+            }
+        }
+    }
+
+    /// Indica si la tabla (`schema.tabla`) ya está en la config compartida
+    fn is_replicated(&self, table: &str) -> bool {
+        let config = self.shared_state.config.borrow();
+        config.tables.iter().any(|t| qualify_table(t) == table)
+    }
+}
+
+/// Tablas del catálogo (`schema.tabla`) que coinciden con los patrones de la selección
+pub async fn matching_tables(client: &Client, selection: &TableSelection) -> Result<Vec<String>> {
+    // Las particiones llegan por su tabla raíz (publish_via_partition_root)
+    let rows = client
+        .query(
+            "SELECT n.nspname, c.relname
+             FROM pg_class c
+             JOIN pg_namespace n ON c.relnamespace = n.oid
+             WHERE c.relkind IN ('r', 'p') AND NOT c.relispartition
+               AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+               AND n.nspname NOT LIKE 'pg_toast%'
+             ORDER BY n.nspname, c.relname",
+            &[],
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| (row.get::<_, String>(0), row.get::<_, String>(1)))
+        .filter(|(namespace, name)| selection.matches(namespace, name))
+        .map(|(namespace, name)| format!("{}.{}", namespace, name))
+        .collect())
+}
@@ -1,29 +1,41 @@
 // Copyright 2025
 // Licensed under the Elastic License v2.0
 
-mod setup;
+pub(crate) mod discovery;
+pub(crate) mod setup;
+mod snapshot;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use futures::{SinkExt, StreamExt};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc;
+use tokio_postgres::CopyBothDuplex;
 
 use crate::config::Config;
 use crate::grpc::{self, CdcConfig, CdcState, Stage};
 use crate::grpc::state::SharedState;
 use crate::pipeline::Pipeline;
-use crate::replication::{parse_replication_message, handle_xlog_data, handle_keepalive, WalMessage};
+use crate::pipeline::control::{ControlLog, ControlPosition};
+use crate::pipeline::snapshot_window::SnapshotChunk;
+use crate::replication::{parse_replication_message, handle_xlog_data, handle_keepalive, WalDecoder, WalMessage};
+use discovery::TableDiscovery;
 use setup::SetupManager;
+use snapshot::{IncrementalSnapshotter, InitialSnapshot};
 use crate::sink::starrocks::StarRocksSink;
 use crate::source::postgres::{PostgresSource, build_standby_status_update};
+use crate::source::type_catalog::TypeCatalog;
 use crate::state_store::StateStore;
 
+/// Intentos de START_REPLICATION al reiniciar el stream en texto
+const RESTART_ATTEMPTS: u32 = 10;
+
 /// Motor principal de CDC que orquesta todos los componentes
 pub struct CdcEngine {
     config: Config,
     shared_state: Arc<SharedState>,
     state_store: StateStore,
+    type_catalog: TypeCatalog,
 }
 
 impl CdcEngine {
@@ -34,22 +46,27 @@ impl CdcEngine {
             flush_size: config.flush_size,
             flush_interval_ms: config.flush_interval_ms,
             tables: config.tables.clone(),
+            table_selection: config.table_selection.clone(),
             slot_name: config.slot_name.clone(),
         };
         let shared_state = SharedState::new(cdc_config);
 
         // 2. Inicializar StateStore
         let state_store = StateStore::new(&config.database_url).await?;
+
+        // 3. Catálogo de tipos del usuario (domains, enums, composites)
+        let type_catalog = TypeCatalog::load(&config.database_url, config.geometry_format).await?;
         
         Ok(Self {
             config,
             shared_state,
             state_store,
+            type_catalog,
         })
     }
 
     /// Ejecutar el motor CDC
-    pub async fn run(self) -> Result<()> {
+    pub async fn run(mut self) -> Result<()> {
         // Stage: SETUP - gRPC Server
         self.shared_state.set_stage(Stage::Setup, "Starting gRPC server").await;
         self.start_grpc_server();
@@ -67,18 +84,25 @@ impl CdcEngine {
             }
         }
 
+        // Stage: SETUP - Tablas que coinciden con los patrones de TABLES
+        let discovery = self.discover_tables().await?;
+
         // Stage: SETUP - Checkpoint
         self.shared_state.set_stage(Stage::Setup, "Loading checkpoint").await;
-        let start_lsn = self.load_checkpoint().await?;
+        let mut start_lsn = self.load_checkpoint().await?;
 
         // Stage: SETUP - Source Connection
         self.shared_state.set_stage(Stage::Setup, "Connecting to PostgreSQL").await;
-        let source = self.init_source().await?;
+        let mut source = self.init_source().await?;
+
+        // Stage: SNAPSHOT - Carga inicial (solo si el slot es nuevo)
+        if let Some(consistent_point) = self.run_initial_snapshot(&source, start_lsn).await? {
+            start_lsn = consistent_point;
+        }
         
         // Stage: SETUP - Replication Stream
         self.shared_state.set_stage(Stage::Setup, "Starting replication stream").await;
-        let replication_stream = source.start_replication_from(start_lsn).await?;
-        tokio::pin!(replication_stream);
+        let mut replication_stream = Box::pin(source.start_replication_from(start_lsn).await?);
 
         // Stage: SETUP - Sink Connection
         self.shared_state.set_stage(Stage::Setup, "Connecting to StarRocks").await;
@@ -86,19 +110,63 @@ impl CdcEngine {
 
         // Stage: SETUP - Pipeline
         self.shared_state.set_stage(Stage::Setup, "Initializing pipeline").await;
-        let (tx, feedback_rx) = self.init_pipeline(sink);
+        let (commit_lsn, seq) = self.state_store.load_control_position(&self.config.slot_name).await?;
+        let control = ControlPosition { commit_lsn, seq };
+        let (tx, feedback_rx, chunk_tx) = self.init_pipeline(sink, discovery.clone(), control);
+
+        // Snapshots incrementales en paralelo al streaming
+        self.start_incremental_snapshots(chunk_tx);
+
+        // Escaneo periódico del catálogo en busca de tablas nuevas
+        self.start_table_discovery(discovery);
 
         // Stage: CDC - Ready to replicate
         self.shared_state.set_stage(Stage::Cdc, "Replicating").await;
         println!("Connected! Streaming CDC events...");
 
         // 6. Ejecutar main loop
-        self.run_main_loop(
-            replication_stream,
-            tx,
-            feedback_rx,
-            start_lsn,
-        ).await
+        let mut feedback_rx = feedback_rx;
+        loop {
+            let exit = self.run_main_loop(
+                &mut replication_stream,
+                &tx,
+                &mut feedback_rx,
+                source.binary_active(),
+            ).await?;
+            if exit == LoopExit::Stopped {
+                return Ok(());
+            }
+
+            // Una tabla con un tipo sin decodificación binaria: se reinicia el stream
+            // en texto desde el último LSN confirmado (los eventos no confirmados se
+            // reenvían y el sink los aplica como upserts)
+            drop(replication_stream);
+            drop(source);
+            start_lsn = start_lsn.max(self.shared_state.get_confirmed_lsn());
+            source = self.init_source().await?.with_binary_tuples(false);
+            replication_stream = Box::pin(self.restart_replication(&mut source, start_lsn).await?);
+        }
+    }
+
+    /// START_REPLICATION en una conexión nueva. El walsender de la conexión
+    /// anterior puede tardar en liberar el slot, así que se reintenta.
+    async fn restart_replication(
+        &self,
+        source: &mut PostgresSource,
+        start_lsn: u64,
+    ) -> Result<CopyBothDuplex<bytes::Bytes>> {
+        let mut attempt = 1;
+        loop {
+            match source.start_replication_from(start_lsn).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) if attempt < RESTART_ATTEMPTS => {
+                    eprintln!("⚠️  Could not restart replication (attempt {}): {}", attempt, e);
+                    attempt += 1;
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
     }
 
     /// Ejecutar setup automático (PostgreSQL + StarRocks)
@@ -107,6 +175,20 @@ impl CdcEngine {
         setup_manager.run().await
     }
 
+    /// Agregar las tablas del catálogo que coinciden con los patrones de `TABLES`
+    /// (pasan por el setup de tablas nuevas). El descubrimiento corre aunque
+    /// `TABLES` no tenga patrones: `ReloadConfig` puede agregarlos.
+    async fn discover_tables(&mut self) -> Result<Arc<TableDiscovery>> {
+        let discovery = Arc::new(TableDiscovery::new(self.config.clone(), self.shared_state.clone()));
+        if self.config.table_selection.has_patterns() {
+            self.shared_state.set_stage(Stage::Setup, "Discovering tables").await;
+            let added = discovery.scan().await?;
+            println!("🔍 {} tables match {}", added.len(), self.config.table_selection);
+            self.config.tables.extend(added);
+        }
+        Ok(discovery)
+    }
+
     /// Cargar checkpoint desde StateStore
     async fn load_checkpoint(&self) -> Result<u64> {
         let last_lsn = self.state_store.load_checkpoint(&self.config.slot_name).await?;
@@ -124,13 +206,72 @@ impl CdcEngine {
         Ok(start_lsn)
     }
 
+    /// Ejecutar la carga inicial cuando el slot no existe.
+    ///
+    /// El slot se crea con `EXPORT_SNAPSHOT` siempre que falte, haya o no
+    /// checkpoint: sin slot no hay streaming posible, y los cambios desde el
+    /// checkpoint se perdieron con el slot anterior. Un slot existente sin
+    /// checkpoint indica un snapshot interrumpido (o un slot ajeno): es un error,
+    /// salvo con `RECREATE_ORPHAN_SLOT=true`. Retorna el `consistent_point`
+    /// desde el cual debe iniciar el streaming.
+    async fn run_initial_snapshot(
+        &self,
+        source: &PostgresSource,
+        checkpoint_lsn: u64,
+    ) -> Result<Option<u64>> {
+        // Sin carga inicial el setup ya creó el slot si faltaba
+        if !self.config.initial_snapshot {
+            return Ok(None);
+        }
+
+        match (source.slot_exists().await?, checkpoint_lsn > 0) {
+            (true, true) => return Ok(None),
+            (true, false) if self.config.recreate_orphan_slot => {
+                println!("⚠️  Slot {} exists without checkpoint, recreating it for the initial snapshot",
+                    self.config.slot_name);
+                source.drop_slot().await?;
+            }
+            (true, false) => {
+                return Err(anyhow!(
+                    "Slot {} exists without a checkpoint (interrupted initial snapshot?). \
+                     Drop the slot or set RECREATE_ORPHAN_SLOT=true to recreate it",
+                    self.config.slot_name
+                ));
+            }
+            (false, true) => {
+                println!("⚠️  Slot {} is missing but checkpoint is 0x{:X}: changes since the checkpoint were lost, \
+                    recreating the slot and repeating the initial snapshot",
+                    self.config.slot_name, checkpoint_lsn);
+            }
+            (false, false) => {}
+        }
+
+        self.shared_state.set_stage(Stage::Snapshot, "Creating replication slot with exported snapshot").await;
+        let slot = source.create_slot_with_snapshot().await?;
+
+        let mut sink = self.init_sink();
+        InitialSnapshot::new(&self.config, &self.shared_state)
+            .run(&slot, sink.as_mut())
+            .await?;
+
+        // Checkpoint en el consistent point: un reinicio no repite el snapshot
+        self.state_store
+            .save_checkpoint(&self.config.slot_name, slot.consistent_point)
+            .await?;
+        self.shared_state.update_lsn(slot.consistent_point);
+        self.shared_state.confirm_lsn(slot.consistent_point);
+
+        Ok(Some(slot.consistent_point))
+    }
+
     /// Iniciar servidor gRPC en background
     fn start_grpc_server(&self) {
         let grpc_state = self.shared_state.clone();
         let grpc_port = self.config.grpc_port;
+        let grpc_config = self.config.clone();
         
         tokio::spawn(async move {
-            if let Err(e) = grpc::start_grpc_server(grpc_port, grpc_state).await {
+            if let Err(e) = grpc::start_grpc_server(grpc_port, grpc_state, grpc_config).await {
                 eprintln!("gRPC server error: {}", e);
             }
         });
@@ -142,7 +283,10 @@ impl CdcEngine {
             &self.config.database_url,
             self.config.slot_name.clone(),
             self.config.publication_name.clone(),
-        ).await?;
+            self.config.two_phase,
+        ).await?
+        .with_origin_filter(self.config.origin_filter.clone())
+        .with_binary_tuples(self.config.binary_tuples);
 
         Ok(source)
     }
@@ -154,16 +298,33 @@ impl CdcEngine {
             self.config.starrocks_db.clone(),
             self.config.starrocks_user.clone(),
             self.config.starrocks_pass.clone(),
-        ))
+        )
+        .with_table_mapping(self.config.table_mapping.clone())
+        .with_truncate_policy(self.config.truncate_policy.clone())
+        .with_messages_table(self.config.messages_table.clone())
+        .with_type_mapping(self.config.type_mapping.clone())
+        .with_type_catalog(self.type_catalog.clone())
+        .with_target_timezone(self.config.target_timezone)
+        .with_masking(self.config.masking.clone()))
     }
 
     /// Inicializar pipeline y retornar canales
     fn init_pipeline(
         &self,
         sink: Box<StarRocksSink>,
-    ) -> (mpsc::Sender<crate::source::parser::CdcEvent>, mpsc::Receiver<u64>) {
-        let (tx, rx) = mpsc::channel(self.config.flush_size * 2);
+        discovery: Arc<TableDiscovery>,
+        control: ControlPosition,
+    ) -> (
+        mpsc::Sender<crate::source::parser::CdcEvent>,
+        mpsc::Receiver<u64>,
+        mpsc::Sender<SnapshotChunk>,
+    ) {
+        // CHANNEL_CAPACITY limita los eventos en tránsito hacia el pipeline y no
+        // depende de flush_size: el batch se acumula en el pipeline, así que un
+        // flush_size recargado con ReloadConfig se aplica completo.
+        let (tx, rx) = mpsc::channel(self.config.channel_capacity);
         let (feedback_tx, feedback_rx) = mpsc::channel::<u64>(100);
+        let (chunk_tx, chunk_rx) = mpsc::channel::<SnapshotChunk>(4);
         
         let pipeline = Pipeline::new(
             rx,
@@ -172,21 +333,53 @@ impl CdcEngine {
             Duration::from_millis(self.config.flush_interval_ms),
         )
         .with_feedback_channel(feedback_tx)
-        .with_shared_state(self.shared_state.clone());
+        .with_shared_state(self.shared_state.clone())
+        .with_snapshot_chunks(chunk_rx)
+        .with_txn_split_size(self.config.txn_split_size)
+        .with_origin_filter(self.config.origin_filter.clone())
+        .with_type_catalog(self.type_catalog.clone())
+        .with_column_projection(self.config.column_projection.clone())
+        .with_control_log(ControlLog::new(self.state_store.clone(), self.config.slot_name.clone(), control))
+        .with_table_discovery(discovery);
         
         tokio::spawn(pipeline.run());
 
-        (tx, feedback_rx)
+        (tx, feedback_rx, chunk_tx)
     }
 
-    /// Main loop de replicación
+    /// Iniciar el snapshotter incremental en background
+    fn start_incremental_snapshots(&self, chunk_tx: mpsc::Sender<SnapshotChunk>) {
+        let Some(requests) = self.shared_state.take_snapshot_requests() else {
+            return;
+        };
+
+        let snapshotter = IncrementalSnapshotter::new(
+            self.config.clone(),
+            self.state_store.clone(),
+            chunk_tx,
+            requests,
+        );
+        tokio::spawn(snapshotter.run());
+    }
+
+    /// Iniciar el escaneo periódico de tablas nuevas en background
+    fn start_table_discovery(&self, discovery: Arc<TableDiscovery>) {
+        if self.config.table_discovery_interval_secs == 0 {
+            return;
+        }
+        let interval = Duration::from_secs(self.config.table_discovery_interval_secs);
+        tokio::spawn(discovery.run(interval));
+    }
+
+    /// Main loop de replicación. Termina por shutdown / fin del stream o porque
+    /// hay que reiniciar el stream con tuplas en texto.
     async fn run_main_loop<S>(
         &self,
-        mut replication_stream: S,
-        tx: mpsc::Sender<crate::source::parser::CdcEvent>,
-        mut feedback_rx: mpsc::Receiver<u64>,
-        start_lsn: u64,
-    ) -> Result<()>
+        replication_stream: &mut S,
+        tx: &mpsc::Sender<crate::source::parser::CdcEvent>,
+        feedback_rx: &mut mpsc::Receiver<u64>,
+        binary: bool,
+    ) -> Result<LoopExit>
     where
         S: StreamExt<Item = Result<bytes::Bytes, tokio_postgres::Error>>
             + SinkExt<bytes::Bytes>
@@ -195,6 +388,9 @@ impl CdcEngine {
     {
         let mut shutdown_rx = self.shared_state.shutdown_tx.subscribe();
         let mut iteration = 0u64;
+        let mut decoder = WalDecoder::new()
+            .with_spill(self.config.stream_spill_threshold, self.config.stream_spill_dir.clone())
+            .with_binary(binary);
 
         loop {
             iteration = iteration.wrapping_add(1);
@@ -202,7 +398,7 @@ impl CdcEngine {
             // 1. Check state changes cada 256 iteraciones para reducir overhead
             // Con ~287 eventos/s, esto verifica estado ~1x/segundo en lugar de 287x/segundo
             if iteration & 0xFF == 0 {
-                if let Some(flow) = self.check_state_control_sync(&tx) {
+                if let Some(flow) = self.check_state_control_sync(tx) {
                     match flow {
                         ControlFlow::Break => break,
                         ControlFlow::Continue => {
@@ -231,9 +427,13 @@ impl CdcEngine {
                             if let Some(msg) = parse_replication_message(&mut data) {
                                 let _ = self.handle_replication_message(
                                     msg,
-                                    &tx,
-                                    &mut replication_stream,
+                                    tx,
+                                    replication_stream,
+                                    &mut decoder,
                                 ).await?;
+                                if decoder.text_required() {
+                                    return Ok(LoopExit::TextTuples);
+                                }
                             }
                         }
                         Some(Err(e)) => {
@@ -250,15 +450,15 @@ impl CdcEngine {
                 // Checkpoint feedback
                 Some(confirmed_lsn) = feedback_rx.recv() => {
                     self.handle_checkpoint_feedback(
-                        confirmed_lsn,
-                        &mut replication_stream,
+                        decoder.clamp_checkpoint(confirmed_lsn),
+                        replication_stream,
                     ).await?;
                 }
             }
         }
 
         println!("CDC shutdown complete");
-        Ok(())
+        Ok(LoopExit::Stopped)
     }
 
     /// Verificar estado del CDC (Pause/Stop/Draining) - Sincrono
@@ -275,7 +475,7 @@ impl CdcEngine {
             }
             CdcState::Draining => {
                 // Check if channel is empty
-                if tx.capacity() == self.config.flush_size * 2 {
+                if tx.capacity() == tx.max_capacity() {
                     println!("CDC drained. Exiting gracefully.");
                     self.shared_state.set_state(CdcState::Stopped);
                     Some(ControlFlow::Break)
@@ -297,6 +497,7 @@ impl CdcEngine {
         msg: WalMessage,
         tx: &mpsc::Sender<crate::source::parser::CdcEvent>,
         replication_stream: &mut S,
+        decoder: &mut WalDecoder,
     ) -> Result<u64>
     where
         S: SinkExt<bytes::Bytes> + Unpin,
@@ -309,12 +510,14 @@ impl CdcEngine {
                     lsn,
                     tx,
                     &self.shared_state,
-                    self.config.flush_size,
+                    decoder,
                 ).await?;
                 Ok(lsn)
             }
             WalMessage::KeepAlive { lsn, reply_requested } => {
-                handle_keepalive(lsn, reply_requested, replication_stream).await?;
+                // Responder con el LSN confirmado en el sink, no con el wal_end del servidor
+                let confirmed_lsn = self.shared_state.get_confirmed_lsn();
+                handle_keepalive(confirmed_lsn, reply_requested, replication_stream).await?;
                 Ok(lsn)
             }
             WalMessage::Unknown(tag) => {
@@ -364,3 +567,12 @@ enum ControlFlow {
     Break,
 }
 
+/// Motivo de salida del main loop
+#[derive(Debug, PartialEq, Eq)]
+enum LoopExit {
+    /// Shutdown, stop del control plane o fin del stream
+    Stopped,
+    /// Reiniciar el stream sin `binary 'true'`
+    TextTuples,
+}
+
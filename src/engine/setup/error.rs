@@ -8,12 +8,19 @@ pub enum SetupError {
     PgTableNotFound { table: String },
     PgReplicaIdentityFailed { table: String, error: String },
     PgPublicationFailed { name: String, error: String },
+    PgColumnListFailed { table: String, error: String },
+    PgFanInIncompatible { table: String, error: String },
     PgSlotFailed { name: String, error: String },
+    PgSignalTableFailed { error: String },
     
     // StarRocks
     SrConnectionFailed { host: String, error: String },
     SrTableNotFound { table: String },
+    SrTableCreateFailed { table: String, error: String },
     SrAuditColumnsFailed { table: String, error: String },
+    SrMessagesTableFailed { table: String, error: String },
+    SrTruncatePolicyUnsupported { table: String, model: String },
+    SrFanInKeyMissing { table: String, column: String },
     
     // General
     CheckpointFailed { error: String },
@@ -30,23 +37,50 @@ impl SetupError {
                 format!("Table '{}' not found in PostgreSQL. Verify the table exists and is accessible.", table)
             }
             SetupError::PgReplicaIdentityFailed { table, error } => {
-                format!("Failed to set REPLICA IDENTITY FULL on '{}': {}", table, error)
+                format!("Failed to set REPLICA IDENTITY on '{}': {}", table, error)
             }
             SetupError::PgPublicationFailed { name, error } => {
                 format!("Failed to setup publication '{}': {}", name, error)
             }
+            SetupError::PgColumnListFailed { table, error } => {
+                format!("Invalid column list for '{}': {}", table, error)
+            }
+            SetupError::PgFanInIncompatible { table, error } => {
+                format!("Source tables of fan-in table '{}' are incompatible: {}", table, error)
+            }
             SetupError::PgSlotFailed { name, error } => {
                 format!("Failed to setup replication slot '{}': {}", name, error)
             }
+            SetupError::PgSignalTableFailed { error } => {
+                format!("Failed to create signal table: {}", error)
+            }
             SetupError::SrConnectionFailed { host, error } => {
                 format!("StarRocks connection failed to '{}': {}", host, error)
             }
             SetupError::SrTableNotFound { table } => {
                 format!("Table '{}' not found in StarRocks. Create the table before starting CDC.", table)
             }
+            SetupError::SrTableCreateFailed { table, error } => {
+                format!("Failed to create StarRocks table '{}': {}", table, error)
+            }
             SetupError::SrAuditColumnsFailed { table, error } => {
                 format!("Failed to add audit columns to StarRocks table '{}': {}", table, error)
             }
+            SetupError::SrMessagesTableFailed { table, error } => {
+                format!("Failed to create StarRocks messages table '{}': {}", table, error)
+            }
+            SetupError::SrTruncatePolicyUnsupported { table, model } => {
+                format!(
+                    "TRUNCATE_POLICY soft_delete needs a PRIMARY KEY table, but StarRocks table '{}' is {}. Use truncate or ignore for it.",
+                    table, model
+                )
+            }
+            SetupError::SrFanInKeyMissing { table, column } => {
+                format!(
+                    "Fan-in table '{}' needs '{}' in its PRIMARY KEY or UNIQUE KEY (recreate it with that column in the key)",
+                    table, column
+                )
+            }
             SetupError::CheckpointFailed { error } => {
                 format!("Checkpoint load failed: {}", error)
             }
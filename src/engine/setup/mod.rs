@@ -36,6 +36,30 @@ impl SetupManager {
         Ok(())
     }
 
+    /// Setup de una tabla agregada en caliente. StarRocks se valida primero para no
+    /// publicar en PostgreSQL una tabla que no tiene destino.
+    pub async fn setup_table(&self, table: &str) -> Result<(), SetupError> {
+        println!("🔧 Setup for new table {}:", table);
+
+        let pool = starrocks::create_starrocks_pool(&self.config)?;
+        starrocks::StarRocksSetup::new(&pool, &self.config)
+            .setup_table(table)
+            .await?;
+
+        let client = postgres::create_postgres_client(&self.config.database_url).await?;
+        postgres::PostgresSetup::new(&client, &self.config)
+            .setup_table(table)
+            .await
+    }
+
+    /// Quitar de la publication una tabla removida en caliente
+    pub async fn remove_table(&self, table: &str) -> Result<(), SetupError> {
+        let client = postgres::create_postgres_client(&self.config.database_url).await?;
+        postgres::PostgresSetup::new(&client, &self.config)
+            .remove_table(table)
+            .await
+    }
+
     /// Setup de PostgreSQL
     async fn setup_postgres(&self) -> Result<(), SetupError> {
         let client = postgres::create_postgres_client(&self.config.database_url).await?;
@@ -1,8 +1,10 @@
 use anyhow::Result;
+use std::collections::HashMap;
 use tokio_postgres::{Client, NoTls};
 
 use super::error::SetupError;
 use crate::config::Config;
+use crate::source::snapshot::{quote_ident, SIGNAL_TABLE};
 
 pub struct PostgresSetup<'a> {
     client: &'a Client,
@@ -20,101 +22,555 @@ impl<'a> PostgresSetup<'a> {
         
         // 1. Verificar que las tablas existen
         self.verify_tables_exist().await?;
+        self.verify_fan_in_compatibility().await?;
         
-        // 2. Configurar REPLICA IDENTITY FULL
-        self.ensure_replica_identity().await?;
+        // 2. Configurar REPLICA IDENTITY (FULL, o la PK en tablas con column
+        //    list y en tablas particionadas)
+        let column_lists = self.supports_column_lists().await?;
+        if !self.config.column_projection.is_empty() {
+            if column_lists {
+                println!("  ✓ Include lists published as publication column lists, exclude lists filtered by the pipeline");
+            } else {
+                println!("  ⚠️  PostgreSQL < 15: excluded columns are filtered by the pipeline");
+            }
+        }
+        self.ensure_replica_identity(column_lists).await?;
+        
+        // 3. Crear tabla de señales (watermarks de snapshots)
+        self.ensure_signal_table().await?;
         
-        // 3. Crear/verificar Publication
-        self.ensure_publication().await?;
+        // 4. Crear/verificar Publication (vía la raíz si hay tablas particionadas).
+        //    Las tablas de los patrones de TABLES las agrega el descubrimiento.
+        let via_root = self.needs_publish_via_root().await?;
+        self.ensure_publication(column_lists, via_root).await?;
         
-        // 4. Crear/verificar Replication Slot
+        // 5. Crear/verificar Replication Slot
         self.ensure_replication_slot().await?;
         
         println!("✅ PostgreSQL setup complete");
         Ok(())
     }
 
+    /// Setup de una tabla agregada en caliente (ReloadConfig): verifica que existe,
+    /// configura REPLICA IDENTITY y la agrega a la publication
+    pub async fn setup_table(&self, table: &str) -> Result<(), SetupError> {
+        self.verify_table_exists(table).await?;
+        self.verify_column_list(table).await?;
+        let column_lists = self.supports_column_lists().await?;
+        self.ensure_table_replica_identity(table, column_lists).await?;
+
+        let pub_name = &self.config.publication_name;
+        if self.is_partitioned(table).await? {
+            self.ensure_publish_via_root(pub_name).await?;
+        }
+        let missing = self
+            .get_missing_tables_in_publication(pub_name, vec![table.to_string()])
+            .await?;
+        for table in missing {
+            self.add_table_to_publication(pub_name, &table, column_lists).await?;
+        }
+        Ok(())
+    }
+
+    /// Quitar de la publication una tabla removida en caliente (ReloadConfig), así
+    /// PostgreSQL deja de enviar sus cambios. Una tabla incluida por su schema o por
+    /// `FOR ALL TABLES` no se puede quitar sola: la descarta el pipeline.
+    pub async fn remove_table(&self, table: &str) -> Result<(), SetupError> {
+        let pub_name = &self.config.publication_name;
+        let (schema, name) = split_table(table);
+        let listed: bool = self.client
+            .query_one(
+                "SELECT EXISTS (
+                    SELECT 1 FROM pg_publication_rel pr
+                    JOIN pg_publication p ON p.oid = pr.prpubid
+                    JOIN pg_class c ON c.oid = pr.prrelid
+                    JOIN pg_namespace n ON n.oid = c.relnamespace
+                    WHERE p.pubname = $1 AND n.nspname = $2 AND c.relname = $3
+                )",
+                &[&pub_name, &schema, &name],
+            )
+            .await
+            .map_err(|e| SetupError::PgPublicationFailed {
+                name: pub_name.clone(),
+                error: e.to_string(),
+            })?
+            .get(0);
+
+        if !listed {
+            println!("  ✓ {} is not listed in publication {} (filtered by the pipeline)", table, pub_name);
+            return Ok(());
+        }
+
+        self.client
+            .execute(
+                &format!("ALTER PUBLICATION {} DROP TABLE {}.{}", pub_name, quote_ident(schema), quote_ident(name)),
+                &[],
+            )
+            .await
+            .map_err(|e| SetupError::PgPublicationFailed {
+                name: pub_name.clone(),
+                error: e.to_string(),
+            })?;
+        println!("  ✅ Table {} removed from publication {}", table, pub_name);
+        Ok(())
+    }
+
     /// Verificar que todas las tablas existen
     async fn verify_tables_exist(&self) -> Result<(), SetupError> {
         for table in &self.config.tables {
-            let parts: Vec<&str> = table.split('.').collect();
-            let schema = if parts.len() > 1 { parts[0] } else { "public" };
-            let table_name = if parts.len() > 1 { parts[1] } else { parts[0] };
-
-            let exists: bool = self.client
-                .query_one(
-                    "SELECT EXISTS (
-                        SELECT FROM information_schema.tables 
-                        WHERE table_schema = $1 AND table_name = $2
-                    )",
-                    &[&schema, &table_name],
+            self.verify_table_exists(table).await?;
+            self.verify_column_list(table).await?;
+        }
+        Ok(())
+    }
+
+    /// Una lista include/exclude tiene que conservar la PK, se publique como
+    /// column list (PG15+) o la filtre el pipeline: sin ella StarRocks no puede
+    /// aplicar los UPDATE y DELETE
+    async fn verify_column_list(&self, table: &str) -> Result<(), SetupError> {
+        if self.is_projected(table) {
+            self.publication_columns(table).await?;
+        }
+        Ok(())
+    }
+
+    /// Verificar que una tabla existe
+    async fn verify_table_exists(&self, table: &str) -> Result<(), SetupError> {
+        let parts: Vec<&str> = table.split('.').collect();
+        let schema = if parts.len() > 1 { parts[0] } else { "public" };
+        let table_name = if parts.len() > 1 { parts[1] } else { parts[0] };
+
+        let exists: bool = self.client
+            .query_one(
+                "SELECT EXISTS (
+                    SELECT FROM information_schema.tables 
+                    WHERE table_schema = $1 AND table_name = $2
+                )",
+                &[&schema, &table_name],
+            )
+            .await
+            .map_err(|e| SetupError::PgConnectionFailed {
+                host: "PostgreSQL".to_string(),
+                error: e.to_string(),
+            })?
+            .get(0);
+
+        if !exists {
+            return Err(SetupError::PgTableNotFound {
+                table: table.to_string(),
+            });
+        }
+        
+        println!("  ✓ Table {} exists", table);
+        Ok(())
+    }
+
+    /// Las tablas origen de una misma tabla fan-in tienen que coincidir en el
+    /// tipo de las columnas que comparten
+    async fn verify_fan_in_compatibility(&self) -> Result<(), SetupError> {
+        let mapping = &self.config.table_mapping;
+        let mut targets: HashMap<String, Vec<&String>> = HashMap::new();
+        for table in &self.config.tables {
+            if mapping.source_column_qualified(table).is_some() {
+                targets.entry(mapping.resolve_qualified(table).to_string()).or_default().push(table);
+            }
+        }
+
+        for (target, tables) in targets {
+            // columna → (tabla, tipo) de la primera tabla que la tiene
+            let mut types: HashMap<String, (&String, String)> = HashMap::new();
+            for table in &tables {
+                let (schema, table_name) = split_table(table);
+                let rows = self.client
+                    .query(
+                        "SELECT attname, format_type(atttypid, atttypmod)
+                         FROM pg_attribute
+                         WHERE attrelid = ($1::text)::regclass AND attnum > 0 AND NOT attisdropped",
+                        &[&format!("{}.{}", schema, table_name)],
+                    )
+                    .await
+                    .map_err(|e| SetupError::PgConnectionFailed {
+                        host: "PostgreSQL".to_string(),
+                        error: e.to_string(),
+                    })?;
+
+                for row in rows {
+                    let column: String = row.get(0);
+                    let pg_type: String = row.get(1);
+                    match types.get(&column) {
+                        Some((other, other_type)) if *other_type != pg_type => {
+                            return Err(SetupError::PgFanInIncompatible {
+                                table: target,
+                                error: format!(
+                                    "column '{}' is {} in {} but {} in {}",
+                                    column, pg_type, table, other_type, other
+                                ),
+                            });
+                        }
+                        Some(_) => {}
+                        None => {
+                            types.insert(column, (table, pg_type));
+                        }
+                    }
+                }
+            }
+            println!("  ✓ Fan-in table {} ← {} source tables", target, tables.len());
+        }
+        Ok(())
+    }
+
+    /// Configurar REPLICA IDENTITY en todas las tablas
+    async fn ensure_replica_identity(&self, column_lists: bool) -> Result<(), SetupError> {
+        for table in &self.config.tables {
+            self.ensure_table_replica_identity(table, column_lists).await?;
+        }
+        Ok(())
+    }
+
+    /// Configurar REPLICA IDENTITY FULL en una tabla. Una column list tiene que
+    /// cubrir la identidad, así que las tablas proyectadas en PG15+ usan la PK.
+    async fn ensure_table_replica_identity(&self, table: &str, column_lists: bool) -> Result<(), SetupError> {
+        let parts: Vec<&str> = table.split('.').collect();
+        let schema = if parts.len() > 1 { parts[0] } else { "public" };
+        let table_name = if parts.len() > 1 { parts[1] } else { parts[0] };
+
+        // Consultar estado actual
+        let row = self.client
+            .query_one(
+                "SELECT c.relreplident 
+                 FROM pg_class c 
+                 JOIN pg_namespace n ON c.relnamespace = n.oid 
+                 WHERE c.relname = $1 AND n.nspname = $2",
+                &[&table_name, &schema],
+            )
+            .await
+            .map_err(|e| SetupError::PgConnectionFailed {
+                host: "PostgreSQL".to_string(),
+                error: e.to_string(),
+            })?;
+
+        let replica_identity: i8 = row.get(0);
+        let identity_char = replica_identity as u8 as char;
+        // Las particiones nuevas nacen con DEFAULT: la raíz usa la misma identidad
+        // para que los DELETE de todas las particiones lleguen igual (solo la PK)
+        let partitioned = self.is_partitioned(table).await?;
+        if partitioned && !self.has_primary_key(table).await? {
+            return Err(SetupError::PgReplicaIdentityFailed {
+                table: table.to_string(),
+                error: "partitioned tables need a primary key".to_string(),
+            });
+        }
+        let (target, identity) = if partitioned || (column_lists && self.has_column_list(table)) {
+            ('d', "DEFAULT")
+        } else {
+            ('f', "FULL")
+        };
+
+        // Si no es la esperada, configurar
+        if identity_char != target {
+            println!("  🔧 Setting REPLICA IDENTITY {} on {}", identity, table);
+            self.client
+                .execute(
+                    &format!("ALTER TABLE {} REPLICA IDENTITY {}", table, identity),
+                    &[],
                 )
                 .await
-                .map_err(|e| SetupError::PgConnectionFailed {
-                    host: "PostgreSQL".to_string(),
+                .map_err(|e| SetupError::PgReplicaIdentityFailed {
+                    table: table.to_string(),
                     error: e.to_string(),
-                })?
-                .get(0);
+                })?;
+            println!("  ✅ REPLICA IDENTITY {} set on {}", identity, table);
+        } else {
+            println!("  ✓ {} already has REPLICA IDENTITY {}", table, identity);
+        }
+        Ok(())
+    }
 
-            if !exists {
-                return Err(SetupError::PgTableNotFound {
-                    table: table.clone(),
-                });
+    /// Crear tabla de señales usada por los snapshots incrementales
+    async fn ensure_signal_table(&self) -> Result<(), SetupError> {
+        self.client
+            .execute(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS public.{} (
+                        id TEXT PRIMARY KEY,
+                        type TEXT NOT NULL,
+                        data TEXT,
+                        created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+                    )",
+                    SIGNAL_TABLE
+                ),
+                &[],
+            )
+            .await
+            .map_err(|e| SetupError::PgSignalTableFailed {
+                error: e.to_string(),
+            })?;
+
+        println!("  ✓ Signal table {} ready", SIGNAL_TABLE);
+        Ok(())
+    }
+
+    /// Tablas que deben estar en la publication (configuradas + señales)
+    fn published_tables(&self) -> Vec<String> {
+        let mut tables = self.config.tables.clone();
+        tables.push(format!("public.{}", SIGNAL_TABLE));
+        tables
+    }
+
+    /// Column lists en la publication (PostgreSQL 15+)
+    async fn supports_column_lists(&self) -> Result<bool, SetupError> {
+        Ok(self.server_version().await? >= 150000)
+    }
+
+    /// `server_version_num` de PostgreSQL (ej. 150004)
+    async fn server_version(&self) -> Result<i32, SetupError> {
+        let version: i32 = self.client
+            .query_one("SELECT current_setting('server_version_num')::int", &[])
+            .await
+            .map_err(|e| SetupError::PgConnectionFailed {
+                host: "PostgreSQL".to_string(),
+                error: e.to_string(),
+            })?
+            .get(0);
+        Ok(version)
+    }
+
+    /// Indica si la tabla es una tabla particionada (declarativa)
+    async fn is_partitioned(&self, table: &str) -> Result<bool, SetupError> {
+        let (schema, table_name) = split_table(table);
+        let partitioned: bool = self.client
+            .query_one(
+                "SELECT c.relkind = 'p'
+                 FROM pg_class c
+                 JOIN pg_namespace n ON c.relnamespace = n.oid
+                 WHERE c.relname = $1 AND n.nspname = $2",
+                &[&table_name, &schema],
+            )
+            .await
+            .map_err(|e| SetupError::PgConnectionFailed {
+                host: "PostgreSQL".to_string(),
+                error: e.to_string(),
+            })?
+            .get(0);
+        Ok(partitioned)
+    }
+
+    /// Indica si la tabla tiene primary key
+    async fn has_primary_key(&self, table: &str) -> Result<bool, SetupError> {
+        let (schema, table_name) = split_table(table);
+        let has_pk: bool = self.client
+            .query_one(
+                "SELECT EXISTS (
+                    SELECT 1 FROM pg_index
+                    WHERE indrelid = ($1::text)::regclass AND indisprimary
+                )",
+                &[&format!("{}.{}", schema, table_name)],
+            )
+            .await
+            .map_err(|e| SetupError::PgConnectionFailed {
+                host: "PostgreSQL".to_string(),
+                error: e.to_string(),
+            })?
+            .get(0);
+        Ok(has_pk)
+    }
+
+    /// Indica si la publication necesita `publish_via_partition_root`: alguna
+    /// tabla configurada es particionada o `TABLES` tiene patrones (una tabla
+    /// particionada puede aparecer después). Con la opción, los cambios de las
+    /// particiones (también las que se agreguen después) llegan con el relation
+    /// id y el nombre de la raíz.
+    async fn needs_publish_via_root(&self) -> Result<bool, SetupError> {
+        let mut partitioned = Vec::new();
+        for table in &self.config.tables {
+            if self.is_partitioned(table).await? {
+                partitioned.push(table.as_str());
             }
-            
-            println!("  ✓ Table {} exists", table);
         }
+        if partitioned.is_empty() {
+            return Ok(self.config.table_selection.has_patterns() && self.server_version().await? >= 130000);
+        }
+
+        if self.server_version().await? < 130000 {
+            return Err(SetupError::PgPublicationFailed {
+                name: self.config.publication_name.clone(),
+                error: format!(
+                    "partitioned tables ({}) need PostgreSQL 13+ (publish_via_partition_root)",
+                    partitioned.join(", ")
+                ),
+            });
+        }
+        println!("  ✓ Partitioned tables: {}", partitioned.join(", "));
+        Ok(true)
+    }
+
+    /// Activar `publish_via_partition_root` en una publication existente y
+    /// verificar que quedó activo
+    async fn ensure_publish_via_root(&self, pub_name: &str) -> Result<(), SetupError> {
+        if self.publishes_via_root(pub_name).await? {
+            println!("  ✓ Publication {} publishes via partition root", pub_name);
+            return Ok(());
+        }
+
+        println!("  🔧 Setting publish_via_partition_root on {}", pub_name);
+        self.client
+            .execute(
+                &format!("ALTER PUBLICATION {} SET (publish_via_partition_root = true)", pub_name),
+                &[],
+            )
+            .await
+            .map_err(|e| SetupError::PgPublicationFailed {
+                name: pub_name.to_string(),
+                error: e.to_string(),
+            })?;
+
+        if !self.publishes_via_root(pub_name).await? {
+            return Err(SetupError::PgPublicationFailed {
+                name: pub_name.to_string(),
+                error: "publish_via_partition_root could not be enabled".to_string(),
+            });
+        }
+        println!("  ✅ publish_via_partition_root enabled on {}", pub_name);
         Ok(())
     }
 
-    /// Configurar REPLICA IDENTITY FULL en todas las tablas
-    async fn ensure_replica_identity(&self) -> Result<(), SetupError> {
-        for table in &self.config.tables {
-            let parts: Vec<&str> = table.split('.').collect();
-            let schema = if parts.len() > 1 { parts[0] } else { "public" };
-            let table_name = if parts.len() > 1 { parts[1] } else { parts[0] };
+    /// Valor de `publish_via_partition_root` de la publication
+    async fn publishes_via_root(&self, pub_name: &str) -> Result<bool, SetupError> {
+        let via_root: bool = self.client
+            .query_one("SELECT pubviaroot FROM pg_publication WHERE pubname = $1", &[&pub_name])
+            .await
+            .map_err(|e| SetupError::PgPublicationFailed {
+                name: pub_name.to_string(),
+                error: e.to_string(),
+            })?
+            .get(0);
+        Ok(via_root)
+    }
+
+    /// Indica si la tabla tiene una lista include/exclude configurada
+    fn is_projected(&self, table: &str) -> bool {
+        let (schema, table_name) = split_table(table);
+        self.config.column_projection.has_table(schema, table_name)
+    }
 
-            // Consultar estado actual
+    /// Indica si la tabla se publica con column list (solo listas include: con
+    /// exclude las columnas nuevas tienen que seguir llegando)
+    fn has_column_list(&self, table: &str) -> bool {
+        let (schema, table_name) = split_table(table);
+        self.config.column_projection.has_include_list(schema, table_name)
+    }
+
+    /// Columnas replicadas de una tabla, en el orden de `pgoutput`. La PK no
+    /// se puede excluir: es la replica identity de las tablas proyectadas.
+    async fn publication_columns(&self, table: &str) -> Result<Vec<String>, SetupError> {
+        let (schema, table_name) = split_table(table);
+        let rows = self.client
+            .query(
+                "SELECT a.attname, COALESCE(a.attnum = ANY(i.indkey), false) AS is_pk
+                 FROM pg_attribute a
+                 LEFT JOIN pg_index i ON i.indrelid = a.attrelid AND i.indisprimary
+                 WHERE a.attrelid = ($1::text)::regclass AND a.attnum > 0
+                   AND NOT a.attisdropped AND a.attgenerated = ''
+                 ORDER BY a.attnum",
+                &[&format!("{}.{}", schema, table_name)],
+            )
+            .await
+            .map_err(|e| SetupError::PgColumnListFailed {
+                table: table.to_string(),
+                error: e.to_string(),
+            })?;
+
+        let projection = &self.config.column_projection;
+        let projected = projection.has_table(schema, table_name);
+        let mut columns = Vec::with_capacity(rows.len());
+        let mut has_pk = false;
+        for row in rows {
+            let name: String = row.get(0);
+            let is_pk: bool = row.get(1);
+            has_pk |= is_pk;
+            if projection.keeps(schema, table_name, &name) {
+                columns.push(name);
+            } else if is_pk {
+                return Err(SetupError::PgColumnListFailed {
+                    table: table.to_string(),
+                    error: format!("primary key column '{}' cannot be excluded", name),
+                });
+            }
+        }
+
+        if projected && !has_pk {
+            return Err(SetupError::PgColumnListFailed {
+                table: table.to_string(),
+                error: "tables with a column list need a primary key".to_string(),
+            });
+        }
+        Ok(columns)
+    }
+
+    /// `tabla` o `tabla (col1, col2)` con la column list de una lista include
+    async fn publication_table(&self, table: &str, column_lists: bool) -> Result<String, SetupError> {
+        if !column_lists || !self.has_column_list(table) {
+            return Ok(table.to_string());
+        }
+        let columns = self.publication_columns(table).await?;
+        Ok(format!("{} ({})", table, columns.join(", ")))
+    }
+
+    /// Actualizar las column lists que no coinciden con la config (listas
+    /// include cambiadas o removidas, o una column list de una versión anterior
+    /// en una tabla que ahora tiene exclude)
+    async fn sync_column_lists(&self, pub_name: &str) -> Result<(), SetupError> {
+        for table in &self.config.tables {
+            let (schema, table_name) = split_table(table);
+            // prattrs es NULL si la tabla se publica sin column list
             let row = self.client
-                .query_one(
-                    "SELECT c.relreplident 
-                     FROM pg_class c 
-                     JOIN pg_namespace n ON c.relnamespace = n.oid 
-                     WHERE c.relname = $1 AND n.nspname = $2",
-                    &[&table_name, &schema],
+                .query_opt(
+                    "SELECT pt.attnames::text[], pr.prattrs IS NOT NULL
+                     FROM pg_publication_tables pt
+                     JOIN pg_publication p ON p.pubname = pt.pubname
+                     LEFT JOIN pg_publication_rel pr ON pr.prpubid = p.oid
+                       AND pr.prrelid = (quote_ident(pt.schemaname) || '.' || quote_ident(pt.tablename))::regclass
+                     WHERE pt.pubname = $1 AND pt.schemaname = $2 AND pt.tablename = $3",
+                    &[&pub_name, &schema, &table_name],
                 )
                 .await
-                .map_err(|e| SetupError::PgConnectionFailed {
-                    host: "PostgreSQL".to_string(),
+                .map_err(|e| SetupError::PgPublicationFailed {
+                    name: pub_name.to_string(),
                     error: e.to_string(),
                 })?;
 
-            let replica_identity: i8 = row.get(0);
-            let identity_char = replica_identity as u8 as char;
-
-            // Si no es FULL, configurar
-            if identity_char != 'f' {
-                println!("  🔧 Setting REPLICA IDENTITY FULL on {}", table);
-                self.client
-                    .execute(
-                        &format!("ALTER TABLE {} REPLICA IDENTITY FULL", table),
-                        &[],
-                    )
-                    .await
-                    .map_err(|e| SetupError::PgReplicaIdentityFailed {
-                        table: table.clone(),
-                        error: e.to_string(),
-                    })?;
-                println!("  ✅ REPLICA IDENTITY FULL set on {}", table);
+            let Some(row) = row else {
+                continue;
+            };
+            let published: Option<Vec<String>> = row.get::<_, bool>(1).then(|| row.get(0));
+            let expected = if self.has_column_list(table) {
+                Some(self.publication_columns(table).await?)
             } else {
-                println!("  ✓ {} already has REPLICA IDENTITY FULL", table);
+                None
+            };
+            if published == expected {
+                continue;
             }
+
+            println!("  🔧 Updating column list of {} in publication {}", table, pub_name);
+            let spec = self.publication_table(table, true).await?;
+            self.client
+                .batch_execute(&format!(
+                    "BEGIN; ALTER PUBLICATION {pub_name} DROP TABLE {table}; \
+                     ALTER PUBLICATION {pub_name} ADD TABLE {spec}; COMMIT",
+                ))
+                .await
+                .map_err(|e| SetupError::PgPublicationFailed {
+                    name: pub_name.to_string(),
+                    error: e.to_string(),
+                })?;
+            println!("  ✅ Column list of {} updated", table);
         }
         Ok(())
     }
 
     /// Crear/verificar Publication
-    async fn ensure_publication(&self) -> Result<(), SetupError> {
+    async fn ensure_publication(&self, column_lists: bool, via_root: bool) -> Result<(), SetupError> {
         let pub_name = &self.config.publication_name;
 
         // Verificar si existe
@@ -133,30 +589,35 @@ impl<'a> PostgresSetup<'a> {
         if exists {
             println!("  ✓ Publication {} exists", pub_name);
             
+            // Antes de comparar tablas: sin esta opción pg_publication_tables
+            // lista las particiones en lugar de la tabla raíz
+            if via_root {
+                self.ensure_publish_via_root(pub_name).await?;
+            }
+            
             // Verificar que incluye todas las tablas
-            let missing = self.get_missing_tables_in_publication(pub_name).await?;
+            let missing = self
+                .get_missing_tables_in_publication(pub_name, self.published_tables())
+                .await?;
             
             for table in missing {
-                println!("  🔧 Adding {} to publication {}", table, pub_name);
-                self.client
-                    .execute(
-                        &format!("ALTER PUBLICATION {} ADD TABLE {}", pub_name, table),
-                        &[],
-                    )
-                    .await
-                    .map_err(|e| SetupError::PgPublicationFailed {
-                        name: pub_name.clone(),
-                        error: e.to_string(),
-                    })?;
-                println!("  ✅ Table {} added to publication", table);
+                self.add_table_to_publication(pub_name, &table, column_lists).await?;
+            }
+            
+            if column_lists {
+                self.sync_column_lists(pub_name).await?;
             }
         } else {
             // Crear nueva publication
             println!("  🔧 Creating publication {}", pub_name);
-            let tables = self.config.tables.join(", ");
+            let mut tables = Vec::new();
+            for table in self.published_tables() {
+                tables.push(self.publication_table(&table, column_lists).await?);
+            }
+            let options = if via_root { " WITH (publish_via_partition_root = true)" } else { "" };
             self.client
                 .execute(
-                    &format!("CREATE PUBLICATION {} FOR TABLE {}", pub_name, tables),
+                    &format!("CREATE PUBLICATION {} FOR TABLE {}{}", pub_name, tables.join(", "), options),
                     &[],
                 )
                 .await
@@ -170,10 +631,29 @@ impl<'a> PostgresSetup<'a> {
         Ok(())
     }
 
-    /// Obtener tablas que faltan en la publication
+    /// Agregar una tabla a una publication existente
+    async fn add_table_to_publication(&self, pub_name: &str, table: &str, column_lists: bool) -> Result<(), SetupError> {
+        println!("  🔧 Adding {} to publication {}", table, pub_name);
+        let spec = self.publication_table(table, column_lists).await?;
+        self.client
+            .execute(
+                &format!("ALTER PUBLICATION {} ADD TABLE {}", pub_name, spec),
+                &[],
+            )
+            .await
+            .map_err(|e| SetupError::PgPublicationFailed {
+                name: pub_name.to_string(),
+                error: e.to_string(),
+            })?;
+        println!("  ✅ Table {} added to publication", table);
+        Ok(())
+    }
+
+    /// Obtener cuáles de `tables` faltan en la publication
     async fn get_missing_tables_in_publication(
         &self,
         pub_name: &str,
+        tables: Vec<String>,
     ) -> Result<Vec<String>, SetupError> {
         let rows = self.client
             .query(
@@ -190,8 +670,8 @@ impl<'a> PostgresSetup<'a> {
 
         let existing: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
         
-        let missing: Vec<String> = self.config.tables
-            .iter()
+        let missing: Vec<String> = tables
+            .into_iter()
             .filter(|table| {
                 // Normalizar nombres para comparación
                 let normalized = if table.contains('.') {
@@ -201,7 +681,6 @@ impl<'a> PostgresSetup<'a> {
                 };
                 !existing.contains(&normalized) && !existing.contains(table)
             })
-            .cloned()
             .collect();
 
         Ok(missing)
@@ -226,11 +705,19 @@ impl<'a> PostgresSetup<'a> {
 
         if exists {
             println!("  ✓ Replication slot {} exists (recovery mode)", slot_name);
+        } else if self.config.initial_snapshot {
+            // El engine lo crea con EXPORT_SNAPSHOT para la carga inicial
+            println!("  ✓ Replication slot {} will be created with the initial snapshot", slot_name);
         } else {
             println!("  🔧 Creating replication slot {}", slot_name);
             self.client
                 .execute(
-                    "SELECT pg_create_logical_replication_slot($1, 'pgoutput')",
+                    // El argumento twophase solo existe en PG14+
+                    if self.config.two_phase {
+                        "SELECT pg_create_logical_replication_slot($1, 'pgoutput', false, true)"
+                    } else {
+                        "SELECT pg_create_logical_replication_slot($1, 'pgoutput')"
+                    },
                     &[&slot_name],
                 )
                 .await
@@ -245,6 +732,11 @@ impl<'a> PostgresSetup<'a> {
     }
 }
 
+/// `schema.tabla` → (schema, tabla); sin schema es `public`
+fn split_table(table: &str) -> (&str, &str) {
+    table.split_once('.').unwrap_or(("public", table))
+}
+
 /// Helper para crear cliente PostgreSQL normal (no replicación)
 pub async fn create_postgres_client(database_url: &str) -> Result<Client, SetupError> {
     // Remover parámetro de replicación para conexión normal
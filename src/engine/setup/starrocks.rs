@@ -2,7 +2,12 @@ use anyhow::Result;
 use mysql_async::{Pool, Conn, prelude::Queryable};
 
 use super::error::SetupError;
+use super::postgres::create_postgres_client;
 use crate::config::Config;
+use crate::engine::discovery::NewTablePolicy;
+use crate::sink::table_map::TargetTable;
+use crate::sink::truncate::TruncatePolicy;
+use crate::source::type_catalog::TypeCatalog;
 
 /// Columnas de auditoría CDC que deben existir en StarRocks
 const AUDIT_COLUMNS: &[(&str, &str)] = &[
@@ -12,6 +17,7 @@ const AUDIT_COLUMNS: &[(&str, &str)] = &[
     ("dbmazz_cdc_version", "BIGINT COMMENT 'LSN PostgreSQL'"),
 ];
 
+
 pub struct StarRocksSetup<'a> {
     pool: &'a Pool,
     config: &'a Config,
@@ -29,16 +35,37 @@ impl<'a> StarRocksSetup<'a> {
         // 1. Verificar conectividad
         self.verify_connection().await?;
         
-        // 2. Verificar que las tablas existen
+        // 2. Verificar que las tablas existen (y admiten su política de TRUNCATE)
         self.verify_tables_exist().await?;
         
         // 3. Agregar columnas de auditoría
         self.ensure_audit_columns().await?;
         
+        // 4. Tabla para mensajes de pg_logical_emit_message (opcional)
+        if let Some(ref table) = self.config.messages_table {
+            self.ensure_messages_table(table).await?;
+        }
+        
         println!("✅ StarRocks setup complete");
         Ok(())
     }
 
+    /// Setup de una tabla agregada en caliente (ReloadConfig)
+    pub async fn setup_table(&self, table: &str) -> Result<(), SetupError> {
+        let mut conn = self.pool
+            .get_conn()
+            .await
+            .map_err(|e| SetupError::SrConnectionFailed {
+                host: self.config.starrocks_url.clone(),
+                error: e.to_string(),
+            })?;
+        self.verify_table_exists(&mut conn, table).await?;
+        self.verify_truncate_policy(&mut conn, table).await?;
+        drop(conn);
+
+        self.ensure_audit_columns_for_table(table).await
+    }
+
     /// Verificar conectividad a StarRocks
     async fn verify_connection(&self) -> Result<(), SetupError> {
         let mut conn = self.pool
@@ -73,44 +100,171 @@ impl<'a> StarRocksSetup<'a> {
             })?;
 
         for table in &self.config.tables {
-            // Extraer nombre de tabla sin schema (StarRocks no usa schemas)
-            let table_name = table.split('.').last().unwrap_or(table);
-
-            let exists: Option<i32> = conn
-                .exec_first(
-                    "SELECT 1 FROM information_schema.tables 
-                     WHERE table_schema = ? AND table_name = ?",
-                    (&self.config.starrocks_db, table_name),
-                )
-                .await
-                .map_err(|e| SetupError::SrConnectionFailed {
-                    host: self.config.starrocks_url.clone(),
-                    error: e.to_string(),
-                })?;
-
-            if exists.is_none() {
-                return Err(SetupError::SrTableNotFound {
-                    table: table.clone(),
-                });
+            self.verify_table_exists(&mut conn, table).await?;
+            self.verify_truncate_policy(&mut conn, table).await?;
+        }
+
+        Ok(())
+    }
+
+    /// El soft delete de un TRUNCATE es un `UPDATE`, que StarRocks solo admite
+    /// en tablas PRIMARY KEY
+    async fn verify_truncate_policy(&self, conn: &mut Conn, table: &str) -> Result<(), SetupError> {
+        if self.config.truncate_policy.for_source(table) != TruncatePolicy::SoftDelete {
+            return Ok(());
+        }
+        let target = self.config.table_mapping.resolve_qualified(table);
+        let model: Option<String> = conn
+            .exec_first(
+                "SELECT TABLE_MODEL FROM information_schema.tables_config
+                 WHERE TABLE_SCHEMA = ? AND TABLE_NAME = ?",
+                (&target.database, &target.table),
+            )
+            .await
+            .map_err(|e| SetupError::SrConnectionFailed {
+                host: self.config.starrocks_url.clone(),
+                error: e.to_string(),
+            })?;
+
+        match model {
+            Some(model) if model != "PRIMARY_KEYS" => Err(SetupError::SrTruncatePolicyUnsupported {
+                table: target.to_string(),
+                model,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Verificar que la tabla destino (según `TABLE_MAPPING`) existe en StarRocks.
+    /// Con `NEW_TABLE_POLICY=create` una tabla que falta se crea.
+    async fn verify_table_exists(&self, conn: &mut Conn, table: &str) -> Result<(), SetupError> {
+        let target = self.config.table_mapping.resolve_qualified(table);
+
+        let exists: Option<i32> = conn
+            .exec_first(
+                "SELECT 1 FROM information_schema.tables 
+                 WHERE table_schema = ? AND table_name = ?",
+                (&target.database, &target.table),
+            )
+            .await
+            .map_err(|e| SetupError::SrConnectionFailed {
+                host: self.config.starrocks_url.clone(),
+                error: e.to_string(),
+            })?;
+
+        if exists.is_none() {
+            if self.config.new_table_policy == NewTablePolicy::Create {
+                return self.create_table(conn, table, &target).await;
             }
+            return Err(SetupError::SrTableNotFound {
+                table: target.to_string(),
+            });
+        }
+
+        println!("  ✓ Table {} exists in StarRocks ({})", target, table);
+        Ok(())
+    }
 
-            println!("  ✓ Table {} exists in StarRocks", table_name);
+    /// Crear la tabla destino con las columnas y la PK de la tabla PostgreSQL,
+    /// con los mismos tipos que usa la schema evolution del sink
+    async fn create_table(&self, conn: &mut Conn, source: &str, target: &TargetTable) -> Result<(), SetupError> {
+        let create_failed = |error: String| SetupError::SrTableCreateFailed {
+            table: target.to_string(),
+            error,
+        };
+        let (schema, table_name) = source.split_once('.').unwrap_or(("public", source));
+        println!("  🔧 Creating table {} in StarRocks from {}.{}", target, schema, table_name);
+
+        let client = create_postgres_client(&self.config.database_url).await?;
+        let rows = client
+            .query(
+                "SELECT a.attname, a.atttypid, a.atttypmod,
+                        COALESCE(array_position(i.indkey::int2[], a.attnum), 0) AS key_position
+                 FROM pg_attribute a
+                 LEFT JOIN pg_index i ON i.indrelid = a.attrelid AND i.indisprimary
+                 WHERE a.attrelid = ($1::text)::regclass AND a.attnum > 0
+                   AND NOT a.attisdropped AND a.attgenerated = ''
+                 ORDER BY a.attnum",
+                &[&format!("{}.{}", schema, table_name)],
+            )
+            .await
+            .map_err(|e| create_failed(e.to_string()))?;
+        let type_catalog = TypeCatalog::load(&self.config.database_url, self.config.geometry_format)
+            .await
+            .map_err(|e| create_failed(e.to_string()))?;
+
+        let masks = self.config.masking.for_table(schema, table_name);
+        let mut keys: Vec<(i32, String, String)> = Vec::new();
+        let mut columns = Vec::new();
+        for row in &rows {
+            let name: String = row.get(0);
+            if !self.config.column_projection.keeps(schema, table_name, &name) {
+                continue;
+            }
+            let key_position: i32 = row.get(3);
+            // Domains como su tipo base; texto para las columnas con hash o mask
+            // (y los bytea truncados)
+            let (pg_type, type_mod) = type_catalog.resolve(row.get(1), row.get(2));
+            let masked = masks.and_then(|m| m.get(&name)).is_some_and(|p| p.changes_type(pg_type == 17));
+            let (pg_type, type_mod) = if masked { (25, -1) } else { (pg_type, type_mod) };
+            let sr_type = self.config.type_mapping.column_type(schema, table_name, &name, pg_type, type_mod);
+            if key_position > 0 {
+                keys.push((key_position, name, sr_type));
+            } else {
+                columns.push(format!("`{}` {}", name, sr_type));
+            }
+        }
+        if keys.is_empty() {
+            return Err(create_failed("the source table has no primary key".to_string()));
         }
 
+        // Columnas de la PK primero y en su orden (requisito de las tablas PRIMARY KEY)
+        keys.sort_by_key(|(position, _, _)| *position);
+        // Fan-in: la tabla origen es parte de la clave
+        let source_column = self.config.table_mapping.source_column_qualified(source);
+        let keys: Vec<(String, String)> = source_column
+            .map(|c| (c.to_string(), "VARCHAR(255)".to_string()))
+            .into_iter()
+            .chain(keys.into_iter().map(|(_, name, sr_type)| (name, sr_type)))
+            .collect();
+        let key_columns: Vec<String> = keys.iter().map(|(name, _)| format!("`{}`", name)).collect();
+        let key_columns = key_columns.join(", ");
+        let mut definitions: Vec<String> = keys
+            .iter()
+            .map(|(name, sr_type)| format!("`{}` {} NOT NULL", name, sr_type))
+            .collect();
+        definitions.extend(columns);
+        definitions.extend(AUDIT_COLUMNS.iter().map(|(name, def)| format!("`{}` {}", name, def)));
+
+        let sql = format!(
+            "CREATE TABLE IF NOT EXISTS {} (\n    {}\n) PRIMARY KEY ({}) DISTRIBUTED BY HASH({})",
+            target,
+            definitions.join(",\n    "),
+            key_columns,
+            key_columns
+        );
+        conn.query_drop(sql)
+            .await
+            .map_err(|e| create_failed(e.to_string()))?;
+
+        println!("  ✅ Table {} created in StarRocks (NEW_TABLE_POLICY=create)", target);
         Ok(())
     }
 
     /// Asegurar que todas las tablas tienen columnas de auditoría
     async fn ensure_audit_columns(&self) -> Result<(), SetupError> {
         for table in &self.config.tables {
-            let table_name = table.split('.').last().unwrap_or(table);
-            self.ensure_audit_columns_for_table(table_name).await?;
+            self.ensure_audit_columns_for_table(table).await?;
         }
         Ok(())
     }
 
-    /// Agregar columnas de auditoría a una tabla específica
-    async fn ensure_audit_columns_for_table(&self, table: &str) -> Result<(), SetupError> {
+    /// Agregar columnas de auditoría (y la de origen si es fan-in) a la tabla
+    /// destino de una tabla de la config
+    async fn ensure_audit_columns_for_table(&self, source: &str) -> Result<(), SetupError> {
+        let table = &self.config.table_mapping.resolve_qualified(source);
+        let source_column = self.config.table_mapping.source_column_qualified(source);
+
         let mut conn = self.pool
             .get_conn()
             .await
@@ -119,6 +273,12 @@ impl<'a> StarRocksSetup<'a> {
                 error: e.to_string(),
             })?;
 
+        // Fan-in: la columna de origen no se puede agregar con ALTER (quedaría
+        // fuera de la clave y las filas de distintas tablas origen se pisarían)
+        if let Some(column) = source_column {
+            self.verify_fan_in_key(&mut conn, table, column).await?;
+        }
+
         // Obtener columnas existentes
         let existing_columns = self.get_table_columns(&mut conn, table).await?;
 
@@ -128,8 +288,8 @@ impl<'a> StarRocksSetup<'a> {
                 println!("  🔧 Adding audit column {} to {}", col_name, table);
                 
                 let sql = format!(
-                    "ALTER TABLE {}.{} ADD COLUMN {} {}",
-                    self.config.starrocks_db, table, col_name, col_def
+                    "ALTER TABLE {} ADD COLUMN {} {}",
+                    table, col_name, col_def
                 );
 
                 conn.query_drop(sql)
@@ -148,13 +308,72 @@ impl<'a> StarRocksSetup<'a> {
         Ok(())
     }
 
+    /// Verificar que la columna de origen de un fan-in es parte de la PRIMARY KEY
+    /// o UNIQUE KEY de la tabla destino
+    async fn verify_fan_in_key(&self, conn: &mut Conn, table: &TargetTable, column: &str) -> Result<(), SetupError> {
+        let column_key: Option<String> = conn
+            .exec_first(
+                "SELECT COLUMN_KEY FROM information_schema.columns
+                 WHERE table_schema = ? AND table_name = ? AND column_name = ?",
+                (&table.database, &table.table, column),
+            )
+            .await
+            .map_err(|e| SetupError::SrConnectionFailed {
+                host: self.config.starrocks_url.clone(),
+                error: e.to_string(),
+            })?;
+
+        match column_key.as_deref() {
+            Some("PRI") | Some("UNI") => {
+                println!("  ✓ Fan-in column {} is part of the key of {}", column, table);
+                Ok(())
+            }
+            _ => Err(SetupError::SrFanInKeyMissing {
+                table: table.to_string(),
+                column: column.to_string(),
+            }),
+        }
+    }
+
+    /// Crear la tabla de mensajes lógicos si no existe
+    async fn ensure_messages_table(&self, table: &str) -> Result<(), SetupError> {
+        let mut conn = self.pool
+            .get_conn()
+            .await
+            .map_err(|e| SetupError::SrConnectionFailed {
+                host: self.config.starrocks_url.clone(),
+                error: e.to_string(),
+            })?;
+
+        let sql = format!(
+            "CREATE TABLE IF NOT EXISTS {}.{} (
+                lsn BIGINT COMMENT 'LSN PostgreSQL del mensaje',
+                prefix VARCHAR(255),
+                content STRING,
+                transactional BOOLEAN,
+                dbmazz_synced_at DATETIME COMMENT 'Timestamp CDC'
+            ) DUPLICATE KEY(lsn) DISTRIBUTED BY HASH(lsn)",
+            self.config.starrocks_db, table
+        );
+
+        conn.query_drop(sql)
+            .await
+            .map_err(|e| SetupError::SrMessagesTableFailed {
+                table: table.to_string(),
+                error: e.to_string(),
+            })?;
+
+        println!("  ✓ Messages table {} ready", table);
+        Ok(())
+    }
+
     /// Obtener lista de columnas de una tabla
-    async fn get_table_columns(&self, conn: &mut Conn, table: &str) -> Result<Vec<String>, SetupError> {
+    async fn get_table_columns(&self, conn: &mut Conn, table: &TargetTable) -> Result<Vec<String>, SetupError> {
         let rows: Vec<(String,)> = conn
             .exec(
                 "SELECT COLUMN_NAME FROM information_schema.columns 
                  WHERE table_schema = ? AND table_name = ?",
-                (&self.config.starrocks_db, table),
+                (&table.database, &table.table),
             )
             .await
             .map_err(|e| SetupError::SrConnectionFailed {
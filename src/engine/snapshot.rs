@@ -0,0 +1,261 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+use anyhow::{Context, Result, anyhow};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::config::Config;
+use crate::grpc::Stage;
+use crate::grpc::state::{SharedState, SnapshotRequest};
+use crate::pipeline::schema_cache::SchemaCache;
+use crate::pipeline::snapshot_window::{SnapshotChunk, WindowOpenData};
+use crate::sink::Sink;
+use crate::source::parser::{CdcMessage, Tuple, TupleData};
+use crate::source::postgres::SlotSnapshot;
+use crate::source::snapshot::{SnapshotReader, SIGNAL_WINDOW_CLOSE, SIGNAL_WINDOW_OPEN};
+use crate::state_store::{SnapshotProgress, StateStore};
+
+/// Reintentos de un chunk que el pipeline no pudo aplicar
+const MAX_CHUNK_RETRIES: u32 = 3;
+
+/// Carga inicial de las tablas configuradas bajo el snapshot exportado por el slot.
+///
+/// Las filas se envían al sink como INSERTs con `lsn = consistent_point`, de modo que
+/// los cambios posteriores del WAL siempre tienen una versión CDC mayor.
+pub struct InitialSnapshot<'a> {
+    config: &'a Config,
+    shared_state: &'a Arc<SharedState>,
+}
+
+impl<'a> InitialSnapshot<'a> {
+    pub fn new(config: &'a Config, shared_state: &'a Arc<SharedState>) -> Self {
+        Self { config, shared_state }
+    }
+
+    /// Copia todas las tablas y retorna el total de filas cargadas
+    pub async fn run(&self, slot: &SlotSnapshot, sink: &mut dyn Sink) -> Result<u64> {
+        println!("📸 Initial snapshot '{}' at LSN 0x{:X}", slot.snapshot_name, slot.consistent_point);
+
+        let reader = SnapshotReader::connect(&self.config.database_url).await?;
+        reader.begin(Some(&slot.snapshot_name)).await?;
+
+        let table_count = self.config.tables.len();
+        let mut total_rows = 0u64;
+
+        for (idx, table) in self.config.tables.iter().enumerate() {
+            let rows = self
+                .copy_table(&reader, table, idx + 1, table_count, slot.consistent_point, sink)
+                .await
+                .with_context(|| format!("Initial snapshot failed for table '{}'", table))?;
+            total_rows += rows;
+        }
+
+        reader.commit().await?;
+
+        println!("✅ Initial snapshot complete: {} rows from {} tables", total_rows, table_count);
+        Ok(total_rows)
+    }
+
+    /// Copia una tabla en batches de `flush_size` filas
+    async fn copy_table(
+        &self,
+        reader: &SnapshotReader,
+        table: &str,
+        position: usize,
+        table_count: usize,
+        lsn: u64,
+        sink: &mut dyn Sink,
+    ) -> Result<u64> {
+        let mut snapshot_table = reader.load_table(table).await?;
+        snapshot_table.project(&self.config.column_projection);
+        let relation_id = snapshot_table.relation_id;
+
+        let mut schema_cache = SchemaCache::new();
+        schema_cache.update(&snapshot_table.relation_message());
+
+        let mut copy = reader.copy_query(&snapshot_table.select_sql()).await?;
+        let mut rows = 0u64;
+
+        loop {
+            let tuples = copy.next_tuples(self.config.flush_size).await?;
+            if tuples.is_empty() {
+                break;
+            }
+
+            rows += tuples.len() as u64;
+            let batch: Vec<CdcMessage> = tuples
+                .into_iter()
+                .map(|tuple| CdcMessage::Insert { relation_id, tuple })
+                .collect();
+
+            sink.push_batch(&batch, &schema_cache, lsn).await?;
+            self.shared_state.increment_batches();
+
+            self.shared_state
+                .set_stage(
+                    Stage::Snapshot,
+                    &format!(
+                        "Snapshotting {} ({}/{} tables, {} rows)",
+                        snapshot_table.qualified_name(), position, table_count, rows
+                    ),
+                )
+                .await;
+        }
+
+        println!("  ✓ Snapshot of {}: {} rows", snapshot_table.qualified_name(), rows);
+        Ok(rows)
+    }
+}
+
+/// Snapshots incrementales por rangos de PK que corren en paralelo al streaming.
+///
+/// Cada chunk se lee entre dos watermarks escritos en la tabla de señales; el
+/// pipeline reconcilia las filas con los eventos del WAL de esa ventana. El
+/// progreso se guarda por chunk para reanudar tras una caída.
+pub struct IncrementalSnapshotter {
+    config: Config,
+    state_store: StateStore,
+    chunk_tx: mpsc::Sender<SnapshotChunk>,
+    requests: mpsc::UnboundedReceiver<SnapshotRequest>,
+}
+
+impl IncrementalSnapshotter {
+    pub fn new(
+        config: Config,
+        state_store: StateStore,
+        chunk_tx: mpsc::Sender<SnapshotChunk>,
+        requests: mpsc::UnboundedReceiver<SnapshotRequest>,
+    ) -> Self {
+        Self { config, state_store, chunk_tx, requests }
+    }
+
+    pub async fn run(mut self) {
+        // 1. Reanudar snapshots interrumpidos
+        match self.state_store.load_pending_snapshots(&self.config.slot_name).await {
+            Ok(pending) => {
+                for progress in pending {
+                    println!("📸 Resuming incremental snapshot of {} ({} rows copied)",
+                        progress.table, progress.rows_copied);
+                    self.snapshot_table(progress).await;
+                }
+            }
+            Err(e) => eprintln!("❌ Failed to load pending snapshots: {}", e),
+        }
+
+        // 2. Procesar solicitudes nuevas
+        while let Some(request) = self.requests.recv().await {
+            let progress = SnapshotProgress {
+                table: request.table,
+                filter: request.filter,
+                last_key: None,
+                rows_copied: 0,
+            };
+            self.snapshot_table(progress).await;
+        }
+    }
+
+    async fn snapshot_table(&self, progress: SnapshotProgress) {
+        let table = progress.table.clone();
+        if let Err(e) = self.copy_chunks(progress).await {
+            eprintln!("❌ Incremental snapshot of {} failed: {}", table, e);
+        }
+    }
+
+    /// Copia la tabla chunk a chunk desde `progress.last_key`
+    async fn copy_chunks(&self, mut progress: SnapshotProgress) -> Result<()> {
+        let slot = &self.config.slot_name;
+        let chunk_size = self.config.snapshot_chunk_size;
+
+        let reader = SnapshotReader::connect(&self.config.database_url).await?;
+        let mut table = reader.load_table(&progress.table).await?;
+        table.project(&self.config.column_projection);
+        if table.primary_key.is_empty() {
+            return Err(anyhow!(
+                "Table '{}' has no primary key, incremental snapshots need one to split chunks",
+                progress.table
+            ));
+        }
+
+        self.state_store.save_snapshot_progress(slot, &progress).await?;
+        println!("📸 Incremental snapshot of {} started", table.qualified_name());
+
+        let key_indices = table.key_indices();
+        let open_data = serde_json::to_string(&WindowOpenData {
+            relation_id: table.relation_id,
+            key: table.primary_key.clone(),
+        })?;
+        let mut retries = 0;
+
+        loop {
+            let chunk_id = format!("{}:{}", table.qualified_name(), unique_suffix());
+
+            // 1. Watermark low → 2. SELECT del chunk → 3. Watermark high
+            reader.write_signal(&chunk_id, SIGNAL_WINDOW_OPEN, &open_data).await?;
+
+            let sql = table.chunk_sql(progress.filter.as_deref(), progress.last_key.as_deref(), chunk_size);
+            let rows = reader.copy_query(&sql).await?.next_tuples(chunk_size).await?;
+            let row_count = rows.len();
+            let last_key = rows.last().map(|tuple| key_text(tuple, &key_indices));
+
+            let (done_tx, done_rx) = oneshot::channel();
+            self.chunk_tx
+                .send(SnapshotChunk { chunk_id: chunk_id.clone(), table: table.clone(), rows, done_tx })
+                .await
+                .map_err(|_| anyhow!("Pipeline is not running"))?;
+
+            // Ambos watermarks usan el id del chunk (PK de la tabla de señales):
+            // el de apertura se borra antes de escribir el de cierre
+            reader.delete_signal(&chunk_id).await?;
+            reader.write_signal(&chunk_id, SIGNAL_WINDOW_CLOSE, "").await?;
+
+            // 4. Esperar a que el pipeline envíe el chunk al sink
+            let applied = done_rx.await.is_ok();
+            reader.delete_signal(&chunk_id).await?;
+            if !applied {
+                retries += 1;
+                if retries > MAX_CHUNK_RETRIES {
+                    return Err(anyhow!("Chunk {} was not applied after {} retries", chunk_id, MAX_CHUNK_RETRIES));
+                }
+                eprintln!("⚠️  Snapshot chunk {} was not applied, retrying", chunk_id);
+                continue;
+            }
+            retries = 0;
+
+            progress.rows_copied += row_count as u64;
+            if let Some(key) = last_key {
+                progress.last_key = Some(key);
+            }
+            self.state_store.save_snapshot_progress(slot, &progress).await?;
+
+            if row_count < chunk_size {
+                break;
+            }
+        }
+
+        self.state_store.complete_snapshot(slot, &progress.table).await?;
+        println!("✅ Incremental snapshot of {} complete: {} rows",
+            table.qualified_name(), progress.rows_copied);
+        Ok(())
+    }
+}
+
+/// Valores (texto) de la PK de una tupla, para reanudar con keyset pagination
+fn key_text(tuple: &Tuple, indices: &[usize]) -> Vec<String> {
+    indices
+        .iter()
+        .map(|&idx| match tuple.cols.get(idx) {
+            Some(TupleData::Text(bytes)) => String::from_utf8_lossy(bytes).into_owned(),
+            _ => String::new(),
+        })
+        .collect()
+}
+
+/// Sufijo único para ids de chunk (la tabla de señales usa `id` como PK)
+fn unique_suffix() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
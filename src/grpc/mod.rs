@@ -6,6 +6,7 @@ use tonic::transport::Server;
 use tonic_reflection::server::Builder as ReflectionBuilder;
 use std::sync::Arc;
 use state::SharedState;
+use crate::config::Config;
 use services::{health_service, control_service, status_service, metrics_service};
 
 pub use state::{CdcState, CdcConfig, Stage};
@@ -14,6 +15,7 @@ pub use state::{CdcState, CdcConfig, Stage};
 pub async fn start_grpc_server(
     port: u16,
     shared_state: Arc<SharedState>,
+    config: Config,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let addr = format!("0.0.0.0:{}", port).parse()?;
     
@@ -27,7 +29,7 @@ pub async fn start_grpc_server(
     Server::builder()
         .add_service(reflection_service)
         .add_service(health_service(shared_state.clone()))
-        .add_service(control_service(shared_state.clone()))
+        .add_service(control_service(shared_state.clone(), config))
         .add_service(status_service(shared_state.clone()))
         .add_service(metrics_service(shared_state.clone()))
         .serve(addr)
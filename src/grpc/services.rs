@@ -3,7 +3,11 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::time::{interval, Duration};
 use tonic::{Request, Response, Status};
 
+use crate::config::Config;
+use crate::engine::discovery::matching_tables;
+use crate::engine::setup::{postgres::create_postgres_client, SetupManager};
 use crate::grpc::state::{CdcState, SharedState, Stage};
+use crate::pipeline::table_selection::{qualify_table, TableSelection};
 use crate::grpc::cpu_metrics::CpuTracker;
 
 // Include the generated protobuf code
@@ -22,7 +26,8 @@ use dbmazz::{
     HealthCheckRequest, HealthCheckResponse,
     health_check_response::ServingStatus,
     PauseRequest, ResumeRequest, DrainRequest, StopRequest, ReloadConfigRequest,
-    ControlResponse,
+    SnapshotTablesRequest,
+    ControlResponse, TableResult,
     StatusRequest, StatusResponse,
     status_response::CdcState as ProtoCdcState,
     MetricsRequest, MetricsResponse,
@@ -66,6 +71,7 @@ impl HealthService for HealthServiceImpl {
             Stage::Init => 1,    // STAGE_INIT
             Stage::Setup => 2,   // STAGE_SETUP
             Stage::Cdc => 3,     // STAGE_CDC
+            Stage::Snapshot => 4, // STAGE_SNAPSHOT
         };
 
         Ok(Response::new(HealthCheckResponse {
@@ -87,14 +93,125 @@ pub fn health_service(shared_state: Arc<SharedState>) -> HealthServiceServer<Hea
 
 pub struct CdcControlServiceImpl {
     shared_state: Arc<SharedState>,
+    // Config de arranque (conexiones) para el setup de tablas nuevas
+    config: Config,
 }
 
 impl CdcControlServiceImpl {
-    pub fn new(shared_state: Arc<SharedState>) -> Self {
-        Self { shared_state }
+    pub fn new(shared_state: Arc<SharedState>, config: Config) -> Self {
+        Self { shared_state, config }
+    }
+
+    /// Aplicar un nuevo set de tablas y patrones: los patrones se expanden con
+    /// las tablas del catálogo que coinciden (también las ya descubiertas), las
+    /// agregadas pasan por el setup de PostgreSQL/StarRocks, las removidas salen
+    /// de la publication (si están listadas por nombre) y se filtran en el
+    /// pipeline. Los nombres se comparan como `schema.tabla`.
+    /// Retorna el cambio a aplicar sobre la config (sin las tablas cuyo setup
+    /// falló; las de un patrón las reintenta el descubrimiento).
+    async fn reload_tables(&self, requested: &[String]) -> Result<ReloadedTables, String> {
+        let (current, selection) = {
+            let config = self.shared_state.config.borrow();
+            (config.tables.clone(), config.table_selection.with_tables(requested))
+        };
+        let selection = selection.map_err(|e| format!("Invalid tables: {}", e))?;
+
+        let mut requested = selection.tables().to_vec();
+        if selection.has_patterns() {
+            let client = create_postgres_client(&self.config.database_url)
+                .await
+                .map_err(|e| e.to_grpc_message())?;
+            let matching = matching_tables(&client, &selection)
+                .await
+                .map_err(|e| format!("Could not expand table patterns: {}", e))?;
+            requested.extend(matching);
+        }
+        let (added, removed) = diff_tables(&current, &requested);
+        let setup = SetupManager::new(self.config.clone());
+        let mut results = Vec::new();
+        let mut failed = Vec::new();
+
+        for table in &added {
+            match setup.setup_table(table).await {
+                Ok(()) => results.push(TableResult {
+                    table: table.clone(),
+                    success: true,
+                    message: "Table added".to_string(),
+                }),
+                Err(e) => {
+                    eprintln!("❌ Setup failed for new table {}: {}", table, e);
+                    failed.push(table.clone());
+                    results.push(TableResult {
+                        table: table.clone(),
+                        success: false,
+                        message: e.to_grpc_message(),
+                    });
+                }
+            }
+        }
+
+        for table in &removed {
+            // La tabla sale del pipeline aunque falle el ALTER PUBLICATION
+            let message = match setup.remove_table(table).await {
+                Ok(()) => "Table removed".to_string(),
+                Err(e) => {
+                    eprintln!("⚠️  Could not remove {} from the publication: {}", table, e);
+                    format!("Table removed (still in the publication: {})", e.to_grpc_message())
+                }
+            };
+            results.push(TableResult {
+                table: table.clone(),
+                success: true,
+                message,
+            });
+        }
+
+        let added = added.into_iter().filter(|t| !failed.contains(t)).collect();
+        Ok(ReloadedTables { selection, added, removed, results })
     }
 }
 
+/// Tablas de un ReloadConfig ya configuradas, pendientes de aplicar a la config
+struct ReloadedTables {
+    selection: TableSelection,
+    added: Vec<String>,
+    removed: Vec<String>,
+    results: Vec<TableResult>,
+}
+
+impl ReloadedTables {
+    /// Aplicar el cambio sobre las tablas actuales, no sobre las leídas antes del
+    /// setup: el descubrimiento u otro ReloadConfig pudo agregar tablas mientras
+    /// tanto. Retorna las tablas que entran al pipeline.
+    fn merge_into(&self, tables: &mut Vec<String>) -> Vec<String> {
+        tables.retain(|t| !self.removed.contains(&qualify_table(t)));
+        let mut added = Vec::new();
+        for table in &self.added {
+            if !tables.iter().any(|t| &qualify_table(t) == table) {
+                tables.push(table.clone());
+                added.push(table.clone());
+            }
+        }
+        added
+    }
+}
+
+/// Compara el set actual con el pedido por nombre calificado (`orders` es
+/// `public.orders`). Retorna las tablas agregadas y las removidas, calificadas
+/// y sin duplicados.
+fn diff_tables(current: &[String], requested: &[String]) -> (Vec<String>, Vec<String>) {
+    let current: Vec<String> = current.iter().map(|t| qualify_table(t)).collect();
+    let mut tables: Vec<String> = Vec::new();
+    for table in requested.iter().map(|t| qualify_table(t.trim())) {
+        if !tables.contains(&table) {
+            tables.push(table);
+        }
+    }
+    let added = tables.iter().filter(|t| !current.contains(t)).cloned().collect();
+    let removed = current.iter().filter(|t| !tables.contains(t)).cloned().collect();
+    (added, removed)
+}
+
 #[tonic::async_trait]
 impl CdcControlService for CdcControlServiceImpl {
     async fn pause(
@@ -105,6 +222,7 @@ impl CdcControlService for CdcControlServiceImpl {
             Ok(Response::new(ControlResponse {
                 success: true,
                 message: "CDC paused successfully".to_string(),
+                table_results: Vec::new(),
             }))
         } else {
             let current = self.shared_state.get_state();
@@ -113,12 +231,14 @@ impl CdcControlService for CdcControlServiceImpl {
                     Ok(Response::new(ControlResponse {
                         success: false,
                         message: "CDC is already paused".to_string(),
+                        table_results: Vec::new(),
                     }))
                 }
                 _ => {
                     Ok(Response::new(ControlResponse {
                         success: false,
                         message: format!("Cannot pause CDC in state: {:?}", current),
+                        table_results: Vec::new(),
                     }))
                 }
             }
@@ -133,6 +253,7 @@ impl CdcControlService for CdcControlServiceImpl {
             Ok(Response::new(ControlResponse {
                 success: true,
                 message: "CDC resumed successfully".to_string(),
+                table_results: Vec::new(),
             }))
         } else {
             let current = self.shared_state.get_state();
@@ -141,12 +262,14 @@ impl CdcControlService for CdcControlServiceImpl {
                     Ok(Response::new(ControlResponse {
                         success: false,
                         message: "CDC is already running".to_string(),
+                        table_results: Vec::new(),
                     }))
                 }
                 _ => {
                     Ok(Response::new(ControlResponse {
                         success: false,
                         message: format!("Cannot resume CDC in state: {:?}", current),
+                        table_results: Vec::new(),
                     }))
                 }
             }
@@ -166,18 +289,21 @@ impl CdcControlService for CdcControlServiceImpl {
                 Ok(Response::new(ControlResponse {
                     success: true,
                     message: "CDC is draining and will stop".to_string(),
+                    table_results: Vec::new(),
                 }))
             }
             CdcState::Draining => {
                 Ok(Response::new(ControlResponse {
                     success: false,
                     message: "CDC is already draining".to_string(),
+                    table_results: Vec::new(),
                 }))
             }
             CdcState::Stopped => {
                 Ok(Response::new(ControlResponse {
                     success: false,
                     message: "CDC is already stopped".to_string(),
+                    table_results: Vec::new(),
                 }))
             }
         }
@@ -193,6 +319,7 @@ impl CdcControlService for CdcControlServiceImpl {
                 Ok(Response::new(ControlResponse {
                     success: false,
                     message: "CDC is already stopped".to_string(),
+                    table_results: Vec::new(),
                 }))
             }
             _ => {
@@ -202,6 +329,7 @@ impl CdcControlService for CdcControlServiceImpl {
                 Ok(Response::new(ControlResponse {
                     success: true,
                     message: "CDC stopped immediately".to_string(),
+                    table_results: Vec::new(),
                 }))
             }
         }
@@ -212,44 +340,115 @@ impl CdcControlService for CdcControlServiceImpl {
         request: Request<ReloadConfigRequest>,
     ) -> Result<Response<ControlResponse>, Status> {
         let req = request.into_inner();
-        let mut config = self.shared_state.config.write().await;
+
+        // Setup de tablas nuevas antes de publicar la config (sin tomar el lock)
+        let reloaded_tables = if req.tables.is_empty() {
+            None
+        } else {
+            match self.reload_tables(&req.tables).await {
+                Ok(reloaded) => Some(reloaded),
+                Err(e) => {
+                    return Ok(Response::new(ControlResponse {
+                        success: false,
+                        message: e,
+                        table_results: Vec::new(),
+                    }))
+                }
+            }
+        };
 
         let mut changes = Vec::new();
+        let mut table_results = Vec::new();
+        let mut added_tables = Vec::new();
+
+        self.shared_state.config.send_modify(|config| {
+            // 0 significa "no cambiar"
+            if req.flush_size > 0 {
+                config.flush_size = req.flush_size as usize;
+                changes.push(format!(
+                    "flush_size={} (channel capacity stays at {}, set with CHANNEL_CAPACITY)",
+                    req.flush_size, self.config.channel_capacity
+                ));
+            }
 
-        // 0 significa "no cambiar"
-        if req.flush_size > 0 {
-            config.flush_size = req.flush_size as usize;
-            changes.push(format!("flush_size={}", req.flush_size));
-        }
+            if req.flush_interval_ms > 0 {
+                config.flush_interval_ms = req.flush_interval_ms;
+                changes.push(format!("flush_interval_ms={}", req.flush_interval_ms));
+            }
 
-        if req.flush_interval_ms > 0 {
-            config.flush_interval_ms = req.flush_interval_ms;
-            changes.push(format!("flush_interval_ms={}", req.flush_interval_ms));
-        }
+            if let Some(reloaded) = reloaded_tables {
+                added_tables = reloaded.merge_into(&mut config.tables);
+                changes.push(format!("tables={:?}", config.tables));
+                config.table_selection = reloaded.selection;
+                table_results = reloaded.results;
+            }
+        });
 
-        if !req.tables.is_empty() {
-            config.tables = req.tables.clone();
-            changes.push(format!("tables={:?}", req.tables));
+        // Las tablas nuevas necesitan sus filas existentes
+        if self.config.initial_snapshot && !added_tables.is_empty() {
+            if let Err(e) = self.shared_state.queue_snapshots(&added_tables, None) {
+                eprintln!("⚠️  Could not queue snapshot for new tables: {}", e);
+            }
         }
 
+        let failed: Vec<&str> = table_results
+            .iter()
+            .filter(|r| !r.success)
+            .map(|r| r.table.as_str())
+            .collect();
+
         if changes.is_empty() {
             Ok(Response::new(ControlResponse {
                 success: false,
                 message: "No configuration changes provided (use 0 to keep current values)".to_string(),
+                table_results,
+            }))
+        } else if !failed.is_empty() {
+            Ok(Response::new(ControlResponse {
+                success: false,
+                message: format!(
+                    "Configuration partially reloaded: {} (setup failed for: {})",
+                    changes.join(", "),
+                    failed.join(", ")
+                ),
+                table_results,
             }))
         } else {
             Ok(Response::new(ControlResponse {
                 success: true,
                 message: format!("Configuration reloaded: {}", changes.join(", ")),
+                table_results,
             }))
         }
     }
+
+    async fn snapshot_tables(
+        &self,
+        request: Request<SnapshotTablesRequest>,
+    ) -> Result<Response<ControlResponse>, Status> {
+        let req = request.into_inner();
+        let filter = Some(req.filter.trim().to_string()).filter(|f| !f.is_empty());
+
+        match self.shared_state.queue_snapshots(&req.tables, filter) {
+            Ok(()) => Ok(Response::new(ControlResponse {
+                success: true,
+                message: format!("Snapshot queued for tables: {}", req.tables.join(", ")),
+                table_results: Vec::new(),
+            })),
+            Err(e) => Ok(Response::new(ControlResponse {
+                success: false,
+                message: e,
+                table_results: Vec::new(),
+            })),
+        }
+    }
 }
 
 pub fn control_service(
     shared_state: Arc<SharedState>,
+    config: Config,
 ) -> CdcControlServiceServer<CdcControlServiceImpl> {
-    CdcControlServiceServer::new(CdcControlServiceImpl::new(shared_state))
+    CdcControlServiceServer::new(CdcControlServiceImpl::new(shared_state, config))
 }
 
 // ============================================================================
@@ -273,7 +472,7 @@ impl CdcStatusService for CdcStatusServiceImpl {
         _request: Request<StatusRequest>,
     ) -> Result<Response<StatusResponse>, Status> {
         let state = self.shared_state.get_state();
-        let config = self.shared_state.config.read().await;
+        let config = self.shared_state.config.borrow().clone();
 
         let proto_state = match state {
             CdcState::Running => ProtoCdcState::Running,
@@ -395,3 +594,39 @@ pub fn metrics_service(
     CdcMetricsServiceServer::new(CdcMetricsServiceImpl::new(shared_state))
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(tables: &[&str]) -> Vec<String> {
+        tables.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_diff_tables_compares_qualified_names() {
+        let current = names(&["orders", "public.customers", "billing.invoices"]);
+        let requested = names(&["public.orders", "customers", "items", "public.items", "sales.orders"]);
+        let (added, removed) = diff_tables(&current, &requested);
+
+        // `orders` y `public.orders` son la misma tabla: ni se agrega ni se remueve
+        assert_eq!(added, ["public.items", "sales.orders"]);
+        assert_eq!(removed, ["billing.invoices"]);
+    }
+
+    #[test]
+    fn test_reloaded_tables_merge_into_current_config() {
+        let reloaded = ReloadedTables {
+            selection: TableSelection::default(),
+            added: names(&["public.items", "sales.orders"]),
+            removed: names(&["billing.invoices"]),
+            results: Vec::new(),
+        };
+        // `sales.orders` y `public.discovered` llegaron durante el setup
+        let mut tables = names(&["orders", "billing.invoices", "sales.orders", "public.discovered"]);
+        let added = reloaded.merge_into(&mut tables);
+
+        assert_eq!(tables, ["orders", "sales.orders", "public.discovered", "public.items"]);
+        assert_eq!(added, ["public.items"]);
+    }
+}
@@ -1,6 +1,9 @@
 use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
-use tokio::sync::{RwLock, watch};
+use tokio::sync::{RwLock, watch, mpsc};
+
+use crate::pipeline::table_selection::{qualify_table, TableSelection};
+use crate::source::snapshot::validate_filter;
 
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -26,6 +29,7 @@ impl CdcState {
 pub enum Stage {
     Init,
     Setup,
+    Snapshot,
     Cdc,
 }
 
@@ -34,9 +38,19 @@ pub struct CdcConfig {
     pub flush_size: usize,
     pub flush_interval_ms: u64,
     pub tables: Vec<String>,
+    /// Patrones de `TABLES` que sigue el descubrimiento de tablas
+    pub table_selection: TableSelection,
     pub slot_name: String,
 }
 
+/// Solicitud de snapshot incremental de una tabla
+#[derive(Debug, Clone)]
+pub struct SnapshotRequest {
+    pub table: String,
+    /// Condición WHERE opcional para snapshot parcial
+    pub filter: Option<String>,
+}
+
 pub struct SharedState {
     pub state: AtomicU8,
     pub stage: RwLock<Stage>,
@@ -48,15 +62,20 @@ pub struct SharedState {
     pub events_processed: AtomicU64,
     pub batches_sent: AtomicU64,
     pub shutdown_tx: watch::Sender<bool>,
-    pub config: RwLock<CdcConfig>,
+    // Config recargable en caliente; el pipeline se suscribe a los cambios
+    pub config: watch::Sender<CdcConfig>,
     // Timestamp del último evento procesado (para calcular events/sec)
     pub last_event_time: RwLock<std::time::Instant>,
     pub events_last_second: AtomicU64,
+    // Cola de snapshots incrementales (el receiver lo toma el engine)
+    snapshot_tx: mpsc::UnboundedSender<SnapshotRequest>,
+    snapshot_rx: std::sync::Mutex<Option<mpsc::UnboundedReceiver<SnapshotRequest>>>,
 }
 
 impl SharedState {
     pub fn new(config: CdcConfig) -> Arc<Self> {
         let (shutdown_tx, _) = watch::channel(false);
+        let (snapshot_tx, snapshot_rx) = mpsc::unbounded_channel();
         Arc::new(Self {
             state: AtomicU8::new(CdcState::Running as u8),
             stage: RwLock::new(Stage::Init),
@@ -68,9 +87,11 @@ impl SharedState {
             events_processed: AtomicU64::new(0),
             batches_sent: AtomicU64::new(0),
             shutdown_tx,
-            config: RwLock::new(config),
+            config: watch::channel(config).0,
             last_event_time: RwLock::new(std::time::Instant::now()),
             events_last_second: AtomicU64::new(0),
+            snapshot_tx,
+            snapshot_rx: std::sync::Mutex::new(Some(snapshot_rx)),
         })
     }
 
@@ -143,6 +164,41 @@ impl SharedState {
         self.setup_error.read().await.clone()
     }
 
+    /// Encolar snapshots incrementales. Solo se aceptan tablas replicadas
+    /// (las que están en la publication y tienen destino en StarRocks).
+    pub fn queue_snapshots(&self, tables: &[String], filter: Option<String>) -> Result<(), String> {
+        if tables.is_empty() {
+            return Err("No tables provided".to_string());
+        }
+
+        let unknown: Vec<&String> = {
+            let config = self.config.borrow();
+            tables
+                .iter()
+                .filter(|t| !config.tables.iter().any(|c| qualify_table(c) == qualify_table(t)))
+                .collect()
+        };
+        if !unknown.is_empty() {
+            return Err(format!("Tables not replicated by this pipeline: {:?}", unknown));
+        }
+        if let Some(filter) = filter.as_deref() {
+            validate_filter(filter).map_err(|e| e.to_string())?;
+        }
+
+        for table in tables {
+            let request = SnapshotRequest { table: table.clone(), filter: filter.clone() };
+            self.snapshot_tx
+                .send(request)
+                .map_err(|_| "Snapshot queue is closed".to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Tomar el receiver de snapshots (solo una vez, lo consume el engine)
+    pub fn take_snapshot_requests(&self) -> Option<mpsc::UnboundedReceiver<SnapshotRequest>> {
+        self.snapshot_rx.lock().ok()?.take()
+    }
+
     // Métodos sincronos para estado CDC (sin await)
     pub fn get_state(&self) -> CdcState {
         CdcState::from_u8(self.state.load(Ordering::Acquire))
@@ -0,0 +1,155 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+use tokio::sync::oneshot;
+
+use crate::source::parser::CdcMessage;
+
+/// Batch de eventos que solo se corta en límites de transacción.
+///
+/// `committed` marca el fin del último Commit (o evento fuera de transacción):
+/// el prefijo hasta ahí se puede enviar y confirmar con `commit_lsn`. Los
+/// eventos de la transacción en curso esperan a su Commit, salvo que superen el
+/// tamaño de split (transacciones enormes).
+#[derive(Default)]
+pub struct TxnBatch {
+    events: Vec<CdcMessage>,
+    committed: usize,
+    in_txn: bool,
+    /// `end_lsn` del último Commit: el checkpoint seguro tras enviar el prefijo
+    commit_lsn: u64,
+    /// Chunks de snapshot con la posición donde terminan sus filas en el batch
+    chunk_acks: Vec<(usize, oneshot::Sender<()>)>,
+}
+
+impl TxnBatch {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            events: Vec::with_capacity(capacity),
+            ..Default::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Eventos de transacciones ya confirmadas
+    pub fn committed_len(&self) -> usize {
+        self.committed
+    }
+
+    pub fn commit_lsn(&self) -> u64 {
+        self.commit_lsn
+    }
+
+    /// Agrega un evento. Fuera de una transacción (mensajes no transaccionales)
+    /// el evento queda confirmado en su propio LSN.
+    pub fn push(&mut self, msg: CdcMessage, lsn: u64) {
+        match msg {
+            CdcMessage::Begin { .. } => self.in_txn = true,
+            CdcMessage::Commit { end_lsn, .. } => {
+                self.in_txn = false;
+                self.commit_lsn = end_lsn;
+            }
+            _ if !self.in_txn => self.commit_lsn = self.commit_lsn.max(lsn),
+            _ => {}
+        }
+        self.events.push(msg);
+        if !self.in_txn {
+            self.committed = self.events.len();
+        }
+    }
+
+    /// Agrega filas de un chunk de snapshot; el ack se libera cuando se envían
+    pub fn extend_chunk(&mut self, rows: Vec<CdcMessage>, done_tx: oneshot::Sender<()>) {
+        self.events.extend(rows);
+        if !self.in_txn {
+            self.committed = self.events.len();
+        }
+        self.chunk_acks.push((self.events.len(), done_tx));
+    }
+
+    /// Cantidad de eventos a enviar ahora, si corresponde: el prefijo confirmado
+    /// al llegar a `batch_size`, o todo si la transacción en curso supera `split_size`
+    pub fn ready(&self, batch_size: usize, split_size: usize) -> Option<usize> {
+        if split_size > 0 && self.events.len() - self.committed >= split_size {
+            return Some(self.events.len());
+        }
+        (self.committed > 0 && self.committed >= batch_size).then_some(self.committed)
+    }
+
+    /// Extrae los primeros `n` eventos y los acks de chunks contenidos en ellos
+    pub fn take(&mut self, n: usize) -> (Vec<CdcMessage>, Vec<oneshot::Sender<()>>) {
+        let n = n.min(self.events.len());
+        let events: Vec<CdcMessage> = self.events.drain(..n).collect();
+        self.committed = self.committed.saturating_sub(n);
+
+        let mut acks = Vec::new();
+        let mut pending = Vec::with_capacity(self.chunk_acks.len());
+        for (end, ack) in self.chunk_acks.drain(..) {
+            if end <= n {
+                acks.push(ack);
+            } else {
+                pending.push((end - n, ack));
+            }
+        }
+        self.chunk_acks = pending;
+        (events, acks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn begin() -> CdcMessage {
+        CdcMessage::Begin { final_lsn: 0, timestamp: 0, xid: 1 }
+    }
+
+    fn commit(end_lsn: u64) -> CdcMessage {
+        CdcMessage::Commit { flags: 0, commit_lsn: end_lsn - 1, end_lsn, timestamp: 0 }
+    }
+
+    #[test]
+    fn test_flushes_only_committed_prefix() {
+        let mut batch = TxnBatch::default();
+        batch.push(begin(), 10);
+        batch.push(CdcMessage::Unknown, 20);
+        batch.push(commit(100), 90);
+        batch.push(begin(), 110);
+        batch.push(CdcMessage::Unknown, 120);
+
+        // batch_size alcanzado a mitad de la segunda transacción: solo va la primera
+        assert_eq!(batch.ready(4, 0), None);
+        assert_eq!(batch.ready(3, 0), Some(3));
+        let (events, _) = batch.take(3);
+        assert_eq!(events.len(), 3);
+        assert_eq!(batch.commit_lsn(), 100);
+        assert_eq!(batch.committed_len(), 0);
+        assert_eq!(batch.len(), 2);
+
+        batch.push(commit(200), 190);
+        assert_eq!(batch.ready(1, 0), Some(3));
+        assert_eq!(batch.commit_lsn(), 200);
+    }
+
+    #[test]
+    fn test_splits_huge_transaction() {
+        let mut batch = TxnBatch::default();
+        batch.push(begin(), 10);
+        for lsn in 0..9 {
+            batch.push(CdcMessage::Unknown, 20 + lsn);
+        }
+        assert_eq!(batch.ready(5, 0), None);
+        assert_eq!(batch.ready(5, 10), Some(10));
+
+        let (tx, mut rx) = oneshot::channel();
+        batch.extend_chunk(vec![CdcMessage::Unknown], tx);
+        batch.take(10);
+        // El chunk quedó fuera de lo enviado: su ack sigue pendiente
+        assert!(rx.try_recv().is_err());
+        let (_, acks) = batch.take(1);
+        assert_eq!(acks.len(), 1);
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+use std::fmt;
+
+use crate::pipeline::snapshot_window::{ExecuteSnapshotData, Signal};
+use crate::source::parser::CdcMessage;
+use crate::state_store::StateStore;
+
+/// Prefijo de los mensajes de `pg_logical_emit_message` dirigidos a dbmazz
+pub const CONTROL_PREFIX: &str = "dbmazz.";
+
+/// Acción pedida con `pg_logical_emit_message(..., 'dbmazz.<acción>', ...)`
+#[derive(Debug)]
+pub enum ControlAction {
+    /// Enviar el batch actual al sink sin esperar el intervalo
+    Flush,
+    /// Pausar el CDC (se reanuda con el RPC Resume)
+    Pause,
+    /// Snapshot ad-hoc, contenido `{"tables": [...], "filter": "..."}`
+    Snapshot(ExecuteSnapshotData),
+    /// Señal (p.ej. watermark) con el formato de `dbmazz_signals`:
+    /// `{"id": "...", "type": "...", "data": "..."}`
+    Signal(Signal),
+}
+
+impl ControlAction {
+    /// Interpretar un mensaje con prefijo `dbmazz.`. None si el prefijo no es de control.
+    pub fn from_message(prefix: &str, content: &[u8]) -> Option<Result<Self, String>> {
+        let action = prefix.strip_prefix(CONTROL_PREFIX)?;
+        Some(match action {
+            "flush" => Ok(Self::Flush),
+            "pause" => Ok(Self::Pause),
+            "snapshot" => serde_json::from_slice(content)
+                .map(Self::Snapshot)
+                .map_err(|e| format!("invalid snapshot content: {}", e)),
+            "signal" => serde_json::from_slice(content)
+                .map(Self::Signal)
+                .map_err(|e| format!("invalid signal content: {}", e)),
+            other => Err(format!("unknown action '{}'", other)),
+        })
+    }
+}
+
+/// Posición de una acción de control en el orden en que llega del slot: LSN
+/// del commit de su transacción (o del mensaje, si no es transaccional) y orden
+/// entre las acciones de esa transacción.
+///
+/// El LSN de cada cambio no sirve: una transacción que confirma después puede
+/// tener cambios con LSN menores que los de otra ya entregada.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ControlPosition {
+    pub commit_lsn: u64,
+    pub seq: u32,
+}
+
+impl fmt::Display for ControlPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:X}#{}", self.commit_lsn, self.seq)
+    }
+}
+
+/// Posición de la última acción de control ejecutada.
+///
+/// Tras un reinicio el WAL se vuelve a leer desde el checkpoint: las acciones
+/// (pausa, snapshots ad-hoc) en una posición ya registrada se ignoran en lugar
+/// de ejecutarse de nuevo. Sin `StateStore` el registro vive solo en memoria.
+#[derive(Default)]
+pub struct ControlLog {
+    store: Option<(StateStore, String)>,
+    handled: ControlPosition,
+    // Transacción en curso (LSN del commit del Begin) y acciones vistas en ella
+    txn_commit_lsn: Option<u64>,
+    txn_seq: u32,
+}
+
+impl ControlLog {
+    /// Registro persistido en `dbmazz_checkpoints` para el slot
+    pub fn new(store: StateStore, slot_name: String, handled: ControlPosition) -> Self {
+        Self { store: Some((store, slot_name)), handled, ..Default::default() }
+    }
+
+    /// Seguir los límites de transacción de cada evento que llega al pipeline
+    pub fn observe(&mut self, msg: &CdcMessage) {
+        match msg {
+            CdcMessage::Begin { final_lsn, .. } => {
+                self.txn_commit_lsn = Some(*final_lsn);
+                self.txn_seq = 0;
+            }
+            CdcMessage::Commit { .. } => self.txn_commit_lsn = None,
+            _ => {}
+        }
+    }
+
+    /// Posición de la siguiente acción de control, recibida en `lsn`
+    pub fn position(&mut self, lsn: u64) -> ControlPosition {
+        match self.txn_commit_lsn {
+            Some(commit_lsn) => {
+                let seq = self.txn_seq;
+                self.txn_seq += 1;
+                ControlPosition { commit_lsn, seq }
+            }
+            // Mensaje no transaccional: se entrega en su LSN
+            None => ControlPosition { commit_lsn: lsn, seq: 0 },
+        }
+    }
+
+    /// Indica si la acción en `position` ya se ejecutó (replay del WAL)
+    pub fn is_replayed(&self, position: ControlPosition) -> bool {
+        position.commit_lsn > 0 && position <= self.handled
+    }
+
+    /// Registrar una acción ejecutada en `position`
+    pub async fn record(&mut self, position: ControlPosition) {
+        if position <= self.handled {
+            return;
+        }
+        self.handled = position;
+        if let Some((ref store, ref slot)) = self.store {
+            if let Err(e) = store.save_control_position(slot, position.commit_lsn, position.seq).await {
+                eprintln!("⚠️  Failed to save control position {}: {}", position, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_actions() {
+        assert!(ControlAction::from_message("app.audit", b"{}").is_none());
+        assert!(matches!(ControlAction::from_message("dbmazz.flush", b""), Some(Ok(ControlAction::Flush))));
+        assert!(matches!(ControlAction::from_message("dbmazz.reboot", b""), Some(Err(_))));
+
+        let Some(Ok(ControlAction::Signal(signal))) = ControlAction::from_message(
+            "dbmazz.signal",
+            br#"{"id": "w-1", "type": "snapshot-window-open", "data": "{}"}"#,
+        ) else {
+            panic!("expected signal");
+        };
+        assert_eq!(signal.kind, "snapshot-window-open");
+    }
+
+    fn begin(final_lsn: u64) -> CdcMessage {
+        CdcMessage::Begin { final_lsn, timestamp: 0, xid: 1 }
+    }
+
+    fn commit(commit_lsn: u64) -> CdcMessage {
+        CdcMessage::Commit { flags: 0, commit_lsn, end_lsn: commit_lsn + 0x10, timestamp: 0 }
+    }
+
+    #[tokio::test]
+    async fn test_control_log_skips_replayed_actions() {
+        let mut log = ControlLog::default();
+        log.observe(&begin(0x300));
+        let first = log.position(0x200);
+        let second = log.position(0x210);
+        assert_eq!(first, ControlPosition { commit_lsn: 0x300, seq: 0 });
+        assert_eq!(second, ControlPosition { commit_lsn: 0x300, seq: 1 });
+        assert!(!log.is_replayed(first));
+        log.record(first).await;
+        assert!(log.is_replayed(first));
+        assert!(!log.is_replayed(second));
+        log.record(second).await;
+        log.observe(&commit(0x300));
+
+        // Cambio con LSN menor en una transacción que confirma después: no es un replay
+        log.observe(&begin(0x400));
+        let later = log.position(0x100);
+        assert!(!log.is_replayed(later));
+        log.record(later).await;
+        log.observe(&commit(0x400));
+
+        // Mensaje no transaccional: su propio LSN
+        let message = log.position(0x380);
+        assert!(log.is_replayed(message));
+        assert_eq!(log.position(0x500), ControlPosition { commit_lsn: 0x500, seq: 0 });
+
+        // Replay de la primera transacción tras un reinicio
+        log.observe(&begin(0x300));
+        let replayed = log.position(0x200);
+        assert!(log.is_replayed(replayed));
+    }
+}
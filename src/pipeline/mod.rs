@@ -1,9 +1,26 @@
+pub mod batch;
+pub mod control;
+pub mod projection;
 pub mod schema_cache;
+pub mod snapshot_window;
+pub mod table_selection;
 
-use crate::source::parser::{CdcMessage, CdcEvent};
-use crate::grpc::state::SharedState;
-use tokio::sync::mpsc;
+use crate::source::parser::{CdcMessage, CdcEvent, Tuple};
+use crate::engine::discovery::TableDiscovery;
+use crate::grpc::state::{CdcConfig, CdcState, SharedState};
+use hashbrown::{HashMap, HashSet};
+use tokio::sync::{mpsc, oneshot, watch};
+use crate::pipeline::batch::TxnBatch;
+use crate::pipeline::control::{ControlAction, ControlLog};
+use crate::pipeline::projection::{project_tuple, ColumnProjection};
 use crate::pipeline::schema_cache::SchemaCache;
+use crate::pipeline::table_selection::qualify_table;
+use crate::pipeline::snapshot_window::{
+    is_signal_change, ExecuteSnapshotData, Signal, SnapshotChunk, SnapshotWindow,
+};
+use crate::source::origin::OriginFilter;
+use crate::source::snapshot::SIGNAL_EXECUTE_SNAPSHOT;
+use crate::source::type_catalog::TypeCatalog;
 use crate::sink::Sink;
 use std::sync::Arc;
 use std::time::Duration;
@@ -14,8 +31,28 @@ pub struct Pipeline {
     sink: Box<dyn Sink + Send>,
     batch_size: usize,
     batch_timeout: Duration,
+    // Eventos de una transacción en curso a partir de los cuales se envía sin esperar el Commit
+    txn_split_size: usize,
     feedback_tx: Option<mpsc::Sender<u64>>,
     shared_state: Option<Arc<SharedState>>,
+    chunk_rx: Option<mpsc::Receiver<SnapshotChunk>>,
+    snapshot_window: SnapshotWindow,
+    // Tablas replicadas según la config recargable (None = todas)
+    config_rx: Option<watch::Receiver<CdcConfig>>,
+    replicated_tables: Option<HashSet<String>>,
+    // Cache relation_id → replicada, se invalida con cada cambio de config
+    relation_filter: HashMap<u32, bool>,
+    // Orígenes de replicación descartados (evita loops en setups bidireccionales)
+    origin_filter: OriginFilter,
+    // Tipos del usuario, compartido con el sink y refrescado con los mensajes Type
+    type_catalog: TypeCatalog,
+    // Columnas replicadas por tabla y posiciones conservadas de cada relation proyectada
+    column_projection: ColumnProjection,
+    projected_columns: HashMap<u32, Vec<usize>>,
+    // Alta de tablas nuevas que coinciden con los patrones de TABLES
+    discovery: Option<Arc<TableDiscovery>>,
+    // Acciones de control ya ejecutadas (no se repiten al releer el WAL)
+    control_log: ControlLog,
 }
 
 impl Pipeline {
@@ -31,8 +68,20 @@ impl Pipeline {
             sink,
             batch_size,
             batch_timeout,
+            txn_split_size: 0,
             feedback_tx: None,
             shared_state: None,
+            chunk_rx: None,
+            snapshot_window: SnapshotWindow::new(),
+            config_rx: None,
+            replicated_tables: None,
+            relation_filter: HashMap::new(),
+            origin_filter: OriginFilter::Any,
+            type_catalog: TypeCatalog::default(),
+            column_projection: ColumnProjection::default(),
+            projected_columns: HashMap::new(),
+            discovery: None,
+            control_log: ControlLog::default(),
         }
     }
 
@@ -42,27 +91,68 @@ impl Pipeline {
         self
     }
 
-    /// Configura el estado compartido para métricas
+    /// Configura el estado compartido para métricas y config recargable
     pub fn with_shared_state(mut self, shared_state: Arc<SharedState>) -> Self {
+        let mut config_rx = shared_state.config.subscribe();
+        self.replicated_tables = Some(qualified_tables(&config_rx.borrow_and_update().tables));
+        self.config_rx = Some(config_rx);
         self.shared_state = Some(shared_state);
         self
     }
 
+    /// Configura el canal de chunks de snapshots incrementales
+    pub fn with_snapshot_chunks(mut self, chunk_rx: mpsc::Receiver<SnapshotChunk>) -> Self {
+        self.chunk_rx = Some(chunk_rx);
+        self
+    }
+
+    /// Configura el split de transacciones enormes (0 = esperar siempre al Commit)
+    pub fn with_txn_split_size(mut self, txn_split_size: usize) -> Self {
+        self.txn_split_size = txn_split_size;
+        self
+    }
+
+    /// Configura el filtro de orígenes de replicación
+    pub fn with_origin_filter(mut self, origin_filter: OriginFilter) -> Self {
+        self.origin_filter = origin_filter;
+        self
+    }
+
+    /// Configura el catálogo de tipos a refrescar con los mensajes Type
+    pub fn with_type_catalog(mut self, type_catalog: TypeCatalog) -> Self {
+        self.type_catalog = type_catalog;
+        self
+    }
+
+    /// Configura las listas include/exclude de columnas por tabla
+    pub fn with_column_projection(mut self, column_projection: ColumnProjection) -> Self {
+        self.column_projection = column_projection;
+        self
+    }
+
+    /// Configura el descubrimiento de tablas nuevas por sus mensajes Relation
+    pub fn with_table_discovery(mut self, discovery: Arc<TableDiscovery>) -> Self {
+        self.discovery = Some(discovery);
+        self
+    }
+
+    /// Configura el registro persistido de acciones de control ejecutadas
+    pub fn with_control_log(mut self, control_log: ControlLog) -> Self {
+        self.control_log = control_log;
+        self
+    }
+
     pub async fn run(mut self) {
-        let mut batch = Vec::with_capacity(self.batch_size);
+        let mut batch = TxnBatch::with_capacity(self.batch_size);
         let mut interval = tokio::time::interval(self.batch_timeout);
-        let mut last_lsn: u64 = 0;
 
         loop {
             // Check if paused before processing
             if let Some(ref state) = self.shared_state {
                 let current_state = state.get_state();
                 if current_state == crate::grpc::state::CdcState::Paused {
-                    // Flush pending batch before pausing
-                    if !batch.is_empty() {
-                        self.flush_batch(&batch, last_lsn).await;
-                        batch.clear();
-                    }
+                    // Flush de las transacciones completas antes de pausar
+                    self.flush_committed(&mut batch).await;
                     // Sleep while paused
                     tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
                     continue;
@@ -70,37 +160,308 @@ impl Pipeline {
             }
 
             tokio::select! {
-                Some(event) = self.rx.recv() => {
-                    last_lsn = event.lsn; // Actualizar LSN
+                Some(mut event) = self.rx.recv() => {
+                    self.control_log.observe(&event.message);
+                    // Mensajes `dbmazz.*` de pg_logical_emit_message: acciones del pipeline
+                    if let CdcMessage::Message { prefix, content, .. } = &event.message {
+                        if let Some(action) = ControlAction::from_message(prefix, content) {
+                            match action {
+                                Ok(action) => self.handle_control(action, event.lsn, &mut batch).await,
+                                Err(e) => eprintln!("⚠️  Ignoring control message {}: {}", prefix, e),
+                            }
+                            continue;
+                        }
+                    }
+                    
+                    // Señales (watermarks de snapshot): no van al sink
+                    if is_signal_change(&event.message, &self.schema_cache) {
+                        if let Some(signal) = Signal::from_message(&event.message, &self.schema_cache) {
+                            self.handle_signal(&signal, event.lsn, &mut batch).await;
+                        }
+                        continue;
+                    }
+                    
+                    // Tipos del usuario: se refrescan antes del Relation que los usa
+                    if let CdcMessage::Type { id, namespace, name } = &event.message {
+                        if let Err(e) = self.type_catalog.refresh(*id).await {
+                            eprintln!("⚠️  Failed to refresh type {}.{} ({}): {}", namespace, name, id, e);
+                        }
+                        continue;
+                    }
                     
-                    // Detectar cambios de schema
-                    if let Some(delta) = self.schema_cache.update(&event.message) {
+                    // Detectar cambios de schema (también de tablas removidas, por si vuelven)
+                    if let CdcMessage::Relation { id, namespace, name, .. } = &event.message {
+                        self.relation_filter.remove(id);
+                        // Tabla nueva que coincide con TABLES: setup antes de sus cambios
+                        self.discover_table(namespace, name).await;
+                    }
+                    // Columnas excluidas: fuera antes del schema cache (la evolución tampoco las ve)
+                    self.project(&mut event.message);
+                    let delta = self.schema_cache.update(&event.message);
+                    
+                    // Tablas removidas con ReloadConfig y orígenes filtrados: se descartan
+                    if !self.is_replicated(&mut event.message) {
+                        continue;
+                    }
+                    if is_change(&event.message) && !self.origin_filter.accepts(event.origin.as_deref()) {
+                        continue;
+                    }
+                    self.snapshot_window.observe(&event.message, &self.schema_cache);
+                    
+                    if let Some(delta) = delta {
                         println!("🔧 Schema change detected for table {}: {} new columns", 
                             delta.table_name, delta.added_columns.len());
-                        if let Err(e) = self.sink.apply_schema_delta(&delta).await {
+                        if let Err(e) = self.sink.apply_schema_delta(&delta, &self.schema_cache).await {
                             eprintln!("❌ Schema evolution failed: {}", e);
                             // Continuar procesando - no detener el pipeline por errores de DDL
                         }
                     }
                     
-                    batch.push(event.message);
+                    batch.push(event.message, event.lsn);
                     
-                    if batch.len() >= self.batch_size {
-                        self.flush_batch(&batch, last_lsn).await;
-                        batch.clear();
+                    // Solo se corta en un Commit (o al superar el split de transacciones enormes)
+                    if let Some(n) = batch.ready(self.batch_size, self.txn_split_size) {
+                        self.flush(&mut batch, n).await;
+                    }
+                }
+                Some(chunk) = recv_chunk(&mut self.chunk_rx) => {
+                    self.snapshot_window.add_chunk(chunk);
+                }
+                Some(()) = config_changed(&mut self.config_rx) => {
+                    self.apply_config(&mut interval);
+                    if let Some(n) = batch.ready(self.batch_size, self.txn_split_size) {
+                        self.flush(&mut batch, n).await;
                     }
                 }
                 _ = interval.tick() => {
-                    if !batch.is_empty() {
-                        self.flush_batch(&batch, last_lsn).await;
-                        batch.clear();
+                    self.flush_committed(&mut batch).await;
+                }
+            }
+        }
+    }
+
+    /// Aplicar la config recargada con ReloadConfig (batch, intervalo y tablas)
+    fn apply_config(&mut self, interval: &mut tokio::time::Interval) {
+        let Some(rx) = self.config_rx.as_mut() else {
+            return;
+        };
+        let config = rx.borrow_and_update().clone();
+
+        if config.flush_size > 0 && config.flush_size != self.batch_size {
+            println!("🔧 Pipeline batch size: {} → {}", self.batch_size, config.flush_size);
+            self.batch_size = config.flush_size;
+        }
+
+        let timeout = Duration::from_millis(config.flush_interval_ms);
+        if config.flush_interval_ms > 0 && timeout != self.batch_timeout {
+            println!("🔧 Pipeline flush interval: {:?} → {:?}", self.batch_timeout, timeout);
+            self.batch_timeout = timeout;
+            // Nuevo período desde ahora (el primer tick de un Interval es inmediato)
+            *interval = tokio::time::interval_at(tokio::time::Instant::now() + timeout, timeout);
+        }
+
+        self.replicated_tables = Some(qualified_tables(&config.tables));
+        self.relation_filter.clear();
+    }
+
+    /// Agregar una tabla que no se replica todavía y coincide con los patrones
+    /// de `TABLES`. El pipeline espera su setup para no descartar sus cambios.
+    async fn discover_table(&mut self, namespace: &str, name: &str) {
+        let Some(ref discovery) = self.discovery else {
+            return;
+        };
+        let table = format!("{}.{}", namespace, name);
+        let Some(ref mut tables) = self.replicated_tables else {
+            return;
+        };
+        if tables.contains(&table) || !discovery.matches(namespace, name) {
+            return;
+        }
+        if discovery.add_table(&table, true).await {
+            tables.insert(table);
+        }
+    }
+
+    /// Indica si el mensaje pertenece a una tabla replicada. Un TRUNCATE conserva
+    /// solo las tablas replicadas y se descarta si no queda ninguna.
+    fn is_replicated(&mut self, msg: &mut CdcMessage) -> bool {
+        let relation_id = match msg {
+            CdcMessage::Relation { id, .. } => *id,
+            CdcMessage::Insert { relation_id, .. }
+            | CdcMessage::Update { relation_id, .. }
+            | CdcMessage::Delete { relation_id, .. } => *relation_id,
+            CdcMessage::Truncate { relation_ids, .. } => {
+                relation_ids.retain(|id| self.is_relation_replicated(*id));
+                return !relation_ids.is_empty();
+            }
+            _ => return true,
+        };
+        self.is_relation_replicated(relation_id)
+    }
+
+    fn is_relation_replicated(&mut self, relation_id: u32) -> bool {
+        let Some(ref tables) = self.replicated_tables else {
+            return true;
+        };
+
+        let schema_cache = &self.schema_cache;
+        *self.relation_filter.entry(relation_id).or_insert_with(|| {
+            // Sin schema no se puede decidir: se deja pasar
+            schema_cache
+                .get(relation_id)
+                .is_none_or(|schema| tables.contains(&format!("{}.{}", schema.namespace, schema.name)))
+        })
+    }
+
+    /// Descartar las columnas que no se replican. Con la column list de una lista
+    /// include (PG15+) el Relation ya llega proyectado y las tuplas no se tocan.
+    fn project(&mut self, msg: &mut CdcMessage) {
+        if self.column_projection.is_empty() {
+            return;
+        }
+        let (relation_id, tuples): (u32, Vec<&mut Tuple>) = match msg {
+            CdcMessage::Relation { id, namespace, name, columns, .. } => {
+                match self.column_projection.kept_indices(namespace, name, columns) {
+                    Some(kept) => {
+                        *columns = kept.iter().map(|&idx| columns[idx].clone()).collect();
+                        self.projected_columns.insert(*id, kept);
+                    }
+                    None => {
+                        self.projected_columns.remove(id);
                     }
                 }
+                return;
+            }
+            CdcMessage::Insert { relation_id, tuple } => (*relation_id, vec![tuple]),
+            CdcMessage::Update { relation_id, old_tuple, new_tuple } => {
+                (*relation_id, old_tuple.iter_mut().chain(Some(new_tuple)).collect())
+            }
+            CdcMessage::Delete { relation_id, old_tuple } => (*relation_id, old_tuple.iter_mut().collect()),
+            _ => return,
+        };
+
+        if let Some(kept) = self.projected_columns.get(&relation_id) {
+            for tuple in tuples {
+                project_tuple(tuple, kept);
             }
         }
     }
 
-    async fn flush_batch(&mut self, batch: &[CdcMessage], lsn: u64) {
+    /// Ejecutar una acción pedida con un mensaje `dbmazz.*` en `lsn`. La pausa y
+    /// los snapshots no se repiten si el mensaje se relee tras un reinicio (se
+    /// comparan por su posición en el orden de commit, ver `ControlPosition`).
+    async fn handle_control(&mut self, action: ControlAction, lsn: u64, batch: &mut TxnBatch) {
+        match action {
+            ControlAction::Flush => {
+                println!("📨 Control message: flush ({} events)", batch.committed_len());
+                self.flush_committed(batch).await;
+            }
+            ControlAction::Pause => {
+                let position = self.control_log.position(lsn);
+                if self.control_log.is_replayed(position) {
+                    println!("📨 Control message: pause at {} already handled, skipping", position);
+                    return;
+                }
+                // El loop hace flush del batch al detectar la pausa
+                if let Some(ref state) = self.shared_state {
+                    if state.compare_and_set_state(CdcState::Running, CdcState::Paused) {
+                        println!("📨 Control message: CDC paused");
+                    }
+                }
+                self.control_log.record(position).await;
+            }
+            ControlAction::Snapshot(data) => {
+                let position = self.control_log.position(lsn);
+                if self.control_log.is_replayed(position) {
+                    println!("📨 Control message: snapshot at {} already handled, skipping", position);
+                    return;
+                }
+                let Some(ref state) = self.shared_state else {
+                    return;
+                };
+                match state.queue_snapshots(&data.tables, data.filter) {
+                    Ok(()) => println!("📨 Control message: snapshot queued for {:?}", data.tables),
+                    Err(e) => eprintln!("⚠️  Ignoring snapshot control message: {}", e),
+                }
+                self.control_log.record(position).await;
+            }
+            ControlAction::Signal(signal) => self.handle_signal(&signal, lsn, batch).await,
+        }
+    }
+
+    /// Procesar una señal de la tabla `dbmazz_signals`. Al cerrar una ventana, las
+    /// filas del chunk que el WAL no modificó se agregan al batch en esta posición.
+    async fn handle_signal(&mut self, signal: &Signal, lsn: u64, batch: &mut TxnBatch) {
+        if signal.kind == SIGNAL_EXECUTE_SNAPSHOT {
+            let position = self.control_log.position(lsn);
+            if self.control_log.is_replayed(position) {
+                println!("📸 Signal {} at {} already handled, skipping", signal.id, position);
+                return;
+            }
+            self.queue_signal_snapshot(signal);
+            self.control_log.record(position).await;
+            return;
+        }
+
+        // El chunk se envía antes de escribir el watermark de cierre, ya está en el canal
+        if let Some(rx) = self.chunk_rx.as_mut() {
+            while let Ok(chunk) = rx.try_recv() {
+                self.snapshot_window.add_chunk(chunk);
+            }
+        }
+
+        let Some(closed) = self.snapshot_window.on_signal(signal) else {
+            return;
+        };
+
+        if self.schema_cache.get(closed.relation_id).is_none() {
+            self.schema_cache.update(&closed.relation);
+        }
+
+        println!("📸 Snapshot chunk {}: {} rows merged", signal.id, closed.inserts.len());
+        if batch.len() == 0 && closed.inserts.is_empty() {
+            let _ = closed.done_tx.send(());
+        } else {
+            batch.extend_chunk(closed.inserts, closed.done_tx);
+        }
+    }
+
+    /// Encolar un snapshot ad-hoc pedido desde SQL con una señal `execute-snapshot`
+    fn queue_signal_snapshot(&self, signal: &Signal) {
+        let Some(ref state) = self.shared_state else {
+            return;
+        };
+
+        let data: ExecuteSnapshotData = match signal.data.as_deref().map(serde_json::from_str) {
+            Some(Ok(data)) => data,
+            _ => {
+                eprintln!("⚠️  Ignoring signal {}: invalid data, expected {{\"tables\": [...]}}", signal.id);
+                return;
+            }
+        };
+
+        match state.queue_snapshots(&data.tables, data.filter) {
+            Ok(()) => println!("📸 Signal {}: snapshot queued for {:?}", signal.id, data.tables),
+            Err(e) => eprintln!("⚠️  Ignoring signal {}: {}", signal.id, e),
+        }
+    }
+
+    /// Enviar las transacciones completas del batch
+    async fn flush_committed(&mut self, batch: &mut TxnBatch) {
+        let n = batch.committed_len();
+        if n > 0 {
+            self.flush(batch, n).await;
+        }
+    }
+
+    /// Enviar los primeros `n` eventos y confirmar el `end_lsn` del último Commit
+    async fn flush(&mut self, batch: &mut TxnBatch, n: usize) {
+        let lsn = batch.commit_lsn();
+        let (events, chunk_acks) = batch.take(n);
+        self.flush_batch(&events, lsn, chunk_acks).await;
+    }
+
+    async fn flush_batch(&mut self, batch: &[CdcMessage], lsn: u64, chunk_acks: Vec<oneshot::Sender<()>>) {
         match self.sink.push_batch(batch, &self.schema_cache, lsn).await {
             Ok(_) => {
                 // Actualizar métrica de batches enviados
@@ -108,18 +469,205 @@ impl Pipeline {
                     state.increment_batches();
                 }
 
+                // Confirmar chunks de snapshot incluidos en este batch
+                for ack in chunk_acks {
+                    let _ = ack.send(());
+                }
+
                 // Enviar LSN al canal de feedback para confirmar checkpoint
+                // (0 = todavía no hubo ningún Commit)
                 if let Some(ref tx) = self.feedback_tx {
-                    if let Err(e) = tx.send(lsn).await {
-                        eprintln!("Failed to send feedback: {}", e);
+                    if lsn > 0 {
+                        if let Err(e) = tx.send(lsn).await {
+                            eprintln!("Failed to send feedback: {}", e);
+                        }
                     }
                 }
             }
             Err(e) => {
+                // Los chunks de snapshot del batch fallido se reintentan al soltar sus acks
                 eprintln!("Sink error (will not checkpoint): {}", e);
             }
         }
     }
 }
 
+/// Cambios de fila y mensajes (los únicos que filtra el origen; Begin/Commit y schema pasan)
+fn is_change(msg: &CdcMessage) -> bool {
+    matches!(
+        msg,
+        CdcMessage::Insert { .. }
+            | CdcMessage::Update { .. }
+            | CdcMessage::Delete { .. }
+            | CdcMessage::Truncate { .. }
+            | CdcMessage::Message { .. }
+    )
+}
+
+/// Normalizar nombres de tablas de la config a `schema.tabla`
+fn qualified_tables(tables: &[String]) -> HashSet<String> {
+    tables.iter().map(|t| qualify_table(t)).collect()
+}
+
+/// Esperar un cambio de la config compartida si el pipeline está suscrito
+async fn config_changed(rx: &mut Option<watch::Receiver<CdcConfig>>) -> Option<()> {
+    match rx {
+        Some(rx) => rx.changed().await.ok(),
+        None => std::future::pending().await,
+    }
+}
+
+/// Recibir un chunk de snapshot si el canal está configurado
+async fn recv_chunk(rx: &mut Option<mpsc::Receiver<SnapshotChunk>>) -> Option<SnapshotChunk> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::schema_cache::SchemaDelta;
+    use crate::source::parser::{Column, ToastBitmap};
+    use anyhow::Result;
+    use async_trait::async_trait;
+
+    /// Sink que registra el tamaño de cada batch recibido
+    struct RecordingSink(Arc<std::sync::Mutex<Vec<usize>>>);
+
+    #[async_trait]
+    impl Sink for RecordingSink {
+        async fn push_batch(&mut self, batch: &[CdcMessage], _schema_cache: &SchemaCache, _lsn: u64) -> Result<()> {
+            self.0.lock().unwrap().push(batch.len());
+            Ok(())
+        }
+
+        async fn apply_schema_delta(&self, _delta: &SchemaDelta, _schema_cache: &SchemaCache) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn pipeline(tables: &[&str]) -> (Pipeline, Arc<SharedState>) {
+        let (_tx, rx) = mpsc::channel(1);
+        let (pipeline, state, _) = pipeline_with(tables, rx);
+        (pipeline, state)
+    }
+
+    fn pipeline_with(
+        tables: &[&str],
+        rx: mpsc::Receiver<CdcEvent>,
+    ) -> (Pipeline, Arc<SharedState>, Arc<std::sync::Mutex<Vec<usize>>>) {
+        let state = SharedState::new(CdcConfig {
+            flush_size: 10,
+            flush_interval_ms: 1000,
+            tables: tables.iter().map(|t| t.to_string()).collect(),
+            table_selection: Default::default(),
+            slot_name: "test_slot".to_string(),
+        });
+        let batches = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut pipeline = Pipeline::new(rx, Box::new(RecordingSink(batches.clone())), 10, Duration::from_secs(3600))
+            .with_shared_state(state.clone());
+        for (id, namespace, name) in [(1, "public", "orders"), (2, "billing", "invoices"), (3, "public", "audit")] {
+            pipeline.schema_cache.update(&CdcMessage::Relation {
+                id,
+                namespace: namespace.to_string(),
+                name: name.to_string(),
+                replica_identity: b'd',
+                columns: vec![Column { flags: 1, name: "id".to_string(), type_id: 23, type_mod: -1 }],
+            });
+        }
+        (pipeline, state, batches)
+    }
+
+    fn insert(relation_id: u32) -> CdcMessage {
+        CdcMessage::Insert {
+            relation_id,
+            tuple: Tuple { cols: Vec::new(), toast_bitmap: ToastBitmap::default() },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_relation_filter_follows_reloaded_tables() {
+        // `orders` sin schema es `public.orders`
+        let (mut pipeline, state) = pipeline(&["orders", "billing.invoices"]);
+        assert!(pipeline.is_replicated(&mut insert(1)));
+        assert!(pipeline.is_replicated(&mut insert(2)));
+        assert!(!pipeline.is_replicated(&mut insert(3)));
+
+        // TRUNCATE conserva solo las tablas replicadas
+        let mut truncate = CdcMessage::Truncate { relation_ids: vec![1, 3], cascade: false, restart_identity: false };
+        assert!(pipeline.is_replicated(&mut truncate));
+        assert!(matches!(truncate, CdcMessage::Truncate { ref relation_ids, .. } if relation_ids == &[1]));
+
+        // ReloadConfig: sale `orders`, entra `public.audit`; el cache se invalida
+        state.config.send_modify(|config| {
+            config.tables = vec!["public.audit".to_string(), "billing.invoices".to_string()];
+        });
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        pipeline.apply_config(&mut interval);
+        assert!(!pipeline.is_replicated(&mut insert(1)));
+        assert!(pipeline.is_replicated(&mut insert(3)));
+        let mut truncate = CdcMessage::Truncate { relation_ids: vec![1], cascade: false, restart_identity: false };
+        assert!(!pipeline.is_replicated(&mut truncate));
+    }
+
+    #[tokio::test]
+    async fn test_reloaded_flush_size_cuts_batches() {
+        let (tx, rx) = mpsc::channel(16);
+        let (mut pipeline, state, batches) = pipeline_with(&["orders"], rx);
+
+        // ReloadConfig baja flush_size de 10 a 2: cada transacción (3 eventos) ya
+        // alcanza el batch y se envía sin esperar al timer de flush
+        state.config.send_modify(|config| config.flush_size = 2);
+        let mut interval = tokio::time::interval(Duration::from_secs(3600));
+        pipeline.apply_config(&mut interval);
+        assert_eq!(pipeline.batch_size, 2);
+        tokio::spawn(pipeline.run());
+
+        for txn in 0..2u64 {
+            let lsn = 0x100 * (txn + 1);
+            let events = [
+                CdcMessage::Begin { final_lsn: lsn + 0x20, timestamp: 0, xid: txn as u32 },
+                insert(1),
+                CdcMessage::Commit { flags: 0, commit_lsn: lsn + 0x20, end_lsn: lsn + 0x30, timestamp: 0 },
+            ];
+            for (offset, message) in events.into_iter().enumerate() {
+                tx.send(CdcEvent { lsn: lsn + offset as u64, message, origin: None }).await.unwrap();
+            }
+        }
+
+        for _ in 0..100 {
+            if batches.lock().unwrap().len() >= 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(*batches.lock().unwrap(), [3, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_pause_message_follows_commit_order() {
+        let (mut pipeline, state) = pipeline(&["orders"]);
+        let mut batch = TxnBatch::with_capacity(10);
+        let begin = |final_lsn| CdcMessage::Begin { final_lsn, timestamp: 0, xid: 1 };
+        let commit = |commit_lsn| CdcMessage::Commit { flags: 0, commit_lsn, end_lsn: commit_lsn + 0x10, timestamp: 0 };
 
+        // Pausa en una transacción que confirma en 0x300
+        pipeline.control_log.observe(&begin(0x300));
+        pipeline.handle_control(ControlAction::Pause, 0x200, &mut batch).await;
+        pipeline.control_log.observe(&commit(0x300));
+        assert!(state.compare_and_set_state(CdcState::Paused, CdcState::Running));
+
+        // Emitida antes (LSN 0x100) pero confirmada después: se ejecuta
+        pipeline.control_log.observe(&begin(0x400));
+        pipeline.handle_control(ControlAction::Pause, 0x100, &mut batch).await;
+        pipeline.control_log.observe(&commit(0x400));
+        assert!(state.compare_and_set_state(CdcState::Paused, CdcState::Running));
+
+        // Replay de la misma transacción tras un reinicio: se ignora
+        pipeline.control_log.observe(&begin(0x400));
+        pipeline.handle_control(ControlAction::Pause, 0x100, &mut batch).await;
+        assert_eq!(state.get_state(), CdcState::Running);
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Proyección de columnas por tabla (listas include/exclude).
+//!
+//! En PostgreSQL 15+ las listas include se publican como column list de la
+//! publication, así las columnas que no están en la lista no salen de la base.
+//! Las listas exclude (y todas en versiones anteriores) las filtra el pipeline:
+//! descarta las columnas de los Relation y de las tuplas antes del `SchemaCache`,
+//! por lo que el sink y la schema evolution nunca las ven. Una column list fija
+//! dejaría afuera las columnas agregadas después, que con exclude se replican.
+
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::source::parser::{Column, ToastBitmap, Tuple};
+
+/// Columnas replicadas de una tabla
+#[derive(Debug, Clone, PartialEq)]
+enum ColumnList {
+    /// Solo estas columnas (las nuevas no se replican)
+    Include(HashSet<String>),
+    /// Todas menos estas (las nuevas se replican)
+    Exclude(HashSet<String>),
+}
+
+/// Listas include/exclude por tabla (`schema.tabla`)
+#[derive(Debug, Clone, Default)]
+pub struct ColumnProjection {
+    tables: HashMap<String, ColumnList>,
+}
+
+impl ColumnProjection {
+    /// Parsear `tabla:col1,col2;schema.tabla:col3` de las listas include y exclude
+    pub fn parse(include: &str, exclude: &str) -> Result<Self> {
+        let mut tables = HashMap::new();
+        for (lists, is_include) in [(include, true), (exclude, false)] {
+            for entry in lists.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+                let (table, columns) = entry
+                    .split_once(':')
+                    .ok_or_else(|| anyhow!("Invalid column list '{}' (expected table:col1,col2)", entry))?;
+                let table = table.trim();
+                let table = if table.contains('.') { table.to_string() } else { format!("public.{}", table) };
+                let columns: HashSet<String> = columns
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|c| !c.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                if columns.is_empty() {
+                    return Err(anyhow!("Empty column list for table '{}'", table));
+                }
+
+                let list = if is_include { ColumnList::Include(columns) } else { ColumnList::Exclude(columns) };
+                if tables.insert(table.clone(), list).is_some() {
+                    return Err(anyhow!("Table '{}' has more than one column list", table));
+                }
+            }
+        }
+        Ok(Self { tables })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Indica si la tabla tiene una lista configurada
+    pub fn has_table(&self, namespace: &str, table: &str) -> bool {
+        self.tables.contains_key(&format!("{}.{}", namespace, table))
+    }
+
+    /// Indica si la tabla tiene una lista include (se publica como column list)
+    pub fn has_include_list(&self, namespace: &str, table: &str) -> bool {
+        matches!(self.tables.get(&format!("{}.{}", namespace, table)), Some(ColumnList::Include(_)))
+    }
+
+    /// Indica si la columna se replica
+    pub fn keeps(&self, namespace: &str, table: &str, column: &str) -> bool {
+        match self.tables.get(&format!("{}.{}", namespace, table)) {
+            Some(ColumnList::Include(columns)) => columns.contains(column),
+            Some(ColumnList::Exclude(columns)) => !columns.contains(column),
+            None => true,
+        }
+    }
+
+    /// Posiciones de las columnas replicadas; None si se replican todas
+    pub fn kept_indices(&self, namespace: &str, table: &str, columns: &[Column]) -> Option<Vec<usize>> {
+        if !self.has_table(namespace, table) {
+            return None;
+        }
+        let kept: Vec<usize> = (0..columns.len())
+            .filter(|&idx| self.keeps(namespace, table, &columns[idx].name))
+            .collect();
+        (kept.len() < columns.len()).then_some(kept)
+    }
+}
+
+impl fmt::Display for ColumnProjection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.tables.is_empty() {
+            return write!(f, "all columns");
+        }
+        let mut tables: Vec<String> = self
+            .tables
+            .iter()
+            .map(|(table, list)| {
+                let (sign, columns) = match list {
+                    ColumnList::Include(columns) => ("+", columns),
+                    ColumnList::Exclude(columns) => ("-", columns),
+                };
+                let mut columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+                columns.sort();
+                format!("{}{}({})", sign, table, columns.join(","))
+            })
+            .collect();
+        tables.sort();
+        write!(f, "{}", tables.join(" "))
+    }
+}
+
+/// Deja en la tupla solo las columnas `kept` (posiciones del Relation original)
+pub fn project_tuple(tuple: &mut Tuple, kept: &[usize]) {
+    let mut toast_bitmap = ToastBitmap::default();
+    let cols = kept
+        .iter()
+        .enumerate()
+        .filter_map(|(new_idx, &idx)| {
+            if tuple.is_toast_column(idx) {
+                toast_bitmap.set(new_idx);
+            }
+            tuple.cols.get(idx).cloned()
+        })
+        .collect();
+    tuple.cols = cols;
+    tuple.toast_bitmap = toast_bitmap;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::parser::TupleData;
+
+    fn column(name: &str) -> Column {
+        Column { flags: 0, name: name.to_string(), type_id: 25, type_mod: -1 }
+    }
+
+    #[test]
+    fn test_include_and_exclude() {
+        let projection = ColumnProjection::parse("orders:id,total", "users:password_hash, ssn").unwrap();
+        let users = [column("id"), column("email"), column("password_hash"), column("ssn")];
+        assert_eq!(projection.kept_indices("public", "users", &users), Some(vec![0, 1]));
+        assert_eq!(projection.kept_indices("public", "orders", &users), Some(vec![0]));
+        assert_eq!(projection.kept_indices("public", "items", &users), None);
+        assert!(projection.has_include_list("public", "orders"));
+        assert!(!projection.has_include_list("public", "users"));
+        // Columna nueva: se replica con exclude, no con include
+        assert!(projection.keeps("public", "users", "phone"));
+        assert!(!projection.keeps("public", "orders", "notes"));
+
+        assert!(ColumnProjection::parse("orders:id", "orders:total").is_err());
+        assert!(ColumnProjection::parse("orders", "").is_err());
+    }
+
+    #[test]
+    fn test_project_tuple_keeps_toast_positions() {
+        let mut toast_bitmap = ToastBitmap::default();
+        toast_bitmap.set(3);
+        let mut tuple = Tuple {
+            cols: vec![
+                TupleData::Text("1".into()),
+                TupleData::Text("secret".into()),
+                TupleData::Null,
+                TupleData::Toast,
+            ],
+            toast_bitmap,
+        };
+        project_tuple(&mut tuple, &[0, 2, 3]);
+        assert_eq!(tuple.cols.len(), 3);
+        assert!(matches!(tuple.cols[1], TupleData::Null));
+        assert!(tuple.is_toast_column(2));
+        assert_eq!(tuple.toast_count(), 1);
+    }
+}
@@ -6,11 +6,14 @@ pub struct TableSchema {
     pub id: u32,
     pub namespace: String,
     pub name: String,
+    /// `relreplident`: con `f` (FULL) el old tuple de un DELETE trae todas las columnas
+    pub replica_identity: u8,
     pub columns: Vec<Column>,
 }
 
 #[derive(Debug, Clone)]
 pub struct SchemaDelta {
+    pub namespace: String,
     pub table_name: String,
     pub added_columns: Vec<AddedColumn>,
 }
@@ -34,7 +37,7 @@ impl SchemaCache {
     }
 
     pub fn update(&mut self, msg: &CdcMessage) -> Option<SchemaDelta> {
-        if let CdcMessage::Relation { id, namespace, name, columns, .. } = msg {
+        if let CdcMessage::Relation { id, namespace, name, replica_identity, columns } = msg {
             // Obtener schema anterior (si existe)
             let prev_columns: HashSet<String> = self.cache
                 .get(id)
@@ -56,6 +59,7 @@ impl SchemaCache {
                 id: *id,
                 namespace: namespace.clone(),
                 name: name.clone(),
+                replica_identity: *replica_identity,
                 columns: columns.clone(),
             });
             
@@ -63,6 +67,7 @@ impl SchemaCache {
             // Solo retornar si prev_columns no esta vacio (no es la primera vez que vemos esta tabla)
             if !added.is_empty() && !prev_columns.is_empty() {
                 return Some(SchemaDelta {
+                    namespace: namespace.clone(),
                     table_name: name.clone(),
                     added_columns: added,
                 });
@@ -74,4 +79,8 @@ impl SchemaCache {
     pub fn get(&self, id: u32) -> Option<&TableSchema> {
         self.cache.get(&id)
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TableSchema> {
+        self.cache.values()
+    }
 }
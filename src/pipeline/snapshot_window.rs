@@ -0,0 +1,373 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+use bytes::Bytes;
+use hashbrown::{HashMap, HashSet};
+use tokio::sync::oneshot;
+
+use crate::pipeline::schema_cache::{SchemaCache, TableSchema};
+use crate::sink::timezone::TargetTimezone;
+use crate::source::binary;
+use crate::source::parser::{CdcMessage, Column, Tuple, TupleData};
+use crate::source::snapshot::{SnapshotTable, SIGNAL_SCHEMA, SIGNAL_TABLE, SIGNAL_WINDOW_CLOSE, SIGNAL_WINDOW_OPEN};
+
+const TIMESTAMPTZ_OID: u32 = 1184;
+
+/// Chunk de un snapshot incremental leído entre dos watermarks
+pub struct SnapshotChunk {
+    pub chunk_id: String,
+    pub table: SnapshotTable,
+    pub rows: Vec<Tuple>,
+    /// Se notifica cuando las filas del chunk fueron enviadas al sink
+    pub done_tx: oneshot::Sender<()>,
+}
+
+/// Fila de la tabla de señales recibida por el WAL (o mensaje `dbmazz.signal`)
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Signal {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub data: Option<String>,
+}
+
+impl Signal {
+    /// Extrae una señal si el mensaje es un INSERT en la tabla de señales
+    pub fn from_message(msg: &CdcMessage, schema_cache: &SchemaCache) -> Option<Self> {
+        let CdcMessage::Insert { relation_id, tuple } = msg else {
+            return None;
+        };
+        let schema = schema_cache.get(*relation_id)?;
+        if !is_signal_table(schema) {
+            return None;
+        }
+
+        let column = |name: &str| -> Option<String> {
+            let idx = schema.columns.iter().position(|c| c.name == name)?;
+            match tuple.cols.get(idx)? {
+                TupleData::Text(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+                TupleData::Binary(bytes) => binary::to_text(schema.columns[idx].type_id, bytes),
+                _ => None,
+            }
+        };
+
+        Some(Self {
+            id: column("id")?,
+            kind: column("type")?,
+            data: column("data"),
+        })
+    }
+}
+
+/// Indica si un cambio de fila pertenece a la tabla de señales (no va al sink)
+pub fn is_signal_change(msg: &CdcMessage, schema_cache: &SchemaCache) -> bool {
+    let relation_id = match msg {
+        CdcMessage::Insert { relation_id, .. }
+        | CdcMessage::Update { relation_id, .. }
+        | CdcMessage::Delete { relation_id, .. } => *relation_id,
+        _ => return false,
+    };
+    schema_cache.get(relation_id).is_some_and(is_signal_table)
+}
+
+/// La tabla de señales vive en `public`: una tabla de usuario con el mismo
+/// nombre en otro schema se replica normalmente
+fn is_signal_table(schema: &TableSchema) -> bool {
+    schema.namespace == SIGNAL_SCHEMA && schema.name == SIGNAL_TABLE
+}
+
+/// Datos del watermark de apertura: tabla y columnas de la PK del chunk
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WindowOpenData {
+    pub relation_id: u32,
+    pub key: Vec<String>,
+}
+
+/// Datos de una señal `execute-snapshot`
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ExecuteSnapshotData {
+    pub tables: Vec<String>,
+    #[serde(default)]
+    pub filter: Option<String>,
+}
+
+/// Ventana abierta entre el watermark low y high de un chunk
+struct OpenWindow {
+    chunk_id: String,
+    relation_id: u32,
+    key_columns: Vec<String>,
+    /// PKs modificadas por el WAL dentro de la ventana
+    seen: HashSet<Vec<Bytes>>,
+    /// TRUNCATE de la tabla dentro de la ventana: el chunk entero quedó obsoleto
+    truncated: bool,
+}
+
+/// Resultado de cerrar una ventana: filas a insertar en el batch
+pub struct ClosedChunk {
+    pub relation: CdcMessage,
+    pub relation_id: u32,
+    pub inserts: Vec<CdcMessage>,
+    pub done_tx: oneshot::Sender<()>,
+}
+
+/// Reconciliación de chunks de snapshot con el stream del WAL (estilo DBLog).
+///
+/// Entre el watermark de apertura y el de cierre se registran las PKs que el WAL
+/// modificó; al cerrar, las filas del chunk con esas PKs se descartan porque el
+/// evento del WAL es igual o más reciente. El resto se emite como INSERT en la
+/// posición del watermark de cierre.
+#[derive(Default)]
+pub struct SnapshotWindow {
+    chunks: HashMap<String, SnapshotChunk>,
+    open: Option<OpenWindow>,
+}
+
+impl SnapshotWindow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registrar un chunk leído por el snapshotter
+    pub fn add_chunk(&mut self, chunk: SnapshotChunk) {
+        self.chunks.insert(chunk.chunk_id.clone(), chunk);
+    }
+
+    /// Procesa una señal de watermark. Retorna el chunk cerrado si corresponde.
+    /// Las señales de chunks desconocidos (p.ej. replay tras reinicio) se ignoran.
+    pub fn on_signal(&mut self, signal: &Signal) -> Option<ClosedChunk> {
+        match signal.kind.as_str() {
+            SIGNAL_WINDOW_OPEN => {
+                let data: WindowOpenData = signal
+                    .data
+                    .as_deref()
+                    .and_then(|d| serde_json::from_str(d).ok())?;
+                self.open = Some(OpenWindow {
+                    chunk_id: signal.id.clone(),
+                    relation_id: data.relation_id,
+                    key_columns: data.key,
+                    seen: HashSet::new(),
+                    truncated: false,
+                });
+                None
+            }
+            SIGNAL_WINDOW_CLOSE => self.close(&signal.id),
+            _ => None,
+        }
+    }
+
+    /// Registrar las PKs tocadas por un evento del WAL si hay una ventana abierta
+    pub fn observe(&mut self, msg: &CdcMessage, schema_cache: &SchemaCache) {
+        let Some(window) = self.open.as_mut() else {
+            return;
+        };
+
+        let (relation_id, tuples): (u32, [Option<&Tuple>; 2]) = match msg {
+            CdcMessage::Insert { relation_id, tuple } => (*relation_id, [Some(tuple), None]),
+            CdcMessage::Update { relation_id, old_tuple, new_tuple } => {
+                (*relation_id, [Some(new_tuple), old_tuple.as_ref()])
+            }
+            CdcMessage::Delete { relation_id, old_tuple } => (*relation_id, [old_tuple.as_ref(), None]),
+            CdcMessage::Truncate { relation_ids, .. } => {
+                window.truncated |= relation_ids.contains(&window.relation_id);
+                return;
+            }
+            _ => return,
+        };
+
+        if relation_id != window.relation_id {
+            return;
+        }
+        let Some(schema) = schema_cache.get(relation_id) else {
+            return;
+        };
+
+        let indices: Vec<usize> = window
+            .key_columns
+            .iter()
+            .filter_map(|k| schema.columns.iter().position(|c| &c.name == k))
+            .collect();
+
+        for tuple in tuples.into_iter().flatten() {
+            if let Some(key) = tuple_key(tuple, &indices, &schema.columns) {
+                window.seen.insert(key);
+            }
+        }
+    }
+
+    fn close(&mut self, chunk_id: &str) -> Option<ClosedChunk> {
+        let chunk = self.chunks.remove(chunk_id)?;
+        let window = match self.open.take() {
+            Some(w) if w.chunk_id == chunk_id => w,
+            other => {
+                // Sin ventana no se puede reconciliar: se descarta el chunk y el
+                // snapshotter lo reintenta al ver cerrado `done_tx`
+                self.open = other;
+                return None;
+            }
+        };
+
+        let relation_id = chunk.table.relation_id;
+        let indices = chunk.table.key_indices();
+        // Tras un TRUNCATE en la ventana ninguna fila leída sigue vigente
+        let rows = if window.truncated { Vec::new() } else { chunk.rows };
+        let inserts = rows
+            .into_iter()
+            .filter(|tuple| match tuple_key(tuple, &indices, &chunk.table.columns) {
+                Some(key) => !window.seen.contains(&key),
+                None => true,
+            })
+            .map(|tuple| CdcMessage::Insert { relation_id, tuple })
+            .collect();
+
+        Some(ClosedChunk {
+            relation: chunk.table.relation_message(),
+            relation_id,
+            inserts,
+            done_tx: chunk.done_tx,
+        })
+    }
+}
+
+/// Valores (texto) de la PK de una tupla. Las columnas binarias del WAL se pasan
+/// a texto para compararlas con las filas del chunk, que vienen de COPY.
+fn tuple_key(tuple: &Tuple, indices: &[usize], columns: &[Column]) -> Option<Vec<Bytes>> {
+    if indices.is_empty() {
+        return None;
+    }
+    indices
+        .iter()
+        .map(|&idx| {
+            let type_id = columns.get(idx)?.type_id;
+            let text = match tuple.cols.get(idx)? {
+                TupleData::Text(bytes) => bytes.clone(),
+                TupleData::Binary(bytes) => Bytes::from(binary::to_text(type_id, bytes)?),
+                _ => return None,
+            };
+            Some(key_value(type_id, text))
+        })
+        .collect()
+}
+
+/// timestamptz se compara en UTC: COPY lo imprime con el offset de la sesión y
+/// el formato binario siempre en UTC
+fn key_value(type_id: u32, text: Bytes) -> Bytes {
+    if type_id != TIMESTAMPTZ_OID {
+        return text;
+    }
+    std::str::from_utf8(&text)
+        .ok()
+        .and_then(|t| TargetTimezone::default().normalize(type_id, t))
+        .map(Bytes::from)
+        .unwrap_or(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::parser::ToastBitmap;
+
+    const RELATION_ID: u32 = 16384;
+
+    fn table() -> SnapshotTable {
+        SnapshotTable {
+            relation_id: RELATION_ID,
+            namespace: "public".to_string(),
+            name: "orders".to_string(),
+            replica_identity: b'd',
+            columns: vec![
+                Column { flags: 1, name: "id".to_string(), type_id: 23, type_mod: -1 },
+                Column { flags: 0, name: "status".to_string(), type_id: 25, type_mod: -1 },
+            ],
+            primary_key: vec!["id".to_string()],
+        }
+    }
+
+    fn row(id: &'static str, status: &'static str) -> Tuple {
+        Tuple {
+            cols: vec![TupleData::Text(Bytes::from_static(id.as_bytes())), TupleData::Text(Bytes::from_static(status.as_bytes()))],
+            toast_bitmap: ToastBitmap::default(),
+        }
+    }
+
+    fn signal(id: &str, kind: &str, data: Option<String>) -> Signal {
+        Signal { id: id.to_string(), kind: kind.to_string(), data }
+    }
+
+    /// Ventana con un chunk de tres filas (ids 1, 2, 3) abierta
+    fn open_window(schema_cache: &mut SchemaCache) -> SnapshotWindow {
+        schema_cache.update(&table().relation_message());
+        let mut window = SnapshotWindow::new();
+        let (done_tx, _done_rx) = oneshot::channel();
+        window.add_chunk(SnapshotChunk {
+            chunk_id: "c1".to_string(),
+            table: table(),
+            rows: vec![row("1", "new"), row("2", "new"), row("3", "new")],
+            done_tx,
+        });
+        let data = serde_json::to_string(&WindowOpenData { relation_id: RELATION_ID, key: vec!["id".to_string()] }).unwrap();
+        assert!(window.on_signal(&signal("c1", SIGNAL_WINDOW_OPEN, Some(data))).is_none());
+        window
+    }
+
+    fn inserted_ids(closed: &ClosedChunk) -> Vec<&[u8]> {
+        closed
+            .inserts
+            .iter()
+            .map(|msg| match msg {
+                CdcMessage::Insert { tuple, .. } => match &tuple.cols[0] {
+                    TupleData::Text(b) => &b[..],
+                    other => panic!("unexpected key {:?}", other),
+                },
+                other => panic!("unexpected message {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_update_inside_window_drops_chunk_row() {
+        let mut schema_cache = SchemaCache::new();
+        let mut window = open_window(&mut schema_cache);
+
+        // El WAL cambió la PK 2 → 20: ambas versiones son más recientes que el chunk
+        let update = CdcMessage::Update {
+            relation_id: RELATION_ID,
+            old_tuple: Some(row("2", "new")),
+            new_tuple: row("20", "paid"),
+        };
+        window.observe(&update, &schema_cache);
+        // Cambios de otras tablas no afectan la ventana
+        window.observe(&CdcMessage::Insert { relation_id: RELATION_ID + 1, tuple: row("1", "x") }, &schema_cache);
+
+        let closed = window.on_signal(&signal("c1", SIGNAL_WINDOW_CLOSE, None)).unwrap();
+        assert_eq!(closed.relation_id, RELATION_ID);
+        assert_eq!(inserted_ids(&closed), [b"1".as_slice(), b"3".as_slice()]);
+    }
+
+    #[test]
+    fn test_delete_inside_window_drops_chunk_row() {
+        let mut schema_cache = SchemaCache::new();
+        let mut window = open_window(&mut schema_cache);
+
+        let delete = CdcMessage::Delete { relation_id: RELATION_ID, old_tuple: Some(row("3", "new")) };
+        window.observe(&delete, &schema_cache);
+
+        let closed = window.on_signal(&signal("c1", SIGNAL_WINDOW_CLOSE, None)).unwrap();
+        assert_eq!(inserted_ids(&closed), [b"1".as_slice(), b"2".as_slice()]);
+
+        // Cierre de un chunk desconocido (replay tras reinicio): se ignora
+        assert!(window.on_signal(&signal("c1", SIGNAL_WINDOW_CLOSE, None)).is_none());
+    }
+
+    #[test]
+    fn test_timestamptz_key_matches_binary_wal() {
+        let columns = [Column { flags: 1, name: "created_at".to_string(), type_id: 1184, type_mod: -1 }];
+        let tuple = |data: TupleData| Tuple { cols: vec![data], toast_bitmap: ToastBitmap::default() };
+
+        // COPY con TimeZone = Europe/Madrid vs. WAL binario (2000-01-02 00:00:01.5 UTC)
+        let copy = tuple(TupleData::Text(Bytes::from_static(b"2000-01-02 01:00:01.5+01")));
+        let usec: i64 = 86_400_000_000 + 1_500_000;
+        let wal = tuple(TupleData::Binary(Bytes::copy_from_slice(&usec.to_be_bytes())));
+        assert_eq!(tuple_key(&copy, &[0], &columns), tuple_key(&wal, &[0], &columns));
+    }
+}
@@ -0,0 +1,152 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Selección de tablas con patrones (`TABLES=orders,public.*,sales.order_*`).
+//!
+//! Los nombres sin `*` se replican siempre. Los patrones seleccionan las tablas
+//! del catálogo que coinciden y no están en `TABLES_EXCLUDE`. La publication
+//! sigue siendo por tabla: el descubrimiento agrega cada tabla que coincide con
+//! `ALTER PUBLICATION ... ADD TABLE`, así las tablas que no se replican no
+//! necesitan replica identity ni generan cambios en el slot.
+
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+use crate::sink::table_map::Pattern;
+
+/// Prefijo de las tablas internas de dbmazz (checkpoints, señales, progreso de
+/// snapshots): nunca las selecciona un patrón
+const INTERNAL_PREFIX: &str = "dbmazz_";
+
+/// Nombre calificado `schema.tabla` de una tabla de `TABLES` (sin schema es `public`)
+pub fn qualify_table(table: &str) -> String {
+    if table.contains('.') {
+        table.to_string()
+    } else {
+        format!("public.{}", table)
+    }
+}
+
+/// Patrón `schema.tabla` (sin schema es `public`)
+#[derive(Debug, Clone)]
+struct TablePattern {
+    schema: Pattern,
+    table: Pattern,
+}
+
+impl TablePattern {
+    fn parse(text: &str) -> Result<Self> {
+        let (schema, table) = text.split_once('.').unwrap_or(("public", text));
+        if schema.is_empty() || table.is_empty() {
+            return Err(anyhow!("Invalid table pattern '{}' (expected schema.table)", text));
+        }
+        Ok(Self { schema: Pattern::parse(schema)?, table: Pattern::parse(table)? })
+    }
+
+    fn matches(&self, namespace: &str, table: &str) -> bool {
+        self.schema.capture(namespace).is_some() && self.table.capture(table).is_some()
+    }
+}
+
+impl fmt::Display for TablePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.schema, self.table)
+    }
+}
+
+/// Tablas explícitas, patrones y exclusiones de `TABLES` / `TABLES_EXCLUDE`
+#[derive(Debug, Clone, Default)]
+pub struct TableSelection {
+    tables: Vec<String>,
+    patterns: Vec<TablePattern>,
+    exclude: Vec<TablePattern>,
+}
+
+impl TableSelection {
+    /// Parsear `TABLES` (nombres y patrones separados por coma) y `TABLES_EXCLUDE`
+    pub fn parse(tables: &str, exclude: &str) -> Result<Self> {
+        let mut selection = Self::default();
+        for entry in tables.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            if entry.contains('*') {
+                selection.patterns.push(TablePattern::parse(entry)?);
+            } else {
+                selection.tables.push(entry.to_string());
+            }
+        }
+        for entry in exclude.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            selection.exclude.push(TablePattern::parse(entry)?);
+        }
+        Ok(selection)
+    }
+
+    /// Selección con otras tablas y patrones (`ReloadConfig`); conserva las exclusiones
+    pub fn with_tables(&self, entries: &[String]) -> Result<Self> {
+        let mut selection = Self::parse(&entries.join(","), "")?;
+        selection.exclude = self.exclude.clone();
+        Ok(selection)
+    }
+
+    /// Tablas con nombre explícito (se replican aunque coincidan con una exclusión)
+    pub fn tables(&self) -> &[String] {
+        &self.tables
+    }
+
+    pub fn has_patterns(&self) -> bool {
+        !self.patterns.is_empty()
+    }
+
+    /// Indica si una tabla del catálogo coincide con algún patrón y no está excluida
+    pub fn matches(&self, namespace: &str, table: &str) -> bool {
+        !table.starts_with(INTERNAL_PREFIX)
+            && self.patterns.iter().any(|p| p.matches(namespace, table))
+            && !self.exclude.iter().any(|p| p.matches(namespace, table))
+    }
+}
+
+impl fmt::Display for TableSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries: Vec<String> = self
+            .tables
+            .iter()
+            .cloned()
+            .chain(self.patterns.iter().map(|p| p.to_string()))
+            .collect();
+        write!(f, "{}", entries.join(", "))?;
+        if !self.exclude.is_empty() {
+            let exclude: Vec<String> = self.exclude.iter().map(|p| p.to_string()).collect();
+            write!(f, " (except {})", exclude.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patterns_and_exclusions() {
+        let selection = TableSelection::parse("orders, public.*, sales.order_*", "public.tmp_*,audit_log").unwrap();
+        assert_eq!(selection.tables(), ["orders"]);
+        assert!(selection.matches("public", "customers"));
+        assert!(selection.matches("sales", "order_items"));
+        assert!(!selection.matches("sales", "invoices"));
+        assert!(!selection.matches("public", "tmp_import"));
+        assert!(!selection.matches("public", "audit_log"));
+        // Tablas internas de dbmazz
+        assert!(!selection.matches("public", "dbmazz_checkpoints"));
+
+        let tenants = TableSelection::parse("tenant_*.orders", "").unwrap();
+        assert!(tenants.matches("tenant_a", "orders"));
+        assert!(!tenants.matches("tenant_a", "items"));
+        assert!(!TableSelection::parse("orders,items", "").unwrap().has_patterns());
+
+        // ReloadConfig reemplaza tablas y patrones pero conserva las exclusiones
+        let reloaded = selection.with_tables(&["items".to_string(), "public.t*".to_string()]).unwrap();
+        assert_eq!(reloaded.tables(), ["items"]);
+        assert!(reloaded.matches("public", "tickets"));
+        assert!(!reloaded.matches("public", "tmp_import"));
+        assert!(!reloaded.matches("sales", "order_items"));
+        assert!(TableSelection::parse("public.a*b*", "").is_err());
+    }
+}
@@ -0,0 +1,209 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Spill a disco de transacciones en streaming.
+//!
+//! Una transacción en curso que supera `STREAM_SPILL_THRESHOLD` cambios en memoria
+//! pasa sus cambios a un archivo temporal. Cada cambio se guarda con el mismo
+//! formato de `pgoutput` (sin el prefijo de xid) y se vuelve a parsear en el
+//! Stream Commit, por bloques, a medida que el pipeline los consume. El archivo
+//! se borra al confirmar o abortar la transacción. La E/S usa `tokio::fs` para
+//! no bloquear el runtime.
+
+use anyhow::{anyhow, Context, Result};
+use bytes::{BufMut, Bytes, BytesMut};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+use crate::source::parser::{CdcMessage, PgOutputParser, Tuple, TupleData};
+
+/// Archivo con los cambios de una transacción en streaming
+pub struct SpillFile {
+    path: PathBuf,
+    file: File,
+    /// Posición en bytes de cada cambio (para descartar subtransacciones abortadas)
+    offsets: Vec<u64>,
+    len: u64,
+    /// Siguiente cambio a leer con `read_chunk`
+    read_index: usize,
+}
+
+impl SpillFile {
+    pub async fn create(dir: &Path, xid: u32) -> Result<Self> {
+        let path = dir.join(format!("dbmazz-stream-{}-{}.spill", std::process::id(), xid));
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .read(true)
+            .write(true)
+            .open(&path)
+            .await
+            .with_context(|| format!("Failed to create spill file {}", path.display()))?;
+        Ok(Self { path, file, offsets: Vec::new(), len: 0, read_index: 0 })
+    }
+
+    /// Cambios guardados en el archivo
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Agregar cambios al final del archivo
+    pub async fn append(&mut self, changes: &[CdcMessage]) -> Result<()> {
+        let mut buf = BytesMut::new();
+        let mut offsets = Vec::with_capacity(changes.len());
+        for msg in changes {
+            offsets.push(self.len + buf.len() as u64);
+            let start = buf.len();
+            buf.put_u32(0);
+            encode_change(msg, &mut buf)?;
+            let record_len = (buf.len() - start - 4) as u32;
+            buf[start..start + 4].copy_from_slice(&record_len.to_be_bytes());
+        }
+        self.file.seek(SeekFrom::Start(self.len)).await?;
+        self.file
+            .write_all(&buf)
+            .await
+            .with_context(|| format!("Failed to write spill file {}", self.path.display()))?;
+        self.file.flush().await?;
+        self.len += buf.len() as u64;
+        self.offsets.extend(offsets);
+        Ok(())
+    }
+
+    /// Descartar los cambios desde la posición `count` (abort de subtransacción)
+    pub async fn truncate(&mut self, count: usize) -> Result<()> {
+        if count >= self.offsets.len() {
+            return Ok(());
+        }
+        self.len = self.offsets[count];
+        self.offsets.truncate(count);
+        self.file.set_len(self.len).await?;
+        Ok(())
+    }
+
+    /// Leer en orden los siguientes `max` cambios (vacío al terminar el archivo)
+    pub async fn read_chunk(&mut self, max: usize) -> Result<Vec<CdcMessage>> {
+        let start = self.read_index;
+        let end = (start + max.max(1)).min(self.offsets.len());
+        if start >= end {
+            return Ok(Vec::new());
+        }
+        let from = self.offsets[start];
+        let to = self.offsets.get(end).copied().unwrap_or(self.len);
+        let mut data = vec![0u8; (to - from) as usize];
+        self.file.seek(SeekFrom::Start(from)).await?;
+        self.file
+            .read_exact(&mut data)
+            .await
+            .with_context(|| format!("Failed to read spill file {}", self.path.display()))?;
+        self.read_index = end;
+        let mut data = Bytes::from(data);
+
+        let mut parser = PgOutputParser::new();
+        let mut changes = Vec::with_capacity(end - start);
+        while !data.is_empty() {
+            if data.len() < 5 {
+                return Err(anyhow!("Truncated record in spill file {}", self.path.display()));
+            }
+            let record_len = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+            if data.len() < 4 + record_len {
+                return Err(anyhow!("Truncated record in spill file {}", self.path.display()));
+            }
+            let mut record = data.split_to(4 + record_len).split_off(4);
+            let tag = record.split_to(1)[0];
+            match parser.parse(tag, record)? {
+                Some(msg) => changes.push(msg),
+                None => return Err(anyhow!("Unexpected record '{}' in spill file", tag as char)),
+            }
+        }
+        Ok(changes)
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let path = std::mem::take(&mut self.path);
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn_blocking(move || std::fs::remove_file(path));
+            }
+            Err(_) => {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+}
+
+/// Codificar un cambio con el formato de `pgoutput`: tag + cuerpo
+fn encode_change(msg: &CdcMessage, buf: &mut BytesMut) -> Result<()> {
+    match msg {
+        CdcMessage::Insert { relation_id, tuple } => {
+            buf.put_u8(b'I');
+            buf.put_u32(*relation_id);
+            buf.put_u8(b'N');
+            encode_tuple(tuple, buf);
+        }
+        CdcMessage::Update { relation_id, old_tuple, new_tuple } => {
+            buf.put_u8(b'U');
+            buf.put_u32(*relation_id);
+            if let Some(old) = old_tuple {
+                buf.put_u8(b'O');
+                encode_tuple(old, buf);
+            }
+            buf.put_u8(b'N');
+            encode_tuple(new_tuple, buf);
+        }
+        CdcMessage::Delete { relation_id, old_tuple } => {
+            buf.put_u8(b'D');
+            buf.put_u32(*relation_id);
+            match old_tuple {
+                Some(old) => {
+                    buf.put_u8(b'O');
+                    encode_tuple(old, buf);
+                }
+                None => buf.put_u8(0),
+            }
+        }
+        CdcMessage::Truncate { relation_ids, cascade, restart_identity } => {
+            buf.put_u8(b'T');
+            buf.put_u32(relation_ids.len() as u32);
+            buf.put_u8(*cascade as u8 | (*restart_identity as u8) << 1);
+            for id in relation_ids {
+                buf.put_u32(*id);
+            }
+        }
+        CdcMessage::Message { lsn, prefix, content, transactional } => {
+            buf.put_u8(b'M');
+            buf.put_u8(*transactional as u8);
+            buf.put_u64(*lsn);
+            buf.put_slice(prefix.as_bytes());
+            buf.put_u8(0);
+            buf.put_u32(content.len() as u32);
+            buf.put_slice(content);
+        }
+        other => return Err(anyhow!("Cannot spill message {:?}", other)),
+    }
+    Ok(())
+}
+
+fn encode_tuple(tuple: &Tuple, buf: &mut BytesMut) {
+    buf.put_u16(tuple.cols.len() as u16);
+    for col in &tuple.cols {
+        match col {
+            TupleData::Null => buf.put_u8(b'n'),
+            TupleData::Toast => buf.put_u8(b'u'),
+            TupleData::Text(bytes) => {
+                buf.put_u8(b't');
+                buf.put_u32(bytes.len() as u32);
+                buf.put_slice(bytes);
+            }
+            TupleData::Binary(bytes) => {
+                buf.put_u8(b'b');
+                buf.put_u32(bytes.len() as u32);
+                buf.put_slice(bytes);
+            }
+        }
+    }
+}
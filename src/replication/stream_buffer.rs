@@ -0,0 +1,476 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+use anyhow::Result;
+use hashbrown::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::replication::spill::SpillFile;
+use crate::source::parser::{CdcEvent, CdcMessage, StreamContext};
+
+/// Cambios que se leen del spill por cada bloque enviado al pipeline
+const SPILL_READ_CHUNK: usize = 1000;
+
+/// Cambios de una transacción en curso recibidos en bloques de streaming
+#[derive(Default)]
+struct StreamedTxn {
+    origin: Option<Arc<str>>,
+    /// Cambios en memoria; los más antiguos pueden estar en `spill`
+    changes: Vec<CdcMessage>,
+    /// Posición del primer cambio de cada subtransacción, en orden de aparición.
+    /// Un abort de subtransacción trunca desde ahí (incluye sus hijas, que van después).
+    subxacts: Vec<(u32, usize)>,
+    spill: Option<SpillFile>,
+}
+
+impl StreamedTxn {
+    fn spilled(&self) -> usize {
+        self.spill.as_ref().map_or(0, |f| f.len())
+    }
+
+    /// Cambios totales (en disco y en memoria)
+    fn len(&self) -> usize {
+        self.spilled() + self.changes.len()
+    }
+
+    /// Descartar los cambios desde la posición `offset`
+    async fn truncate(&mut self, offset: usize) -> Result<()> {
+        let spilled = self.spilled();
+        if offset >= spilled {
+            self.changes.truncate(offset - spilled);
+        } else if let Some(spill) = self.spill.as_mut() {
+            spill.truncate(offset).await?;
+            self.changes.clear();
+        }
+        Ok(())
+    }
+}
+
+/// Transacción preparada (`PREPARE TRANSACTION`) a la espera de COMMIT/ROLLBACK PREPARED.
+/// Si llegó en streaming conserva su spill.
+struct PreparedTxn {
+    /// LSN del PREPARE: el checkpoint no puede pasarlo mientras siga pendiente,
+    /// o PostgreSQL no reenviaría los cambios tras un reinicio
+    prepare_lsn: u64,
+    txn: StreamedTxn,
+}
+
+/// Transacción confirmada (Stream Commit / COMMIT PREPARED) que se entrega al
+/// pipeline por bloques: Begin, cambios del spill, cambios en memoria y Commit.
+/// El spill se lee a medida que el pipeline consume, sin volver a cargar toda la
+/// transacción en memoria.
+///
+/// Los cambios usan commit_lsn: confirmar ese LSN no salta la transacción al
+/// reiniciar. Solo el Commit final avanza el checkpoint a end_lsn.
+pub struct CommittedTxn {
+    txn: StreamedTxn,
+    xid: u32,
+    flags: u8,
+    commit_lsn: u64,
+    end_lsn: u64,
+    timestamp: u64,
+    read_chunk: usize,
+    begun: bool,
+    done: bool,
+}
+
+impl CommittedTxn {
+    fn new(txn: StreamedTxn, xid: u32, flags: u8, commit_lsn: u64, end_lsn: u64, timestamp: u64) -> Self {
+        Self { txn, xid, flags, commit_lsn, end_lsn, timestamp, read_chunk: SPILL_READ_CHUNK, begun: false, done: false }
+    }
+
+    /// Siguiente bloque de eventos en orden (None al terminar la transacción)
+    pub async fn next_events(&mut self) -> Result<Option<Vec<CdcEvent>>> {
+        if !self.begun {
+            self.begun = true;
+            let begin = CdcMessage::Begin { final_lsn: self.commit_lsn, timestamp: self.timestamp, xid: self.xid };
+            return Ok(Some(vec![self.event(self.commit_lsn, begin)]));
+        }
+        if let Some(spill) = self.txn.spill.as_mut() {
+            let changes = spill.read_chunk(self.read_chunk).await?;
+            if !changes.is_empty() {
+                return Ok(Some(self.events(changes)));
+            }
+            // Leído completo: se borra el archivo
+            self.txn.spill = None;
+        }
+        if !self.txn.changes.is_empty() {
+            let changes = std::mem::take(&mut self.txn.changes);
+            return Ok(Some(self.events(changes)));
+        }
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+        let commit = CdcMessage::Commit {
+            flags: self.flags,
+            commit_lsn: self.commit_lsn,
+            end_lsn: self.end_lsn,
+            timestamp: self.timestamp,
+        };
+        Ok(Some(vec![self.event(self.end_lsn, commit)]))
+    }
+
+    fn events(&self, changes: Vec<CdcMessage>) -> Vec<CdcEvent> {
+        changes.into_iter().map(|message| self.event(self.commit_lsn, message)).collect()
+    }
+
+    fn event(&self, lsn: u64, message: CdcMessage) -> CdcEvent {
+        CdcEvent { lsn, message, origin: self.txn.origin.clone() }
+    }
+}
+
+/// Qué hacer con un mensaje después de pasar por el buffer
+pub enum Routed {
+    /// Mensaje fuera de una transacción retenida (o de schema): va al pipeline
+    Forward(CdcMessage),
+    /// Guardado (o descartado) hasta el commit
+    Buffered,
+    /// Stream Commit / COMMIT PREPARED: la transacción completa, lista para el pipeline
+    Committed(CommittedTxn),
+}
+
+/// Buffer de transacciones que PostgreSQL envía antes de su commit: las grandes
+/// en streaming (`streaming 'on'`, por xid) y las preparadas (`two_phase 'on'`, por
+/// gid). Los cambios solo llegan al pipeline cuando el commit se confirma; los
+/// aborts y ROLLBACK PREPARED se descartan.
+#[derive(Default)]
+pub struct StreamBuffer {
+    txns: HashMap<u32, StreamedTxn>,
+    /// Transacción entre Begin Prepare y Prepare
+    preparing: Option<PreparedTxn>,
+    prepared: HashMap<String, PreparedTxn>,
+    /// Origen de la transacción normal en curso (Begin ... Commit)
+    origin: Option<Arc<str>>,
+    /// Cambios en memoria por transacción en streaming antes de pasar a disco (0 = nunca)
+    spill_threshold: usize,
+    spill_dir: PathBuf,
+}
+
+impl StreamBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Configura el spill a disco de transacciones en streaming grandes
+    pub fn with_spill(mut self, threshold: usize, dir: PathBuf) -> Self {
+        self.spill_threshold = threshold;
+        self.spill_dir = dir;
+        self
+    }
+
+    /// Eventos pendientes en transacciones abiertas (incluye los pasados a disco)
+    pub fn buffered_changes(&self) -> usize {
+        self.txns.values().map(|t| t.len()).sum::<usize>()
+            + self.prepared.values().map(|t| t.txn.len()).sum::<usize>()
+    }
+
+    /// Origen de la transacción en curso para los mensajes que pasan directo
+    pub fn origin(&self) -> Option<Arc<str>> {
+        self.origin.clone()
+    }
+
+    /// Limita el LSN a confirmar para no saltar transacciones preparadas pendientes
+    pub fn clamp_checkpoint(&self, lsn: u64) -> u64 {
+        self.prepared
+            .values()
+            .map(|t| t.prepare_lsn)
+            .chain(self.preparing.as_ref().map(|t| t.prepare_lsn))
+            .fold(lsn, u64::min)
+    }
+
+    /// Falla si no se puede escribir el spill de una transacción en streaming
+    pub async fn route(&mut self, msg: CdcMessage, stream: Option<StreamContext>) -> Result<Routed> {
+        Ok(match msg {
+            CdcMessage::StreamStart { xid, .. } => {
+                self.txns.entry(xid).or_default();
+                Routed::Buffered
+            }
+            CdcMessage::StreamStop => Routed::Buffered,
+            CdcMessage::StreamAbort { xid, subxid } => {
+                self.abort(xid, subxid).await?;
+                Routed::Buffered
+            }
+            CdcMessage::StreamCommit { xid, flags, commit_lsn, end_lsn, timestamp } => {
+                let txn = self.txns.remove(&xid).unwrap_or_default();
+                Routed::Committed(CommittedTxn::new(txn, xid, flags, commit_lsn, end_lsn, timestamp))
+            }
+            CdcMessage::BeginPrepare { prepare_lsn, .. } => {
+                self.preparing = Some(PreparedTxn { prepare_lsn, txn: StreamedTxn::default() });
+                Routed::Buffered
+            }
+            CdcMessage::Prepare { prepare_lsn, xid, gid, streamed, .. } => {
+                // Una transacción preparada en streaming conserva su spill hasta el COMMIT PREPARED
+                let txn = if streamed {
+                    self.txns.remove(&xid).unwrap_or_default()
+                } else {
+                    self.preparing.take().map(|t| t.txn).unwrap_or_default()
+                };
+                println!("⏸️  Transaction '{}' prepared: {} changes held until COMMIT PREPARED", gid, txn.len());
+                self.prepared.insert(gid, PreparedTxn { prepare_lsn, txn });
+                Routed::Buffered
+            }
+            CdcMessage::CommitPrepared { flags, commit_lsn, end_lsn, timestamp, xid, gid } => {
+                let txn = match self.prepared.remove(&gid) {
+                    Some(prepared) => prepared.txn,
+                    None => {
+                        eprintln!("⚠️  COMMIT PREPARED for unknown transaction '{}'", gid);
+                        StreamedTxn::default()
+                    }
+                };
+                Routed::Committed(CommittedTxn::new(txn, xid, flags, commit_lsn, end_lsn, timestamp))
+            }
+            CdcMessage::RollbackPrepared { gid, .. } => {
+                if let Some(prepared) = self.prepared.remove(&gid) {
+                    println!("🗑️  Transaction '{}' rolled back: {} changes discarded", gid, prepared.txn.len());
+                }
+                Routed::Buffered
+            }
+            // El origen llega después del Begin (o del primer Stream Start)
+            CdcMessage::Origin { name, .. } => {
+                let origin = Some(Arc::from(name));
+                if let Some(ctx) = stream {
+                    self.txns.entry(ctx.top_xid).or_default().origin = origin;
+                } else if let Some(prepared) = self.preparing.as_mut() {
+                    prepared.txn.origin = origin;
+                } else {
+                    self.origin = origin;
+                }
+                Routed::Buffered
+            }
+            CdcMessage::Begin { .. } => {
+                self.origin = None;
+                Routed::Forward(msg)
+            }
+            // Los cambios de schema se aplican de inmediato, como en un apply worker
+            CdcMessage::Relation { .. } | CdcMessage::Type { .. } => Routed::Forward(msg),
+            // Los mensajes no transaccionales no esperan ningún commit
+            CdcMessage::Message { transactional: false, .. } => Routed::Forward(msg),
+            msg => {
+                if let Some(ctx) = stream {
+                    self.push(ctx, msg).await;
+                    return Ok(Routed::Buffered);
+                }
+                match self.preparing.as_mut() {
+                    Some(prepared) if is_change(&msg) => {
+                        prepared.txn.changes.push(msg);
+                        Routed::Buffered
+                    }
+                    _ => Routed::Forward(msg),
+                }
+            }
+        })
+    }
+
+    async fn push(&mut self, ctx: StreamContext, msg: CdcMessage) {
+        let txn = self.txns.entry(ctx.top_xid).or_default();
+        if ctx.xid != ctx.top_xid && !txn.subxacts.iter().any(|(xid, _)| *xid == ctx.xid) {
+            txn.subxacts.push((ctx.xid, txn.len()));
+        }
+        txn.changes.push(msg);
+
+        if self.spill_threshold > 0 && txn.changes.len() >= self.spill_threshold {
+            if let Err(e) = spill(txn, ctx.top_xid, &self.spill_dir).await {
+                // Sin disco la transacción sigue en memoria
+                eprintln!("⚠️  Could not spill streamed transaction {} to disk: {}", ctx.top_xid, e);
+            }
+        }
+    }
+
+    async fn abort(&mut self, xid: u32, subxid: u32) -> Result<()> {
+        if xid == subxid {
+            if let Some(txn) = self.txns.remove(&xid) {
+                println!("🗑️  Streamed transaction {} aborted: {} changes discarded", xid, txn.len());
+            }
+            return Ok(());
+        }
+
+        let Some(txn) = self.txns.get_mut(&xid) else {
+            return Ok(());
+        };
+        if let Some(pos) = txn.subxacts.iter().position(|(sub, _)| *sub == subxid) {
+            let offset = txn.subxacts[pos].1;
+            txn.truncate(offset).await?;
+            txn.subxacts.truncate(pos);
+        }
+        Ok(())
+    }
+}
+
+/// Pasar a disco los cambios en memoria de una transacción en streaming
+async fn spill(txn: &mut StreamedTxn, xid: u32, dir: &std::path::Path) -> Result<()> {
+    if txn.spill.is_none() {
+        txn.spill = Some(SpillFile::create(dir, xid).await?);
+        println!("💾 Streamed transaction {} exceeds {} changes in memory, spilling to disk", xid, txn.changes.len());
+    }
+    if let Some(file) = txn.spill.as_mut() {
+        file.append(&txn.changes).await?;
+        txn.changes.clear();
+    }
+    Ok(())
+}
+
+/// Cambios de fila y mensajes (lo que se acumula dentro de una transacción preparada)
+fn is_change(msg: &CdcMessage) -> bool {
+    matches!(
+        msg,
+        CdcMessage::Insert { .. }
+            | CdcMessage::Update { .. }
+            | CdcMessage::Delete { .. }
+            | CdcMessage::Truncate { .. }
+            | CdcMessage::Message { .. }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::parser::{Tuple, TupleData};
+    use bytes::Bytes;
+
+    fn insert(value: &'static str) -> CdcMessage {
+        CdcMessage::Insert {
+            relation_id: 1,
+            tuple: Tuple { cols: vec![TupleData::Text(Bytes::from_static(value.as_bytes()))], toast_bitmap: Default::default() },
+        }
+    }
+
+    fn ctx(top_xid: u32, xid: u32) -> Option<StreamContext> {
+        Some(StreamContext { top_xid, xid })
+    }
+
+    async fn collect(mut txn: CommittedTxn) -> Vec<CdcEvent> {
+        let mut events = Vec::new();
+        while let Some(chunk) = txn.next_events().await.unwrap() {
+            events.extend(chunk);
+        }
+        events
+    }
+
+    fn commit(xid: u32) -> CdcMessage {
+        CdcMessage::StreamCommit { xid, flags: 0, commit_lsn: 100, end_lsn: 200, timestamp: 0 }
+    }
+
+    #[tokio::test]
+    async fn test_subxact_abort_discards_its_changes_and_children() {
+        let mut buffer = StreamBuffer::new();
+        buffer.route(CdcMessage::StreamStart { xid: 10, first_segment: true }, None).await.unwrap();
+        buffer.route(insert("a"), ctx(10, 10)).await.unwrap();
+        buffer.route(insert("b"), ctx(10, 11)).await.unwrap();
+        buffer.route(insert("c"), ctx(10, 12)).await.unwrap();
+        buffer.route(CdcMessage::StreamAbort { xid: 10, subxid: 11 }, None).await.unwrap();
+        buffer.route(insert("d"), ctx(10, 10)).await.unwrap();
+
+        let Routed::Committed(txn) = buffer.route(commit(10), None).await.unwrap() else {
+            panic!("expected committed transaction");
+        };
+        let events = collect(txn).await;
+
+        // Begin + a + d + Commit
+        assert_eq!(events.len(), 4);
+        assert!(matches!(events[0].message, CdcMessage::Begin { xid: 10, .. }));
+        assert_eq!(events[1].lsn, 100);
+        assert!(matches!(events[3].message, CdcMessage::Commit { end_lsn: 200, .. }));
+        assert_eq!(events[3].lsn, 200);
+        assert_eq!(buffer.buffered_changes(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_toplevel_abort_discards_transaction() {
+        let mut buffer = StreamBuffer::new();
+        buffer.route(CdcMessage::StreamStart { xid: 20, first_segment: true }, None).await.unwrap();
+        buffer.route(insert("a"), ctx(20, 20)).await.unwrap();
+        buffer.route(CdcMessage::StreamAbort { xid: 20, subxid: 20 }, None).await.unwrap();
+        assert_eq!(buffer.buffered_changes(), 0);
+
+        // Fuera de un bloque de streaming los mensajes pasan directo
+        assert!(matches!(buffer.route(insert("b"), None).await.unwrap(), Routed::Forward(_)));
+    }
+
+    #[tokio::test]
+    async fn test_prepared_transaction_waits_for_commit_prepared() {
+        let mut buffer = StreamBuffer::new();
+        buffer.route(
+            CdcMessage::BeginPrepare { prepare_lsn: 50, end_lsn: 60, timestamp: 0, xid: 30, gid: "g1".into() },
+            None,
+        ).await.unwrap();
+        assert!(matches!(buffer.route(insert("a"), None).await.unwrap(), Routed::Buffered));
+        buffer.route(
+            CdcMessage::Prepare { flags: 0, prepare_lsn: 50, end_lsn: 60, timestamp: 0, xid: 30, gid: "g1".into(), streamed: false },
+            None,
+        ).await.unwrap();
+
+        // Mientras está preparada, el checkpoint no puede pasar el PREPARE
+        assert_eq!(buffer.clamp_checkpoint(500), 50);
+
+        let commit = CdcMessage::CommitPrepared {
+            flags: 0, commit_lsn: 90, end_lsn: 100, timestamp: 0, xid: 30, gid: "g1".into(),
+        };
+        let Routed::Committed(txn) = buffer.route(commit, None).await.unwrap() else {
+            panic!("expected committed transaction");
+        };
+        let events = collect(txn).await;
+        assert_eq!(events.len(), 3);
+        assert_eq!(buffer.clamp_checkpoint(500), 500);
+    }
+
+    #[tokio::test]
+    async fn test_origin_is_kept_per_transaction() {
+        let mut buffer = StreamBuffer::new();
+        buffer.route(CdcMessage::StreamStart { xid: 40, first_segment: true }, None).await.unwrap();
+        buffer.route(CdcMessage::Origin { commit_lsn: 0, name: "writeback".into() }, ctx(40, 40)).await.unwrap();
+        buffer.route(insert("a"), ctx(40, 40)).await.unwrap();
+        buffer.route(CdcMessage::StreamStop, None).await.unwrap();
+
+        // Una transacción normal intercalada no hereda el origen del stream
+        buffer.route(CdcMessage::Begin { final_lsn: 0, timestamp: 0, xid: 41 }, None).await.unwrap();
+        assert!(buffer.origin().is_none());
+
+        let Routed::Committed(txn) = buffer.route(commit(40), None).await.unwrap() else {
+            panic!("expected committed transaction");
+        };
+        let events = collect(txn).await;
+        assert!(events.iter().all(|e| e.origin.as_deref() == Some("writeback")));
+    }
+
+    #[tokio::test]
+    async fn test_spilled_transaction_keeps_order_and_subxact_abort() {
+        let mut buffer = StreamBuffer::new().with_spill(2, std::env::temp_dir());
+        buffer.route(CdcMessage::StreamStart { xid: 50, first_segment: true }, None).await.unwrap();
+        buffer.route(insert("a"), ctx(50, 50)).await.unwrap();
+        buffer.route(insert("b"), ctx(50, 51)).await.unwrap();
+        // a, b ya están en disco; el abort de 51 trunca el archivo
+        buffer.route(insert("c"), ctx(50, 51)).await.unwrap();
+        buffer.route(CdcMessage::StreamAbort { xid: 50, subxid: 51 }, None).await.unwrap();
+        buffer.route(insert("d"), ctx(50, 50)).await.unwrap();
+        buffer.route(insert("e"), ctx(50, 50)).await.unwrap();
+        buffer.route(insert("f"), ctx(50, 50)).await.unwrap();
+        assert_eq!(buffer.buffered_changes(), 4);
+
+        let Routed::Committed(mut txn) = buffer.route(commit(50), None).await.unwrap() else {
+            panic!("expected committed transaction");
+        };
+        // a, d, e están en disco y se leen por bloques; f sigue en memoria
+        txn.read_chunk = 2;
+        let mut events = Vec::new();
+        let mut chunks = Vec::new();
+        while let Some(chunk) = txn.next_events().await.unwrap() {
+            chunks.push(chunk.len());
+            events.extend(chunk);
+        }
+        assert_eq!(chunks, [1, 2, 1, 1, 1]);
+        let values: Vec<&[u8]> = events[1..events.len() - 1]
+            .iter()
+            .map(|e| match &e.message {
+                CdcMessage::Insert { tuple, .. } => match &tuple.cols[0] {
+                    TupleData::Text(b) => &b[..],
+                    other => panic!("unexpected column {:?}", other),
+                },
+                other => panic!("unexpected message {:?}", other),
+            })
+            .collect();
+        assert_eq!(values, [b"a".as_slice(), b"d", b"e", b"f"]);
+        assert_eq!(buffer.buffered_changes(), 0);
+    }
+}
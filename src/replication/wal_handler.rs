@@ -1,9 +1,12 @@
 use anyhow::Result;
 use bytes::{Buf, Bytes};
 use futures::SinkExt;
+use std::path::PathBuf;
 use tokio::sync::mpsc;
 
-use crate::source::parser::{CdcEvent, PgOutputParser};
+use crate::replication::stream_buffer::{Routed, StreamBuffer};
+use crate::source::binary::BINARY_TYPES;
+use crate::source::parser::{CdcEvent, CdcMessage, PgOutputParser};
 use crate::source::postgres::build_standby_status_update;
 use crate::grpc::state::SharedState;
 
@@ -60,13 +63,54 @@ pub fn parse_replication_message(bytes: &mut Bytes) -> Option<WalMessage> {
     }
 }
 
+/// Estado de decodificación del stream: parser + buffer de transacciones en streaming
+#[derive(Default)]
+pub struct WalDecoder {
+    parser: PgOutputParser,
+    streams: StreamBuffer,
+    /// El stream usa `binary 'true'`
+    binary: bool,
+    /// Llegó una tabla con un tipo sin decodificación binaria
+    text_required: bool,
+}
+
+impl WalDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Transacciones en streaming con más de `threshold` cambios en memoria pasan a disco
+    pub fn with_spill(mut self, threshold: usize, dir: PathBuf) -> Self {
+        self.streams = self.streams.with_spill(threshold, dir);
+        self
+    }
+
+    /// Stream con tuplas binarias: cada Relation se valida contra `BINARY_TYPES`
+    pub fn with_binary(mut self, binary: bool) -> Self {
+        self.binary = binary;
+        self
+    }
+
+    /// Indica que hay que reiniciar el stream con tuplas en texto. Una columna
+    /// agregada después del START_REPLICATION (o una tabla nueva en la
+    /// publication) puede tener un tipo que no se decodifica desde binario.
+    pub fn text_required(&self) -> bool {
+        self.text_required
+    }
+
+    /// LSN que se puede confirmar sin perder transacciones preparadas pendientes
+    pub fn clamp_checkpoint(&self, lsn: u64) -> u64 {
+        self.streams.clamp_checkpoint(lsn)
+    }
+}
+
 /// Procesar datos XLogData
 pub async fn handle_xlog_data(
-    mut data: Bytes,
+    data: Bytes,
     lsn: u64,
     tx: &mpsc::Sender<CdcEvent>,
     shared_state: &SharedState,
-    flush_size: usize,
+    decoder: &mut WalDecoder,
 ) -> Result<()> {
     // Actualizar LSN en SharedState
     shared_state.update_lsn(lsn);
@@ -78,29 +122,62 @@ pub async fn handle_xlog_data(
     let pgoutput_tag = data[0];
     let pgoutput_body = data.slice(1..);
 
-    match PgOutputParser::parse(pgoutput_tag, pgoutput_body) {
-        Ok(Some(cdc_msg)) => {
-            let event = CdcEvent {
-                lsn,
-                message: cdc_msg,
-            };
-            
-            shared_state.increment_events();
-            
-            // Update pending events count
-            let capacity = tx.capacity();
-            let pending = (flush_size * 2) - capacity;
-            shared_state.set_pending(pending as u64);
-            
-            if let Err(e) = tx.send(event).await {
-                eprintln!("Failed to send to pipeline: {}", e);
-                return Err(e.into());
+    let cdc_msg = match decoder.parser.parse(pgoutput_tag, pgoutput_body) {
+        Ok(Some(cdc_msg)) => cdc_msg,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            eprintln!("Parse error: {}", e);
+            return Ok(());
+        }
+    };
+
+    // El Relation llega antes que las filas: no se envía nada de la tabla hasta
+    // reiniciar en texto
+    if decoder.binary {
+        if let CdcMessage::Relation { namespace, name, columns, .. } = &cdc_msg {
+            if let Some(column) = columns.iter().find(|c| !BINARY_TYPES.contains(&c.type_id)) {
+                eprintln!(
+                    "⚠️  Column {}.{}.{} (type oid {}) has no binary decoding, restarting replication with text tuples",
+                    namespace, name, column.name, column.type_id
+                );
+                decoder.text_required = true;
+                return Ok(());
             }
         }
-        Ok(None) => {}
-        Err(e) => eprintln!("Parse error: {}", e),
     }
 
+    // Transacciones en streaming: se acumulan por xid hasta el Stream Commit
+    let stream = decoder.parser.stream_context();
+    match decoder.streams.route(cdc_msg, stream).await? {
+        Routed::Forward(message) => {
+            let origin = decoder.streams.origin();
+            send_event(tx, shared_state, CdcEvent { lsn, message, origin }).await
+        }
+        Routed::Buffered => Ok(()),
+        // Por bloques: el canal acota lo que se lee del spill
+        Routed::Committed(mut txn) => {
+            while let Some(events) = txn.next_events().await? {
+                for event in events {
+                    send_event(tx, shared_state, event).await?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Enviar un evento al pipeline actualizando métricas
+async fn send_event(tx: &mpsc::Sender<CdcEvent>, shared_state: &SharedState, event: CdcEvent) -> Result<()> {
+    shared_state.increment_events();
+
+    // Update pending events count (la capacidad del canal es fija, no sigue a flush_size)
+    let pending = tx.max_capacity() - tx.capacity();
+    shared_state.set_pending(pending as u64);
+
+    if let Err(e) = tx.send(event).await {
+        eprintln!("Failed to send to pipeline: {}", e);
+        return Err(e.into());
+    }
     Ok(())
 }
 
@@ -3,6 +3,8 @@ use curl::easy::{Easy, List};
 use std::io::Read;
 use std::sync::Arc;
 
+use crate::sink::table_map::TargetTable;
+
 // TODO: Este módulo maneja redirects FE→BE de StarRocks con reescritura de 127.0.0.1.
 // No se ha validado si esta implementación es óptima en términos de:
 // - Performance (double request overhead, manejo de conexiones)
@@ -18,19 +20,26 @@ pub struct LoadResult {
     pub message: String,
 }
 
+/// Header `columns` de un Stream Load
+#[derive(Debug, Clone)]
+pub struct LoadColumns {
+    /// Campos del JSON y expresiones `columna=expr` (ej. `to_binary` para bytea)
+    pub columns: Vec<String>,
+    /// Partial update: solo se escriben estas columnas
+    pub partial: bool,
+}
+
 /// Cliente Stream Load usando libcurl (soporta Expect: 100-continue correctamente)
 pub struct CurlStreamLoader {
     base_url: String,
-    database: String,
     user: String,
     pass: String,
 }
 
 impl CurlStreamLoader {
-    pub fn new(base_url: String, database: String, user: String, pass: String) -> Self {
+    pub fn new(base_url: String, user: String, pass: String) -> Self {
         Self {
             base_url: base_url.trim_end_matches('/').to_string(),
-            database,
             user,
             pass,
         }
@@ -39,22 +48,22 @@ impl CurlStreamLoader {
     /// Envía datos a StarRocks via Stream Load (ejecuta en thread pool para no bloquear async)
     pub async fn send(
         &self,
-        table_name: &str,
+        target: &TargetTable,
         body: Arc<Vec<u8>>,
-        partial_columns: Option<Vec<String>>,
+        columns: Option<LoadColumns>,
     ) -> Result<LoadResult> {
         let url = format!(
             "{}/api/{}/{}/_stream_load",
-            self.base_url, self.database, table_name
+            self.base_url, target.database, target.table
         );
         let user = self.user.clone();
         let pass = self.pass.clone();
-        let table = table_name.to_string();
+        let table = target.table.clone();
         let body = body.clone();
         
         // spawn_blocking para no bloquear el runtime async
         tokio::task::spawn_blocking(move || {
-            Self::send_sync(&url, &user, &pass, &table, body, partial_columns)
+            Self::send_sync(&url, &user, &pass, &table, body, columns)
         })
         .await
         .map_err(|e| anyhow!("Task join error: {}", e))?
@@ -66,7 +75,7 @@ impl CurlStreamLoader {
         pass: &str,
         table_name: &str,
         body: Arc<Vec<u8>>,
-        partial_columns: Option<Vec<String>>,
+        columns: Option<LoadColumns>,
     ) -> Result<LoadResult> {
         // Extraer hostname original para reescribir redirects de 127.0.0.1
         let original_hostname = Self::extract_hostname(url)?;
@@ -92,12 +101,9 @@ impl CurlStreamLoader {
         headers.append("ignore_json_size: true")?;
         headers.append("max_filter_ratio: 0.2")?;
         
-        // Headers de partial update si existen
-        let partial_cols_clone = partial_columns.clone();
-        if let Some(ref cols) = partial_columns {
-            headers.append("partial_update: true")?;
-            headers.append("partial_update_mode: row")?;
-            headers.append(&format!("columns: {}", cols.join(",")))?;
+        // Headers de partial update / columnas si existen
+        Self::append_column_headers(&mut headers, columns.as_ref())?;
+        if let Some(LoadColumns { columns: ref cols, partial: true }) = columns {
             println!("🔄 Partial update for {}: {} columns", table_name, cols.len());
         }
         
@@ -165,7 +171,7 @@ impl CurlStreamLoader {
                 };
                 
                 // Hacer segunda petición al BE (redirect)
-                return Self::send_to_be(&corrected_location, user, pass, partial_cols_clone, body.clone());
+                return Self::send_to_be(&corrected_location, user, pass, columns, body.clone());
             }
         }
         
@@ -200,7 +206,7 @@ impl CurlStreamLoader {
             "✅ Sent {} rows to StarRocks ({}.{})", 
             loaded_rows,
             table_name.split('.').last().unwrap_or(table_name),
-            if partial_columns.is_some() { "partial" } else { "full" }
+            if columns.is_some_and(|c| c.partial) { "partial" } else { "full" }
         );
         
         Ok(LoadResult {
@@ -210,6 +216,18 @@ impl CurlStreamLoader {
         })
     }
     
+    /// Headers `columns` (y de partial update) del Stream Load
+    fn append_column_headers(headers: &mut List, columns: Option<&LoadColumns>) -> Result<()> {
+        if let Some(columns) = columns {
+            if columns.partial {
+                headers.append("partial_update: true")?;
+                headers.append("partial_update_mode: row")?;
+            }
+            headers.append(&format!("columns: {}", columns.columns.join(",")))?;
+        }
+        Ok(())
+    }
+    
     /// Extrae el hostname de una URL (ej: "http://starrocks:8030" → "starrocks")
     fn extract_hostname(url: &str) -> Result<String> {
         let url_parts: Vec<&str> = url.split('/').collect();
@@ -228,7 +246,7 @@ impl CurlStreamLoader {
         be_url: &str,
         user: &str,
         pass: &str,
-        partial_columns: Option<Vec<String>>,
+        columns: Option<LoadColumns>,
         body: Arc<Vec<u8>>,
     ) -> Result<LoadResult> {
         let mut easy = Easy::new();
@@ -249,11 +267,7 @@ impl CurlStreamLoader {
         headers.append("ignore_json_size: true")?;
         headers.append("max_filter_ratio: 0.2")?;
         
-        if let Some(ref cols) = partial_columns {
-            headers.append("partial_update: true")?;
-            headers.append("partial_update_mode: row")?;
-            headers.append(&format!("columns: {}", cols.join(",")))?;
-        }
+        Self::append_column_headers(&mut headers, columns.as_ref())?;
         
         easy.http_headers(headers)?;
         
@@ -0,0 +1,238 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Políticas de enmascaramiento de columnas con datos personales.
+//!
+//! Se aplican en el sink al armar cada fila, así INSERTs, UPDATEs, soft
+//! deletes y snapshots llevan el mismo valor transformado. El hash usa un
+//! salt leído de un archivo secreto (`MASKING_SALT_FILE`): el mismo valor
+//! produce siempre el mismo hash y se puede usar en JOINs sin exponerlo.
+
+use anyhow::{anyhow, Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Transformación de una columna
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaskPolicy {
+    /// SHA-256 en hex de `salt || valor`
+    Hash,
+    /// Reemplaza letras y dígitos por `*` salvo los últimos `n` (conserva separadores)
+    Mask(usize),
+    /// Siempre NULL
+    Null,
+    /// Primeros `n` caracteres
+    Truncate(usize),
+}
+
+impl MaskPolicy {
+    /// Valor enmascarado del texto de la columna
+    fn apply(self, salt: &[u8], text: &str) -> Option<String> {
+        match self {
+            Self::Hash => {
+                let mut hasher = Sha256::new();
+                hasher.update(salt);
+                hasher.update(text.as_bytes());
+                Some(hex::encode(hasher.finalize()))
+            }
+            Self::Mask(visible) => {
+                let total = text.chars().filter(|c| c.is_alphanumeric()).count();
+                let mut hidden = total.saturating_sub(visible);
+                Some(
+                    text.chars()
+                        .map(|c| {
+                            if hidden > 0 && c.is_alphanumeric() {
+                                hidden -= 1;
+                                '*'
+                            } else {
+                                c
+                            }
+                        })
+                        .collect(),
+                )
+            }
+            Self::Null => None,
+            Self::Truncate(len) => Some(text.chars().take(len).collect()),
+        }
+    }
+
+    /// Cambia el tipo del valor (texto en vez del tipo de la columna). En un
+    /// bytea `truncate` corta su texto (`\x...`), así que también es texto.
+    pub fn changes_type(self, bytea: bool) -> bool {
+        match self {
+            Self::Hash | Self::Mask(_) => true,
+            Self::Truncate(_) => bytea,
+            Self::Null => false,
+        }
+    }
+}
+
+impl FromStr for MaskPolicy {
+    type Err = anyhow::Error;
+
+    /// `hash`, `mask(4)`, `null`, `truncate(10)`
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim().to_lowercase();
+        let (name, arg) = match s.split_once('(') {
+            Some((name, rest)) => {
+                let arg = rest
+                    .strip_suffix(')')
+                    .ok_or_else(|| anyhow!("Invalid mask policy '{}' (missing ')')", s))?;
+                let arg: usize = arg
+                    .trim()
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid mask policy '{}' (expected a length)", s))?;
+                (name.trim(), Some(arg))
+            }
+            None => (s.as_str(), None),
+        };
+
+        match (name, arg) {
+            ("hash", None) => Ok(Self::Hash),
+            ("mask", arg) => Ok(Self::Mask(arg.unwrap_or(4))),
+            ("null", None) => Ok(Self::Null),
+            ("truncate", Some(len)) => Ok(Self::Truncate(len)),
+            _ => Err(anyhow!(
+                "Invalid mask policy '{}' (expected hash, mask(n), null or truncate(n))",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for MaskPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Hash => write!(f, "hash"),
+            Self::Mask(visible) => write!(f, "mask({})", visible),
+            Self::Null => write!(f, "null"),
+            Self::Truncate(len) => write!(f, "truncate({})", len),
+        }
+    }
+}
+
+/// Políticas por columna, agrupadas por tabla (`schema.tabla` → columna → política)
+#[derive(Clone, Default)]
+pub struct MaskingPolicies {
+    tables: HashMap<String, HashMap<String, MaskPolicy>>,
+    salt: Vec<u8>,
+}
+
+impl MaskingPolicies {
+    /// Parsear `tabla.columna:politica;...` y leer el salt del archivo (requerido con `hash`)
+    pub fn load(policies: &str, salt_file: Option<&str>) -> Result<Self> {
+        let mut tables: HashMap<String, HashMap<String, MaskPolicy>> = HashMap::new();
+        for entry in policies.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let (column, policy) = entry
+                .split_once(':')
+                .ok_or_else(|| anyhow!("Invalid column mask '{}' (expected table.column:policy)", entry))?;
+            let parts: Vec<&str> = column.trim().split('.').collect();
+            let (table, column) = match parts.as_slice() {
+                [table, column] => (format!("public.{}", table), column.to_string()),
+                [schema, table, column] => (format!("{}.{}", schema, table), column.to_string()),
+                _ => return Err(anyhow!("Invalid column mask column '{}' (expected table.column)", column)),
+            };
+            tables.entry(table).or_default().insert(column, policy.parse()?);
+        }
+
+        let salt = match salt_file {
+            Some(path) => {
+                let salt = std::fs::read(path)
+                    .with_context(|| format!("Failed to read masking salt file '{}'", path))?;
+                // Sin el salto de línea final que dejan la mayoría de los editores
+                salt.trim_ascii_end().to_vec()
+            }
+            None => Vec::new(),
+        };
+
+        let hashes = tables.values().flat_map(|c| c.values()).any(|p| *p == MaskPolicy::Hash);
+        if hashes && salt.is_empty() {
+            return Err(anyhow!("Hash masks need a non-empty salt (set MASKING_SALT_FILE)"));
+        }
+
+        Ok(Self { tables, salt })
+    }
+
+    /// Políticas de las columnas de una tabla (None si no tiene ninguna)
+    pub fn for_table(&self, namespace: &str, table: &str) -> Option<&HashMap<String, MaskPolicy>> {
+        if self.tables.is_empty() {
+            return None;
+        }
+        self.tables.get(&format!("{}.{}", namespace, table))
+    }
+
+    /// Aplica la política al texto de un valor (None = NULL)
+    pub fn apply(&self, policy: MaskPolicy, text: &str) -> Option<String> {
+        policy.apply(&self.salt, text)
+    }
+}
+
+impl fmt::Display for MaskingPolicies {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.tables.is_empty() {
+            return write!(f, "none");
+        }
+        let mut columns: Vec<String> = self
+            .tables
+            .iter()
+            .flat_map(|(table, columns)| {
+                columns.iter().map(move |(column, policy)| format!("{}.{}={}", table, column, policy))
+            })
+            .collect();
+        columns.sort();
+        write!(f, "{}", columns.join(", "))
+    }
+}
+
+/// El salt no se imprime en logs ni en el banner
+impl fmt::Debug for MaskingPolicies {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MaskingPolicies")
+            .field("tables", &self.tables)
+            .field("salt", &"<redacted>")
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policies() {
+        let salt = b"pepper";
+        assert_eq!(MaskPolicy::Mask(4).apply(salt, "4111-1111-1111-1234").unwrap(), "****-****-****-1234");
+        assert_eq!(MaskPolicy::Mask(4).apply(salt, "12345678").unwrap(), "****5678");
+        assert_eq!(MaskPolicy::Mask(4).apply(salt, "12").unwrap(), "12");
+        assert_eq!(MaskPolicy::Truncate(3).apply(salt, "Ñandú azul").unwrap(), "Ñan");
+        assert_eq!(MaskPolicy::Null.apply(salt, "secret"), None);
+
+        let hash = MaskPolicy::Hash.apply(salt, "ana@example.com").unwrap();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, MaskPolicy::Hash.apply(salt, "ana@example.com").unwrap());
+        assert_ne!(hash, MaskPolicy::Hash.apply(b"other", "ana@example.com").unwrap());
+
+        // Un bytea enmascarado se carga como texto, salvo con null
+        assert!(MaskPolicy::Hash.changes_type(false));
+        assert!(!MaskPolicy::Truncate(3).changes_type(false));
+        assert!(MaskPolicy::Truncate(3).changes_type(true));
+        assert!(!MaskPolicy::Null.changes_type(true));
+    }
+
+    #[test]
+    fn test_parse() {
+        let policies = MaskingPolicies::load("users.card:mask(4); crm.leads.phone:truncate(3);users.notes:null", None).unwrap();
+        let users = policies.for_table("public", "users").unwrap();
+        assert_eq!(users["card"], MaskPolicy::Mask(4));
+        assert_eq!(users["notes"], MaskPolicy::Null);
+        assert_eq!(policies.for_table("crm", "leads").unwrap()["phone"], MaskPolicy::Truncate(3));
+        assert!(policies.for_table("public", "orders").is_none());
+
+        // hash sin salt
+        assert!(MaskingPolicies::load("users.email:hash", None).is_err());
+        assert!(MaskingPolicies::load("users.email:truncate", None).is_err());
+        assert!(MaskingPolicies::load("email:null", None).is_err());
+    }
+}
@@ -1,5 +1,10 @@
 pub mod curl_loader;
+pub mod masking;
 pub mod starrocks;
+pub mod table_map;
+pub mod timezone;
+pub mod truncate;
+pub mod type_map;
 
 use async_trait::async_trait;
 use anyhow::Result;
@@ -15,6 +20,8 @@ pub trait Sink: Send + Sync {
         lsn: u64
     ) -> Result<()>;
     
-    async fn apply_schema_delta(&self, delta: &SchemaDelta) -> Result<()>;
+    /// Agrega las columnas nuevas; `schema_cache` permite validar contra otras tablas
+    /// que escriben en el mismo destino (fan-in)
+    async fn apply_schema_delta(&self, delta: &SchemaDelta, schema_cache: &SchemaCache) -> Result<()>;
 }
 
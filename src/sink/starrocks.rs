@@ -1,23 +1,56 @@
 use async_trait::async_trait;
 use anyhow::{Result, anyhow};
 use sonic_rs::{Value, Object as Map, json};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
-use chrono::Utc;
 use mysql_async::{Pool, Conn, OptsBuilder, prelude::Queryable};
 
 use crate::sink::Sink;
-use crate::sink::curl_loader::CurlStreamLoader;
-use crate::source::parser::{CdcMessage, TupleData, Tuple};
+use crate::sink::curl_loader::{CurlStreamLoader, LoadColumns};
+use crate::sink::masking::{MaskPolicy, MaskingPolicies};
+use crate::sink::table_map::{TableMapping, TargetTable};
+use crate::sink::timezone::TargetTimezone;
+use crate::sink::truncate::{TruncatePolicies, TruncatePolicy};
+use crate::sink::type_map::TypeMapping;
+use crate::source::binary;
+use crate::source::type_catalog::TypeCatalog;
+use crate::source::parser::{CdcMessage, Column, ToastBitmap, TupleData, Tuple};
 use crate::pipeline::schema_cache::{SchemaCache, TableSchema, SchemaDelta};
 
 pub struct StarRocksSink {
     curl_loader: CurlStreamLoader,
-    database: String,
+    database: String,  // Base por defecto (tablas sin regla y tabla de mensajes)
+    table_mapping: TableMapping,  // schema.tabla → database.tabla
     mysql_pool: Option<Pool>,  // Pool MySQL para DDL (puerto 9030)
+    truncate_policy: TruncatePolicies,
+    messages_table: Option<String>,  // Destino de los mensajes lógicos (None = se descartan)
+    type_mapping: TypeMapping,  // Tipos de las columnas agregadas por schema evolution
+    type_catalog: TypeCatalog,  // Domains, enums y composites de la base de origen
+    target_timezone: TargetTimezone,  // Zona de los DATETIME (timestamptz y dbmazz_synced_at)
+    masking: MaskingPolicies,  // Hash / mask / null / truncate de columnas con datos personales
 }
 
+/// OID de bytea
+const BYTEA_OID: u32 = 17;
+/// Prefijo del campo JSON con el hex de una columna bytea (se convierte con `to_binary`)
+const BINARY_FIELD_PREFIX: &str = "__dbmazz_bin_";
+/// `relreplident` de REPLICA IDENTITY FULL
+const REPLICA_IDENTITY_FULL: u8 = b'f';
+/// Columnas de auditoría que se agregan a cada fila
+const AUDIT_COLUMNS: [&str; 4] = ["dbmazz_op_type", "dbmazz_is_deleted", "dbmazz_synced_at", "dbmazz_cdc_version"];
+
+/// Estructura: (relation_id, toast_bitmap) -> (rows, columns)
+/// Agrupamos por tabla Y por patron de TOAST para optimizar partial updates
+#[derive(Hash, Eq, PartialEq)]
+struct BatchKey {
+    relation_id: u32,
+    toast_bitmap: ToastBitmap,
+}
+
+type Batches = HashMap<BatchKey, (Vec<Map>, Option<Vec<String>>)>;
+
 impl StarRocksSink {
     pub fn new(base_url: String, database: String, user: String, pass: String) -> Self {
         let base_url = base_url.trim_end_matches('/').to_string();
@@ -48,17 +81,65 @@ impl StarRocksSink {
         // Crear CurlStreamLoader para Stream Load (usa libcurl con 100-continue)
         let curl_loader = CurlStreamLoader::new(
             base_url.clone(),
-            database.clone(),
             user.clone(),
             pass.clone(),
         );
         
         Self {
             curl_loader,
+            table_mapping: TableMapping::new(&database),
             database,
             mysql_pool: Some(Pool::new(mysql_opts)),
+            truncate_policy: TruncatePolicies::default(),
+            messages_table: None,
+            type_mapping: TypeMapping::default(),
+            type_catalog: TypeCatalog::default(),
+            target_timezone: TargetTimezone::default(),
+            masking: MaskingPolicies::default(),
         }
     }
+
+    /// Configura las reglas de mapeo de tablas origen → destino
+    pub fn with_table_mapping(mut self, table_mapping: TableMapping) -> Self {
+        self.table_mapping = table_mapping;
+        self
+    }
+    
+    /// Configura qué hacer con los TRUNCATE replicados (por defecto soft delete)
+    pub fn with_truncate_policy(mut self, truncate_policy: TruncatePolicies) -> Self {
+        self.truncate_policy = truncate_policy;
+        self
+    }
+    
+    /// Configura la tabla donde se guardan los mensajes de `pg_logical_emit_message`
+    pub fn with_messages_table(mut self, messages_table: Option<String>) -> Self {
+        self.messages_table = messages_table;
+        self
+    }
+    
+    /// Configura los overrides de tipos por columna para schema evolution
+    pub fn with_type_mapping(mut self, type_mapping: TypeMapping) -> Self {
+        self.type_mapping = type_mapping;
+        self
+    }
+    
+    /// Configura el catálogo de tipos definidos por el usuario
+    pub fn with_type_catalog(mut self, type_catalog: TypeCatalog) -> Self {
+        self.type_catalog = type_catalog;
+        self
+    }
+    
+    /// Configura la zona horaria de los DATETIME escritos en StarRocks (por defecto UTC)
+    pub fn with_target_timezone(mut self, target_timezone: TargetTimezone) -> Self {
+        self.target_timezone = target_timezone;
+        self
+    }
+    
+    /// Configura las políticas de enmascaramiento por columna
+    pub fn with_masking(mut self, masking: MaskingPolicies) -> Self {
+        self.masking = masking;
+        self
+    }
     
     /// Convierte un Tuple a JSON usando el schema de la tabla (incluye todas las columnas)
     fn tuple_to_json(
@@ -69,6 +150,27 @@ impl StarRocksSink {
         self.tuple_to_json_selective(tuple, schema, false).map(|(row, _)| row)
     }
     
+    /// Convierte un Tuple a JSON; con columnas TOAST arma un partial update y
+    /// retorna las columnas enviadas (incluidas las de auditoría)
+    fn tuple_to_row(
+        &self,
+        tuple: &Tuple,
+        schema: &TableSchema
+    ) -> Result<(Map, Option<Vec<String>>)> {
+        // Usar POPCNT (SIMD) para detectar TOAST rapido: O(1)
+        if !tuple.has_toast() {
+            // Full row (sin TOAST)
+            return Ok((self.tuple_to_json(tuple, schema)?, None));
+        }
+        
+        // Partial update: excluir columnas TOAST
+        let (row, mut cols) = self.tuple_to_json_selective(tuple, schema, true)?;
+        
+        // Agregar columnas de auditoria a la lista
+        cols.extend(AUDIT_COLUMNS.iter().map(|c| c.to_string()));
+        Ok((row, Some(cols)))
+    }
+    
     /// Convierte un Tuple a JSON con opcion de excluir columnas TOAST
     /// Retorna (row, columnas_incluidas) para usar en partial update
     fn tuple_to_json_selective(
@@ -80,6 +182,7 @@ impl StarRocksSink {
         let column_count = schema.columns.len();
         let mut row = Map::with_capacity(column_count);
         let mut included_columns = Vec::with_capacity(column_count);
+        let masks = self.masking.for_table(&schema.namespace, &schema.name);
         
         // Iterar sobre columnas y datos en paralelo
         for (idx, (column, data)) in schema.columns.iter().zip(tuple.cols.iter()).enumerate() {
@@ -88,9 +191,14 @@ impl StarRocksSink {
                 continue;
             }
             
-            let value = match data {
-                TupleData::Null => json!(null),
-                TupleData::Toast => {
+            // bytea: el hex va en un campo aparte que Stream Load convierte a VARBINARY
+            let bytea = self.is_binary_column(column, masks);
+            
+            // Datos personales: el valor enmascarado reemplaza al original
+            let policy = masks.and_then(|m| m.get(&column.name));
+            let value = match (data, policy) {
+                (TupleData::Null, _) => json!(null),
+                (TupleData::Toast, _) => {
                     if exclude_toast {
                         // Ya fue skipped arriba, pero por seguridad
                         continue;
@@ -99,49 +207,121 @@ impl StarRocksSink {
                     // Si no excluimos, usamos null (indica valor sin cambios)
                     json!(null)
                 },
-                TupleData::Text(bytes) => {
+                (TupleData::Text(bytes), Some(policy)) => {
+                    self.mask_value(*policy, &String::from_utf8_lossy(bytes))
+                }
+                (TupleData::Binary(bytes), Some(policy)) => {
+                    let text = binary::to_text(column.type_id, bytes).unwrap_or_else(|| hex::encode(bytes));
+                    self.mask_value(*policy, &text)
+                }
+                (TupleData::Text(bytes), None) => {
                     // Convertir bytes a string y luego al tipo apropiado
                     let text = String::from_utf8_lossy(bytes);
                     self.convert_pg_value(&text, column.type_id)
                 }
+                // Binario: directo al valor tipado, sin parsear texto
+                (TupleData::Binary(bytes), None) if bytea => json!(hex::encode(bytes)),
+                (TupleData::Binary(bytes), None) => self.convert_binary_value(bytes, column.type_id),
             };
             
-            row.insert(column.name.as_str(), value);
+            if bytea {
+                row.insert(&format!("{}{}", BINARY_FIELD_PREFIX, column.name), value);
+            } else {
+                row.insert(column.name.as_str(), value);
+            }
             included_columns.push(column.name.clone());
         }
         
+        // Fan-in: la tabla origen distingue las filas en la tabla compartida
+        if let Some(source_column) = self.table_mapping.source_column(&schema.namespace, &schema.name) {
+            row.insert(source_column, json!(format!("{}.{}", schema.namespace, schema.name)));
+            included_columns.push(source_column.to_string());
+        }
+        
         Ok((row, included_columns))
     }
     
-    /// Convierte un valor de PostgreSQL al tipo JSON apropiado
+    /// Convierte un valor de PostgreSQL al tipo JSON apropiado (ver `PgValue` y `TypeCatalog`)
     fn convert_pg_value(&self, text: &str, pg_type_id: u32) -> Value {
-        match pg_type_id {
-            // Boolean
-            16 => {
-                match text.to_lowercase().as_str() {
-                    "t" | "true" | "1" => json!(true),
-                    _ => json!(false),
-                }
-            },
-            // Integer types (INT2, INT4, INT8)
-            21 | 23 | 20 => {
-                text.parse::<i64>()
-                    .map(|n| json!(n))
-                    .unwrap_or_else(|_| json!(text))
-            },
-            // Float types (FLOAT4, FLOAT8)
-            700 | 701 => {
-                text.parse::<f64>()
-                    .map(|f| json!(f))
-                    .unwrap_or_else(|_| json!(text))
-            },
-            // NUMERIC/DECIMAL - mantener como string para precisión
-            1700 => json!(text),
-            // Timestamp types
-            1114 | 1184 => json!(text),
-            // Default: string
-            _ => json!(text),
+        // Fechas al formato de StarRocks y timestamptz a la zona destino (también en domains)
+        let (base_type, _) = self.type_catalog.resolve(pg_type_id, -1);
+        if let Some(datetime) = self.target_timezone.normalize(base_type, text) {
+            return json!(datetime);
+        }
+        self.type_catalog.to_json(pg_type_id, text)
+    }
+    
+    /// Aplica una política de enmascaramiento al texto de un valor
+    fn mask_value(&self, policy: MaskPolicy, text: &str) -> Value {
+        match self.masking.apply(policy, text) {
+            Some(masked) => json!(masked),
+            None => json!(null),
+        }
+    }
+    
+    /// Convierte un valor binario; las fechas pasan por la misma normalización que en texto
+    fn convert_binary_value(&self, data: &[u8], pg_type_id: u32) -> Value {
+        if matches!(pg_type_id, 1082 | 1114 | 1184) {
+            let datetime = binary::to_text(pg_type_id, data)
+                .and_then(|text| self.target_timezone.normalize(pg_type_id, &text));
+            if let Some(datetime) = datetime {
+                return json!(datetime);
+            }
+        }
+        binary::to_json(pg_type_id, data)
+    }
+    
+    /// Indica si la columna es bytea (o un domain sobre bytea)
+    fn is_bytea(&self, column: &Column) -> bool {
+        self.type_catalog.resolve(column.type_id, column.type_mod).0 == BYTEA_OID
+    }
+    
+    /// Indica si la columna se carga con `to_binary`: un bytea enmascarado (salvo
+    /// `null`) se envía como texto, su columna destino es STRING (ver `column_type`)
+    fn is_binary_column(&self, column: &Column, masks: Option<&HashMap<String, MaskPolicy>>) -> bool {
+        self.is_bytea(column)
+            && !masks
+                .and_then(|m| m.get(&column.name))
+                .is_some_and(|policy| policy.changes_type(true))
+    }
+    
+    /// Header `columns` del Stream Load de una tabla. Sin bytea ni partial update
+    /// no hace falta: StarRocks mapea los campos del JSON por nombre.
+    fn load_columns(&self, schema: &TableSchema, partial_columns: Option<Vec<String>>) -> Option<LoadColumns> {
+        let masks = self.masking.for_table(&schema.namespace, &schema.name);
+        let binary: Vec<&str> = schema.columns.iter()
+            .filter(|c| self.is_binary_column(c, masks))
+            .map(|c| c.name.as_str())
+            .collect();
+        
+        let (columns, partial) = match partial_columns {
+            Some(cols) => (cols, true),
+            None if binary.is_empty() => return None,
+            None => {
+                let cols = schema.columns.iter()
+                    .map(|c| c.name.clone())
+                    .chain(AUDIT_COLUMNS.iter().map(|c| c.to_string()))
+                    .chain(self.table_mapping.source_column(&schema.namespace, &schema.name).map(str::to_string))
+                    .collect();
+                (cols, false)
+            }
+        };
+        
+        // Cada bytea se lee del campo hex y se convierte con to_binary
+        let mut mapped = Vec::with_capacity(columns.len() + binary.len());
+        let mut conversions = Vec::new();
+        for column in columns {
+            if binary.contains(&column.as_str()) {
+                let field = format!("{}{}", BINARY_FIELD_PREFIX, column);
+                conversions.push(format!("{}=to_binary({}, 'hex')", column, field));
+                mapped.push(field);
+            } else {
+                mapped.push(column);
+            }
         }
+        mapped.extend(conversions);
+        
+        Some(LoadColumns { columns: mapped, partial })
     }
     
     /// Serializa un batch a JSON bytes sin duplicar buffers.
@@ -162,15 +342,15 @@ impl StarRocksSink {
     /// Envía con reintentos en caso de fallo usando un body preconstruido.
     async fn send_body_with_retry(
         &self,
-        table_name: &str,
+        target: &TargetTable,
         body: Arc<Vec<u8>>,
-        partial_columns: Option<Vec<String>>,
+        columns: Option<LoadColumns>,
         max_retries: u32,
     ) -> Result<()> {
         let mut attempt = 0;
         
         loop {
-            match self.curl_loader.send(table_name, body.clone(), partial_columns.clone()).await {
+            match self.curl_loader.send(target, body.clone(), columns.clone()).await {
                 Ok(_) => return Ok(()),
                 Err(e) => {
                     attempt += 1;
@@ -186,7 +366,7 @@ impl StarRocksSink {
                         "⚠️  Retry {}/{} for {}: {}", 
                         attempt, 
                         max_retries, 
-                        table_name, 
+                        target, 
                         e
                     );
                     
@@ -202,7 +382,7 @@ impl StarRocksSink {
     /// Envía un batch de filas a StarRocks via Stream Load (full row) con reintentos.
     async fn send_with_retry(
         &self,
-        table_name: &str,
+        target: &TargetTable,
         rows: Vec<Map>,
         max_retries: u32
     ) -> Result<()> {
@@ -210,7 +390,7 @@ impl StarRocksSink {
         if body.is_empty() {
             return Ok(());
         }
-        self.send_body_with_retry(table_name, body, None, max_retries).await
+        self.send_body_with_retry(target, body, None, max_retries).await
     }
     
     /// Ejecuta DDL en StarRocks via MySQL protocol
@@ -227,33 +407,63 @@ impl StarRocksSink {
         Ok(())
     }
     
-    /// Convierte tipo PostgreSQL a tipo StarRocks
-    fn pg_type_to_starrocks(&self, pg_type: u32) -> &'static str {
-        match pg_type {
-            16 => "BOOLEAN",           // bool
-            21 => "SMALLINT",          // int2
-            23 => "INT",               // int4
-            20 => "BIGINT",            // int8
-            700 => "FLOAT",            // float4
-            701 => "DOUBLE",           // float8
-            1700 => "DECIMAL(38,9)",   // numeric
-            1114 => "DATETIME",        // timestamp
-            1184 => "DATETIME",        // timestamptz
-            25 => "STRING",            // text
-            1043 => "STRING",          // varchar
-            1042 => "STRING",          // char
-            3802 => "JSON",            // jsonb
-            _ => "STRING",             // default
+    /// Tipo StarRocks de una columna de `schema.tabla`
+    fn column_type(&self, namespace: &str, table: &str, column: &str, type_id: u32, type_mod: i32) -> String {
+        // Domains como su tipo base, enums como texto y composites como JSON.
+        // Las columnas con hash o mask (o un bytea truncado) llevan texto.
+        let (pg_type, type_mod) = self.type_catalog.resolve(type_id, type_mod);
+        let masked = self.masking
+            .for_table(namespace, table)
+            .and_then(|m| m.get(column))
+            .is_some_and(|policy| policy.changes_type(pg_type == BYTEA_OID));
+        let (pg_type, type_mod) = if masked { (25, -1) } else { (pg_type, type_mod) };
+        self.type_mapping.column_type(namespace, table, column, pg_type, type_mod)
+    }
+    
+    /// Fan-in: una columna nueva tiene que tener el mismo tipo en las otras tablas
+    /// origen del destino que ya la tienen
+    fn check_fan_in_column(
+        &self,
+        delta: &SchemaDelta,
+        column: &str,
+        sr_type: &str,
+        target: &TargetTable,
+        schema_cache: &SchemaCache,
+    ) -> Result<()> {
+        let members = schema_cache.iter().filter(|s| {
+            (s.namespace != delta.namespace || s.name != delta.table_name)
+                && self.table_mapping.resolve(&s.namespace, &s.name) == *target
+        });
+        for member in members {
+            let Some(other) = member.columns.iter().find(|c| c.name == column) else {
+                continue;
+            };
+            let other_type = self.column_type(&member.namespace, &member.name, column, other.type_id, other.type_mod);
+            if other_type != sr_type {
+                return Err(anyhow!(
+                    "Column {} of {}.{} ({}) is incompatible with {}.{} ({}) in fan-in table {}",
+                    column, delta.namespace, delta.table_name, sr_type,
+                    member.namespace, member.name, other_type, target
+                ));
+            }
         }
+        Ok(())
     }
     
     /// Aplica cambios de schema (agrega columnas nuevas)
-    pub async fn apply_schema_delta(&self, delta: &SchemaDelta) -> Result<()> {
+    pub async fn apply_schema_delta(&self, delta: &SchemaDelta, schema_cache: &SchemaCache) -> Result<()> {
+        let target = self.table_mapping.resolve(&delta.namespace, &delta.table_name);
+        let fan_in = self.table_mapping.source_column(&delta.namespace, &delta.table_name).is_some();
         for col in &delta.added_columns {
-            let sr_type = self.pg_type_to_starrocks(col.pg_type_id);
+            let sr_type = self.column_type(
+                &delta.namespace, &delta.table_name, &col.name, col.pg_type_id, col.type_mod,
+            );
+            if fan_in {
+                self.check_fan_in_column(delta, &col.name, &sr_type, &target, schema_cache)?;
+            }
             let sql = format!(
-                "ALTER TABLE {}.{} ADD COLUMN {} {}",
-                self.database, delta.table_name, col.name, sr_type
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                target, col.name, sr_type
             );
             
             // Intentar ejecutar DDL, ignorar error si columna ya existe
@@ -261,7 +471,7 @@ impl StarRocksSink {
                 Ok(_) => {
                     println!(
                         "✅ Schema evolution: added column {} ({}) to {}", 
-                        col.name, sr_type, delta.table_name
+                        col.name, sr_type, target
                     );
                 }
                 Err(e) => {
@@ -270,12 +480,12 @@ impl StarRocksSink {
                     if err_msg.contains("Duplicate column") || err_msg.contains("already exists") {
                         println!(
                             "⚠️  Column {} already exists in {}, skipping",
-                            col.name, delta.table_name
+                            col.name, target
                         );
                     } else {
                         return Err(anyhow!(
                             "Failed to add column {} to {}: {}",
-                            col.name, delta.table_name, err_msg
+                            col.name, target, err_msg
                         ));
                     }
                 }
@@ -284,23 +494,103 @@ impl StarRocksSink {
         Ok(())
     }
     
-    /// Envía partial update con reintentos en caso de fallo
-    async fn send_partial_update_with_retry(
+    /// Envía filas con header `columns` (partial update y/o bytea) con reintentos
+    async fn send_columns_with_retry(
         &self,
-        table_name: &str,
+        target: &TargetTable,
         rows: Vec<Map>,
-        columns: &[String],
+        columns: LoadColumns,
         max_retries: u32
     ) -> Result<()> {
         let body = self.build_body(rows)?;
         if body.is_empty() {
             return Ok(());
         }
-        let columns_vec = columns.to_vec();
-        self.send_body_with_retry(table_name, body, Some(columns_vec), max_retries).await
+        self.send_body_with_retry(target, body, Some(columns), max_retries).await
+    }
+
+    /// Envía cada batch agrupado por (tabla, toast_signature)
+    async fn send_batches(&self, batches: Batches, schema_cache: &SchemaCache) -> Result<()> {
+        for (key, (rows, columns)) in batches {
+            if let Some(schema) = schema_cache.get(key.relation_id) {
+                let target = self.table_mapping.resolve(&schema.namespace, &schema.name);
+                if let Some(load_columns) = self.load_columns(schema, columns) {
+                    // Partial update o tabla con columnas bytea
+                    self.send_columns_with_retry(&target, rows, load_columns, 3).await?;
+                } else {
+                    // Full row
+                    self.send_with_retry(&target, rows, 3).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Aplica un TRUNCATE de PostgreSQL según la política de la tabla
+    async fn apply_truncate(
+        &self,
+        schema: &TableSchema,
+        cascade: bool,
+        restart_identity: bool,
+        synced_at: &str,
+        lsn: u64,
+    ) -> Result<()> {
+        let policy = self.truncate_policy.for_table(&schema.namespace, &schema.name);
+        let target = self.table_mapping.resolve(&schema.namespace, &schema.name);
+        let mut options = Vec::new();
+        if cascade { options.push("CASCADE"); }
+        if restart_identity { options.push("RESTART IDENTITY"); }
+
+        // Fan-in: solo las filas de esta tabla origen
+        let source_filter = self.table_mapping
+            .source_column(&schema.namespace, &schema.name)
+            .map(|column| format!("{} = '{}.{}'", column, schema.namespace, schema.name));
+
+        let sql = match (policy, source_filter) {
+            (TruncatePolicy::Truncate, None) => {
+                format!("TRUNCATE TABLE {}", target)
+            }
+            (TruncatePolicy::Truncate, Some(filter)) => {
+                format!("DELETE FROM {} WHERE {}", target, filter)
+            }
+            (TruncatePolicy::SoftDelete, filter) => format!(
+                "UPDATE {} SET dbmazz_op_type = 2, dbmazz_is_deleted = true, \
+                 dbmazz_synced_at = '{}', dbmazz_cdc_version = {} WHERE dbmazz_is_deleted = false{}",
+                target, synced_at, lsn as i64,
+                filter.map(|f| format!(" AND {}", f)).unwrap_or_default()
+            ),
+            (TruncatePolicy::Ignore, _) => {
+                println!(
+                    "⚠️  TRUNCATE {}.{} {:?} ignored (policy: ignore)",
+                    schema.namespace, schema.name, options
+                );
+                return Ok(());
+            }
+        };
+
+        self.execute_ddl(&sql).await
+            .map_err(|e| anyhow!("Failed to apply TRUNCATE to {}: {}", target, e))?;
+        println!(
+            "🧹 TRUNCATE {}.{} {:?} applied to StarRocks {} (policy: {})",
+            schema.namespace, schema.name, options, target, policy
+        );
+        Ok(())
     }
 }
 
+/// Old tuple de un DELETE sin REPLICA IDENTITY FULL: las columnas fuera de la
+/// identidad se marcan como TOAST para enviarlo como partial update de la PK
+fn key_only_tuple(tuple: &Tuple, schema: &TableSchema) -> Tuple {
+    let mut key_only = tuple.clone();
+    for (idx, column) in schema.columns.iter().enumerate() {
+        if column.flags & 1 == 0 && idx < key_only.cols.len() {
+            key_only.cols[idx] = TupleData::Toast;
+            key_only.toast_bitmap.set(idx);
+        }
+    }
+    key_only
+}
+
 #[async_trait]
 impl Sink for StarRocksSink {
     async fn push_batch(
@@ -310,17 +600,10 @@ impl Sink for StarRocksSink {
         lsn: u64
     ) -> Result<()> {
         // Cache timestamp para toda el batch (evita llamadas repetidas)
-        let synced_at = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let synced_at = self.target_timezone.now();
         
-        // Estructura: (relation_id, toast_bitmap) -> (rows, columns)
-        // Agrupamos por tabla Y por patron de TOAST para optimizar partial updates
-        #[derive(Hash, Eq, PartialEq)]
-        struct BatchKey {
-            relation_id: u32,
-            toast_bitmap: u64,
-        }
-        
-        let mut batches: HashMap<BatchKey, (Vec<Map>, Option<Vec<String>>)> = HashMap::new();
+        let mut batches: Batches = HashMap::new();
+        let mut messages: Vec<Map> = Vec::new();
         
         for msg in batch {
             match msg {
@@ -337,7 +620,7 @@ impl Sink for StarRocksSink {
                         
                         let key = BatchKey { 
                             relation_id: *relation_id, 
-                            toast_bitmap: 0  // Full row
+                            toast_bitmap: ToastBitmap::default()  // Full row
                         };
                         batches.entry(key)
                             .or_insert_with(|| (Vec::new(), None))
@@ -347,26 +630,8 @@ impl Sink for StarRocksSink {
                 
                 CdcMessage::Update { relation_id, new_tuple, .. } => {
                     if let Some(schema) = schema_cache.get(*relation_id) {
-                        // Usar POPCNT (SIMD) para detectar TOAST rapido: O(1)
-                        let has_toast = new_tuple.has_toast();
-                        
-                        let (mut row, columns) = if has_toast {
-                            // Partial update: excluir columnas TOAST
-                            let (r, mut cols) = self.tuple_to_json_selective(
-                                new_tuple, schema, true
-                            )?;
-                            
-                            // Agregar columnas de auditoria a la lista
-                            cols.push("dbmazz_op_type".to_string());
-                            cols.push("dbmazz_is_deleted".to_string());
-                            cols.push("dbmazz_synced_at".to_string());
-                            cols.push("dbmazz_cdc_version".to_string());
-                            
-                            (r, Some(cols))
-                        } else {
-                            // Full row update (sin TOAST)
-                            (self.tuple_to_json(new_tuple, schema)?, None)
-                        };
+                        // Partial update si hay columnas TOAST
+                        let (mut row, columns) = self.tuple_to_row(new_tuple, schema)?;
                         
                         // Columnas de auditoría CDC
                         row.insert("dbmazz_op_type", json!(1)); // 1 = UPDATE
@@ -376,7 +641,7 @@ impl Sink for StarRocksSink {
                         
                         let key = BatchKey { 
                             relation_id: *relation_id, 
-                            toast_bitmap: new_tuple.toast_bitmap
+                            toast_bitmap: new_tuple.toast_bitmap.clone()
                         };
                         
                         let entry = batches.entry(key).or_insert_with(|| (Vec::new(), columns.clone()));
@@ -387,8 +652,14 @@ impl Sink for StarRocksSink {
                 CdcMessage::Delete { relation_id, old_tuple } => {
                     if let Some(old) = old_tuple {
                         if let Some(schema) = schema_cache.get(*relation_id) {
-                            // DELETEs siempre son full row (necesitamos todos los campos)
-                            let mut row = self.tuple_to_json(old, schema)?;
+                            // Con REPLICA IDENTITY FULL son full row; si no, el old tuple
+                            // solo trae la PK y el resto de las columnas no se toca
+                            let old = if schema.replica_identity == REPLICA_IDENTITY_FULL {
+                                Cow::Borrowed(old)
+                            } else {
+                                Cow::Owned(key_only_tuple(old, schema))
+                            };
+                            let (mut row, columns) = self.tuple_to_row(&old, schema)?;
                             
                             // Columnas de auditoría CDC
                             row.insert("dbmazz_op_type", json!(2)); // 2 = DELETE
@@ -398,37 +669,52 @@ impl Sink for StarRocksSink {
                             
                             let key = BatchKey { 
                                 relation_id: *relation_id, 
-                                toast_bitmap: 0  // Full row
+                                toast_bitmap: old.toast_bitmap.clone()
                             };
                             batches.entry(key)
-                                .or_insert_with(|| (Vec::new(), None))
+                                .or_insert_with(|| (Vec::new(), columns))
                                 .0.push(row);
                         }
                     }
                 },
                 
-                // Begin, Commit, Relation, KeepAlive, Unknown - no necesitan sink
+                CdcMessage::Truncate { relation_ids, cascade, restart_identity } => {
+                    // Las filas anteriores al TRUNCATE se envían primero para respetar el orden
+                    self.send_batches(std::mem::take(&mut batches), schema_cache).await?;
+                    for relation_id in relation_ids {
+                        if let Some(schema) = schema_cache.get(*relation_id) {
+                            self.apply_truncate(schema, *cascade, *restart_identity, &synced_at, lsn).await?;
+                        }
+                    }
+                },
+                
+                CdcMessage::Message { lsn: message_lsn, prefix, content, transactional } => {
+                    if self.messages_table.is_some() {
+                        let mut row = Map::with_capacity(5);
+                        row.insert("lsn", json!(*message_lsn as i64));
+                        row.insert("prefix", json!(prefix));
+                        row.insert("content", json!(String::from_utf8_lossy(content)));
+                        row.insert("transactional", json!(*transactional));
+                        row.insert("dbmazz_synced_at", json!(&synced_at));
+                        messages.push(row);
+                    }
+                },
+                
+                // Begin, Commit, Relation, Type, KeepAlive, Unknown - no necesitan sink
                 _ => {}
             }
         }
         
-        // Enviar cada batch agrupado por (tabla, toast_signature)
-        for (key, (rows, columns)) in batches {
-            if let Some(schema) = schema_cache.get(key.relation_id) {
-                if let Some(cols) = columns {
-                    // Partial update
-                    self.send_partial_update_with_retry(&schema.name, rows, &cols, 3).await?;
-                } else {
-                    // Full row
-                    self.send_with_retry(&schema.name, rows, 3).await?;
-                }
-            }
-        }
+        self.send_batches(batches, schema_cache).await?;
         
+        if let Some(ref table) = self.messages_table {
+            let target = TargetTable { database: self.database.clone(), table: table.clone() };
+            self.send_with_retry(&target, messages, 3).await?;
+        }
         Ok(())
     }
     
-    async fn apply_schema_delta(&self, delta: &SchemaDelta) -> Result<()> {
-        self.apply_schema_delta(delta).await
+    async fn apply_schema_delta(&self, delta: &SchemaDelta, schema_cache: &SchemaCache) -> Result<()> {
+        self.apply_schema_delta(delta, schema_cache).await
     }
 }
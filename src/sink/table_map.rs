@@ -0,0 +1,258 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Mapeo de tablas PostgreSQL (`schema.tabla`) → StarRocks (`database.tabla`).
+//!
+//! Reglas en orden, gana la primera que coincide. Cada parte del origen puede
+//! tener un `*` (`billing.*`, `public.tmp_*`) y el `*` del destino se reemplaza
+//! por lo que capturó la parte correspondiente (`billing.*` → `billing_db.*`,
+//! `public.*` → `analytics.pg_*`). Sin regla, la tabla va a `STARROCKS_DB`
+//! con el mismo nombre.
+//!
+//! Las reglas fan-in (`TABLE_FANIN`) juntan varias tablas origen en una sola
+//! (`public.events_*:events`); cada fila lleva la tabla origen en una columna
+//! extra (`dbmazz_source` por defecto).
+
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+/// Tabla destino en StarRocks
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TargetTable {
+    pub database: String,
+    pub table: String,
+}
+
+impl fmt::Display for TargetTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.database, self.table)
+    }
+}
+
+/// Parte de un nombre con a lo sumo un `*`
+#[derive(Debug, Clone)]
+pub(crate) struct Pattern {
+    prefix: String,
+    suffix: Option<String>,
+}
+
+impl Pattern {
+    pub(crate) fn parse(text: &str) -> Result<Self> {
+        match text.split_once('*') {
+            Some((_, suffix)) if suffix.contains('*') => {
+                Err(anyhow!("Invalid table pattern '{}' (only one '*' per name)", text))
+            }
+            Some((prefix, suffix)) => Ok(Self { prefix: prefix.to_string(), suffix: Some(suffix.to_string()) }),
+            None => Ok(Self { prefix: text.to_string(), suffix: None }),
+        }
+    }
+
+    /// Lo que capturó el `*` (el nombre completo si no tiene `*`)
+    pub(crate) fn capture<'a>(&self, name: &'a str) -> Option<&'a str> {
+        match &self.suffix {
+            None => (name == self.prefix).then_some(name),
+            Some(suffix) => name
+                .strip_prefix(self.prefix.as_str())?
+                .strip_suffix(suffix.as_str()),
+        }
+    }
+
+    /// Reemplaza el `*` por la captura
+    fn expand(&self, capture: &str) -> String {
+        match &self.suffix {
+            None => self.prefix.clone(),
+            Some(suffix) => format!("{}{}{}", self.prefix, capture, suffix),
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.suffix {
+            None => write!(f, "{}", self.prefix),
+            Some(suffix) => write!(f, "{}*{}", self.prefix, suffix),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    schema: Pattern,
+    table: Pattern,
+    database: Pattern,
+    target: Pattern,
+    /// Varias tablas origen en una destino (con columna de origen)
+    fan_in: bool,
+}
+
+/// Reglas `schema.tabla` → `database.tabla` con la base por defecto
+#[derive(Debug, Clone)]
+pub struct TableMapping {
+    default_database: String,
+    rules: Vec<Rule>,
+    /// Columna con `schema.tabla` de origen en las tablas fan-in
+    source_column: String,
+}
+
+/// Columna de origen por defecto de las tablas fan-in
+pub const DEFAULT_SOURCE_COLUMN: &str = "dbmazz_source";
+
+impl TableMapping {
+    /// Sin reglas: todas las tablas van a `default_database` con el mismo nombre
+    pub fn new(default_database: &str) -> Self {
+        Self {
+            default_database: default_database.to_string(),
+            rules: Vec::new(),
+            source_column: DEFAULT_SOURCE_COLUMN.to_string(),
+        }
+    }
+
+    /// Parsear `origen:destino;...` (`billing.*:billing_db.*;orders:orders_v2`)
+    pub fn parse(rules: &str, default_database: &str) -> Result<Self> {
+        let mut mapping = Self::new(default_database);
+        mapping.rules = mapping.parse_rules(rules, false)?;
+        Ok(mapping)
+    }
+
+    /// Agrega reglas fan-in (`public.events_*:events`), evaluadas antes que las de mapeo
+    pub fn with_fan_in(mut self, rules: &str, source_column: &str) -> Result<Self> {
+        let mut fan_in = self.parse_rules(rules, true)?;
+        fan_in.append(&mut self.rules);
+        self.rules = fan_in;
+        if !source_column.trim().is_empty() {
+            self.source_column = source_column.trim().to_string();
+        }
+        Ok(self)
+    }
+
+    fn parse_rules(&self, rules: &str, fan_in: bool) -> Result<Vec<Rule>> {
+        let default_database = self.default_database.as_str();
+        let mut parsed = Vec::new();
+        for entry in rules.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let (source, target) = entry
+                .split_once(':')
+                .ok_or_else(|| anyhow!("Invalid table mapping '{}' (expected schema.table:database.table)", entry))?;
+            let (schema, table) = match source.trim().split_once('.') {
+                Some((schema, table)) => (schema, table),
+                None => ("public", source.trim()),
+            };
+            let (database, target) = match target.trim().split_once('.') {
+                Some((database, table)) => (database, table),
+                None => (default_database, target.trim()),
+            };
+            if table.is_empty() || target.is_empty() || database.is_empty() {
+                return Err(anyhow!("Invalid table mapping '{}' (empty name)", entry));
+            }
+            let rule = Rule {
+                schema: Pattern::parse(schema)?,
+                table: Pattern::parse(table)?,
+                database: Pattern::parse(database)?,
+                target: Pattern::parse(target)?,
+                fan_in,
+            };
+            // Con `*` en el destino cada origen iría a su propia tabla
+            if fan_in && (rule.database.suffix.is_some() || rule.target.suffix.is_some()) {
+                return Err(anyhow!("Invalid fan-in rule '{}' (the target can't use '*')", entry));
+            }
+            parsed.push(rule);
+        }
+        Ok(parsed)
+    }
+
+    /// Primera regla que coincide con la tabla
+    fn rule<'a>(&self, namespace: &'a str, table: &'a str) -> Option<(&Rule, &'a str, &'a str)> {
+        self.rules.iter().find_map(|rule| {
+            Some((rule, rule.schema.capture(namespace)?, rule.table.capture(table)?))
+        })
+    }
+
+    /// Tabla destino de `schema.tabla`
+    pub fn resolve(&self, namespace: &str, table: &str) -> TargetTable {
+        match self.rule(namespace, table) {
+            Some((rule, schema, name)) => TargetTable {
+                database: rule.database.expand(schema),
+                table: rule.target.expand(name),
+            },
+            None => TargetTable { database: self.default_database.clone(), table: table.to_string() },
+        }
+    }
+
+    /// Columna de origen si la tabla entra en una tabla fan-in
+    pub fn source_column(&self, namespace: &str, table: &str) -> Option<&str> {
+        self.rule(namespace, table)
+            .filter(|(rule, _, _)| rule.fan_in)
+            .map(|_| self.source_column.as_str())
+    }
+
+    /// Tabla destino de una tabla de la config (`tabla` o `schema.tabla`)
+    pub fn resolve_qualified(&self, table: &str) -> TargetTable {
+        let (namespace, name) = table.split_once('.').unwrap_or(("public", table));
+        self.resolve(namespace, name)
+    }
+
+    /// Columna de origen de una tabla de la config (`tabla` o `schema.tabla`)
+    pub fn source_column_qualified(&self, table: &str) -> Option<&str> {
+        let (namespace, name) = table.split_once('.').unwrap_or(("public", table));
+        self.source_column(namespace, name)
+    }
+}
+
+impl fmt::Display for TableMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rules: Vec<String> = self
+            .rules
+            .iter()
+            .map(|r| {
+                let fan_in = if r.fan_in { format!(" (fan-in, {})", self.source_column) } else { String::new() };
+                format!("{}.{} → {}.{}{}", r.schema, r.table, r.database, r.target, fan_in)
+            })
+            .chain(Some(format!("* → {}.*", self.default_database)))
+            .collect();
+        write!(f, "{}", rules.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(database: &str, table: &str) -> TargetTable {
+        TargetTable { database: database.to_string(), table: table.to_string() }
+    }
+
+    #[test]
+    fn test_rules() {
+        let mapping = TableMapping::parse(
+            "billing.*:billing_db.*; orders:orders_v2; public.tmp_*:staging.*; legacy_*.*:*_db.legacy_*",
+            "analytics",
+        )
+        .unwrap();
+        assert_eq!(mapping.resolve("billing", "orders"), target("billing_db", "orders"));
+        assert_eq!(mapping.resolve("public", "orders"), target("analytics", "orders_v2"));
+        assert_eq!(mapping.resolve("public", "tmp_events"), target("staging", "events"));
+        assert_eq!(mapping.resolve("legacy_crm", "users"), target("crm_db", "legacy_users"));
+        // Sin regla: base por defecto y mismo nombre
+        assert_eq!(mapping.resolve("public", "customers"), target("analytics", "customers"));
+        assert_eq!(mapping.resolve_qualified("billing.invoices"), target("billing_db", "invoices"));
+
+        assert!(TableMapping::parse("billing.*", "analytics").is_err());
+        assert!(TableMapping::parse("a*b*:x", "analytics").is_err());
+    }
+
+    #[test]
+    fn test_fan_in() {
+        let mapping = TableMapping::parse("public.*:raw.*", "analytics")
+            .unwrap()
+            .with_fan_in("public.events_*:events; tenant_*.orders:sales.orders", "")
+            .unwrap();
+        // Las reglas fan-in se evalúan antes que las de mapeo
+        assert_eq!(mapping.resolve("public", "events_2025_01"), target("analytics", "events"));
+        assert_eq!(mapping.resolve("public", "events_2025_02"), target("analytics", "events"));
+        assert_eq!(mapping.resolve("tenant_a", "orders"), target("sales", "orders"));
+        assert_eq!(mapping.source_column("tenant_b", "orders"), Some(DEFAULT_SOURCE_COLUMN));
+        assert_eq!(mapping.resolve("public", "users"), target("raw", "users"));
+        assert_eq!(mapping.source_column("public", "users"), None);
+
+        assert!(TableMapping::new("analytics").with_fan_in("public.events_*:events_*", "").is_err());
+    }
+}
@@ -0,0 +1,180 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Normalización de fechas y timestamps al formato DATETIME/DATE de StarRocks.
+//!
+//! PostgreSQL imprime `timestamptz` con el offset de la sesión
+//! (`2025-01-01 10:00:00+02`), las fechas antes de Cristo con sufijo ` BC` y los
+//! valores especiales `infinity` / `-infinity`. StarRocks no acepta ninguno de
+//! ellos: los timestamptz se convierten a la zona destino (`TARGET_TIMEZONE`) y
+//! los valores fuera de rango se acotan a `0000-01-01` .. `9999-12-31`.
+
+use anyhow::{anyhow, Result};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::fmt;
+
+const MIN_DATE: &str = "0000-01-01";
+const MAX_DATE: &str = "9999-12-31";
+const MIN_DATETIME: &str = "0000-01-01 00:00:00";
+const MAX_DATETIME: &str = "9999-12-31 23:59:59.999999";
+
+/// Zona horaria en la que se escriben los DATETIME en StarRocks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetTimezone(Tz);
+
+impl Default for TargetTimezone {
+    fn default() -> Self {
+        Self(Tz::UTC)
+    }
+}
+
+impl TargetTimezone {
+    /// Nombre IANA (`UTC`, `America/Bogota`, `Europe/Madrid`, ...)
+    pub fn parse(name: &str) -> Result<Self> {
+        name.trim()
+            .parse::<Tz>()
+            .map(Self)
+            .map_err(|_| anyhow!("Unknown timezone '{}' (expected an IANA name like UTC or America/Bogota)", name))
+    }
+
+    /// Hora actual en la zona destino (para `dbmazz_synced_at`)
+    pub fn now(&self) -> String {
+        Utc::now().with_timezone(&self.0).format("%Y-%m-%d %H:%M:%S").to_string()
+    }
+
+    /// Normaliza un valor date/timestamp/timestamptz en texto. None si el tipo
+    /// no es de fecha o el texto no se reconoce (se envía sin cambios).
+    pub fn normalize(&self, type_id: u32, text: &str) -> Option<String> {
+        match type_id {
+            1082 => match text {
+                "infinity" => Some(MAX_DATE.to_string()),
+                "-infinity" => Some(MIN_DATE.to_string()),
+                _ => parse_date(text).map(|(date, _)| format_date(date)),
+            },
+            1114 | 1184 => match text {
+                "infinity" => Some(MAX_DATETIME.to_string()),
+                "-infinity" => Some(MIN_DATETIME.to_string()),
+                _ => {
+                    let (naive, offset) = parse_timestamp(text)?;
+                    // timestamp sin zona: se conserva la hora de pared
+                    if type_id == 1114 {
+                        return Some(format_datetime(naive));
+                    }
+                    // timestamptz: a UTC con el offset impreso (sin offset = UTC, formato binario)
+                    let utc = naive - chrono::Duration::seconds(offset.unwrap_or(0) as i64);
+                    Some(format_datetime(Utc.from_utc_datetime(&utc).with_timezone(&self.0).naive_local()))
+                }
+            },
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TargetTimezone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.name())
+    }
+}
+
+/// `YYYY-MM-DD[ BC]`, seguido opcionalmente de la hora. Retorna la fecha y el resto.
+fn parse_date(text: &str) -> Option<(NaiveDate, &str)> {
+    let (text, bc) = match text.strip_suffix(" BC") {
+        Some(text) => (text, true),
+        None => (text, false),
+    };
+    let (date, rest) = text.split_once(' ').unwrap_or((text, ""));
+    let mut parts = date.splitn(3, '-');
+    let year: i32 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    // Año 1 BC es el año 0 astronómico
+    let year = if bc { 1 - year } else { year };
+    Some((NaiveDate::from_ymd_opt(year, month, day)?, rest))
+}
+
+/// `YYYY-MM-DD HH:MM:SS[.ffffff][+HH[:MM[:SS]]][ BC]` → (fecha-hora, offset en segundos)
+fn parse_timestamp(text: &str) -> Option<(NaiveDateTime, Option<i32>)> {
+    let (date, time) = parse_date(text)?;
+
+    // El offset empieza en el primer signo después de la hora
+    let (time, offset) = match time.find(['+', '-']) {
+        Some(idx) => (&time[..idx], Some(parse_offset(&time[idx..])?)),
+        None => (time, None),
+    };
+
+    let (hms, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut parts = hms.splitn(3, ':');
+    let hour: u32 = parts.next()?.parse().ok()?;
+    let minute: u32 = parts.next()?.parse().ok()?;
+    let second: u32 = parts.next()?.parse().ok()?;
+    // PostgreSQL guarda microsegundos: hasta 6 dígitos de fracción
+    let micros = if fraction.is_empty() {
+        0
+    } else {
+        format!("{:0<6}", fraction).get(..6)?.parse().ok()?
+    };
+
+    let time = NaiveTime::from_hms_micro_opt(hour, minute, second, micros)?;
+    Some((date.and_time(time), offset))
+}
+
+/// `+02`, `-05:30`, `+00:53:28` → segundos al este de UTC
+fn parse_offset(text: &str) -> Option<i32> {
+    let sign = if text.starts_with('-') { -1 } else { 1 };
+    let mut seconds = 0;
+    for (part, unit) in text[1..].split(':').zip([3600, 60, 1]) {
+        seconds += part.parse::<i32>().ok()? * unit;
+    }
+    Some(sign * seconds)
+}
+
+fn format_date(date: NaiveDate) -> String {
+    match date.year() {
+        year if year < 0 => MIN_DATE.to_string(),
+        year if year > 9999 => MAX_DATE.to_string(),
+        _ => date.format("%Y-%m-%d").to_string(),
+    }
+}
+
+fn format_datetime(datetime: NaiveDateTime) -> String {
+    match datetime.year() {
+        year if year < 0 => MIN_DATETIME.to_string(),
+        year if year > 9999 => MAX_DATETIME.to_string(),
+        _ => datetime.format("%Y-%m-%d %H:%M:%S%.f").to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamptz_to_target_timezone() {
+        let utc = TargetTimezone::default();
+        assert_eq!(utc.normalize(1184, "2025-01-01 10:00:00+02").unwrap(), "2025-01-01 08:00:00");
+        assert_eq!(utc.normalize(1184, "2025-01-01 10:00:00.25-05:30").unwrap(), "2025-01-01 15:30:00.250");
+        assert_eq!(utc.normalize(1184, "1900-01-01 00:00:00+00:53:28").unwrap(), "1899-12-31 23:06:32");
+        // Sin offset (formato binario): ya está en UTC
+        assert_eq!(utc.normalize(1184, "2025-01-01 10:00:00.000001").unwrap(), "2025-01-01 10:00:00.000001");
+
+        let bogota = TargetTimezone::parse("America/Bogota").unwrap();
+        assert_eq!(bogota.normalize(1184, "2025-01-01 10:00:00+00").unwrap(), "2025-01-01 05:00:00");
+        assert!(TargetTimezone::parse("Mars/Olympus").is_err());
+    }
+
+    #[test]
+    fn test_special_values() {
+        let tz = TargetTimezone::default();
+        assert_eq!(tz.normalize(1184, "infinity").unwrap(), MAX_DATETIME);
+        assert_eq!(tz.normalize(1114, "-infinity").unwrap(), MIN_DATETIME);
+        assert_eq!(tz.normalize(1082, "infinity").unwrap(), MAX_DATE);
+        // 1 BC = año 0 (representable); antes se acota al mínimo
+        assert_eq!(tz.normalize(1082, "0001-06-01 BC").unwrap(), "0000-06-01");
+        assert_eq!(tz.normalize(1114, "0044-03-15 12:00:00 BC").unwrap(), MIN_DATETIME);
+        assert_eq!(tz.normalize(1082, "20000-01-01").unwrap(), MAX_DATE);
+        // timestamp sin zona: la hora no cambia
+        assert_eq!(tz.normalize(1114, "2025-06-30 23:59:59.5").unwrap(), "2025-06-30 23:59:59.500");
+        assert_eq!(tz.normalize(25, "2025-01-01"), None);
+    }
+}
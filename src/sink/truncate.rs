@@ -0,0 +1,133 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::pipeline::table_selection::qualify_table;
+
+/// Qué hacer en StarRocks cuando PostgreSQL replica un `TRUNCATE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TruncatePolicy {
+    /// `TRUNCATE TABLE` en el destino
+    Truncate,
+    /// Marcar todas las filas con `dbmazz_is_deleted = true` (como los DELETE)
+    SoftDelete,
+    /// Solo registrar el evento en el log
+    Ignore,
+}
+
+impl FromStr for TruncatePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "truncate" => Ok(Self::Truncate),
+            "soft_delete" => Ok(Self::SoftDelete),
+            "ignore" => Ok(Self::Ignore),
+            other => Err(anyhow!(
+                "Invalid truncate policy '{}' (expected truncate, soft_delete or ignore)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TruncatePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncate => write!(f, "truncate"),
+            Self::SoftDelete => write!(f, "soft_delete"),
+            Self::Ignore => write!(f, "ignore"),
+        }
+    }
+}
+
+/// Política de TRUNCATE por defecto con overrides por tabla (`schema.tabla`)
+#[derive(Debug, Clone)]
+pub struct TruncatePolicies {
+    default: TruncatePolicy,
+    tables: HashMap<String, TruncatePolicy>,
+}
+
+impl Default for TruncatePolicies {
+    fn default() -> Self {
+        Self {
+            default: TruncatePolicy::Ignore,
+            tables: HashMap::new(),
+        }
+    }
+}
+
+impl TruncatePolicies {
+    /// Parsear la política por defecto y los overrides `tabla:politica,...`
+    pub fn parse(default: &str, overrides: &str) -> Result<Self> {
+        let mut tables = HashMap::new();
+        for entry in overrides.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (table, policy) = entry
+                .rsplit_once(':')
+                .ok_or_else(|| anyhow!("Invalid truncate policy override '{}' (expected table:policy)", entry))?;
+            let table = table.trim();
+            let table = if table.contains('.') { table.to_string() } else { format!("public.{}", table) };
+            tables.insert(table, policy.parse()?);
+        }
+
+        Ok(Self {
+            default: default.parse()?,
+            tables,
+        })
+    }
+
+    /// Política para una tabla de la config (`tabla` o `schema.tabla`)
+    pub fn for_source(&self, table: &str) -> TruncatePolicy {
+        let table = qualify_table(table);
+        let (namespace, name) = table.split_once('.').unwrap_or(("public", &table));
+        self.for_table(namespace, name)
+    }
+
+    /// Política para una tabla de PostgreSQL
+    pub fn for_table(&self, namespace: &str, name: &str) -> TruncatePolicy {
+        self.tables
+            .get(&format!("{}.{}", namespace, name))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+impl fmt::Display for TruncatePolicies {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.default)?;
+        let mut tables: Vec<_> = self.tables.iter().collect();
+        tables.sort_by(|a, b| a.0.cmp(b.0));
+        for (table, policy) in tables {
+            write!(f, ", {}={}", table, policy)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_take_precedence_over_default() {
+        let policies = TruncatePolicies::parse("soft_delete", "orders:truncate, audit.events:ignore").unwrap();
+        assert_eq!(policies.for_table("public", "orders"), TruncatePolicy::Truncate);
+        assert_eq!(policies.for_table("audit", "events"), TruncatePolicy::Ignore);
+        assert_eq!(policies.for_table("public", "order_items"), TruncatePolicy::SoftDelete);
+        assert_eq!(policies.for_source("orders"), TruncatePolicy::Truncate);
+        assert_eq!(policies.for_source("audit.events"), TruncatePolicy::Ignore);
+
+        // Sin configurar, un TRUNCATE no toca el destino
+        assert_eq!(TruncatePolicies::default().for_table("public", "orders"), TruncatePolicy::Ignore);
+    }
+
+    #[test]
+    fn test_invalid_policy_is_rejected() {
+        assert!(TruncatePolicies::parse("drop", "").is_err());
+        assert!(TruncatePolicies::parse("ignore", "orders").is_err());
+    }
+}
@@ -0,0 +1,157 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Mapeo de tipos PostgreSQL → StarRocks para schema evolution.
+//!
+//! Usa el `type_mod` de la columna (el `atttypmod` de PostgreSQL) para conservar
+//! precisión y largo: `numeric(12,2)` → `DECIMAL(12,2)`, `varchar(50)` →
+//! `VARCHAR(200)`. `timestamp(p)` va siempre a `DATETIME`: StarRocks no admite
+//! precisión en DATETIME.
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Precisión máxima de DECIMAL en StarRocks
+const MAX_DECIMAL_PRECISION: i32 = 38;
+/// Largo máximo de VARCHAR en StarRocks (en bytes)
+const MAX_VARCHAR_BYTES: i32 = 1_048_576;
+/// Bytes por carácter en el peor caso de UTF-8 (VARCHAR(n) de StarRocks cuenta bytes)
+const UTF8_MAX_BYTES: i32 = 4;
+/// `atttypmod` incluye 4 bytes de header (VARHDRSZ) en numeric, varchar y bpchar
+const VARHDRSZ: i32 = 4;
+
+/// Tipos StarRocks por columna, con overrides configurados (`schema.tabla.columna`)
+#[derive(Debug, Clone, Default)]
+pub struct TypeMapping {
+    overrides: HashMap<String, String>,
+}
+
+impl TypeMapping {
+    /// Parsear overrides `tabla.columna:TIPO;...` (`;` separa, los tipos llevan comas)
+    pub fn parse(overrides: &str) -> Result<Self> {
+        let mut columns = HashMap::new();
+        for entry in overrides.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let (column, sr_type) = entry
+                .split_once(':')
+                .ok_or_else(|| anyhow!("Invalid type override '{}' (expected table.column:TYPE)", entry))?;
+            let parts: Vec<&str> = column.trim().split('.').collect();
+            let key = match parts.as_slice() {
+                [table, column] => format!("public.{}.{}", table, column),
+                [schema, table, column] => format!("{}.{}.{}", schema, table, column),
+                _ => return Err(anyhow!("Invalid type override column '{}' (expected table.column)", column)),
+            };
+            let sr_type = sr_type.trim();
+            if sr_type.is_empty() {
+                return Err(anyhow!("Empty type in type override '{}'", entry));
+            }
+            columns.insert(key, sr_type.to_string());
+        }
+        Ok(Self { overrides: columns })
+    }
+
+    /// Tipo StarRocks de una columna: override configurado o derivado de tipo + typmod
+    pub fn column_type(&self, namespace: &str, table: &str, column: &str, pg_type: u32, type_mod: i32) -> String {
+        self.overrides
+            .get(&format!("{}.{}.{}", namespace, table, column))
+            .cloned()
+            .unwrap_or_else(|| starrocks_type(pg_type, type_mod))
+    }
+}
+
+impl fmt::Display for TypeMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.overrides.is_empty() {
+            return write!(f, "auto");
+        }
+        let mut columns: Vec<_> = self.overrides.iter().collect();
+        columns.sort_by(|a, b| a.0.cmp(b.0));
+        let columns: Vec<String> = columns.iter().map(|(c, t)| format!("{}={}", c, t)).collect();
+        write!(f, "auto, {}", columns.join(", "))
+    }
+}
+
+/// Convierte tipo PostgreSQL (+ typmod, -1 = sin modificador) a tipo StarRocks
+pub fn starrocks_type(pg_type: u32, type_mod: i32) -> String {
+    match pg_type {
+        16 => "BOOLEAN".to_string(),           // bool
+        21 => "SMALLINT".to_string(),          // int2
+        23 => "INT".to_string(),               // int4
+        20 => "BIGINT".to_string(),            // int8
+        700 => "FLOAT".to_string(),            // float4
+        701 => "DOUBLE".to_string(),           // float8
+        1700 => decimal(type_mod),             // numeric
+        1082 => "DATE".to_string(),            // date
+        1114 | 1184 => "DATETIME".to_string(), // timestamp, timestamptz
+        1043 | 1042 => varchar(type_mod),      // varchar, char
+        25 => "STRING".to_string(),            // text
+        3802 => "JSON".to_string(),            // jsonb
+        17 => "VARBINARY".to_string(),         // bytea
+        _ => "STRING".to_string(),             // default
+    }
+}
+
+/// `numeric(p,s)`: typmod = ((p << 16) | s) + 4. Sin typmod (o p > 38) no hay
+/// un DECIMAL que no pierda datos: se mantiene el default DECIMAL(38,9).
+fn decimal(type_mod: i32) -> String {
+    if type_mod < VARHDRSZ {
+        return "DECIMAL(38,9)".to_string();
+    }
+    let tm = type_mod - VARHDRSZ;
+    let precision = (tm >> 16) & 0xffff;
+    // La escala es un entero de 11 bits con signo (PG15+ admite escala negativa)
+    let scale = ((tm & 0x7ff) ^ 1024) - 1024;
+
+    // Escala negativa: redondea a la izquierda del punto, se guardan p - s dígitos enteros
+    let (precision, scale) = if scale < 0 { (precision - scale, 0) } else { (precision, scale) };
+    if precision > MAX_DECIMAL_PRECISION || scale > precision {
+        return "DECIMAL(38,9)".to_string();
+    }
+    format!("DECIMAL({},{})", precision, scale)
+}
+
+/// `varchar(n)` / `char(n)`: typmod = n + 4 (en caracteres)
+fn varchar(type_mod: i32) -> String {
+    if type_mod < VARHDRSZ {
+        return "STRING".to_string();
+    }
+    let bytes = (type_mod - VARHDRSZ).saturating_mul(UTF8_MAX_BYTES);
+    if bytes > MAX_VARCHAR_BYTES {
+        return "STRING".to_string();
+    }
+    format!("VARCHAR({})", bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numeric_typmod(precision: i32, scale: i32) -> i32 {
+        ((precision << 16) | (scale & 0x7ff)) + VARHDRSZ
+    }
+
+    #[test]
+    fn test_typmod_mapping() {
+        assert_eq!(starrocks_type(1700, numeric_typmod(12, 2)), "DECIMAL(12,2)");
+        assert_eq!(starrocks_type(1700, numeric_typmod(3, -2)), "DECIMAL(5,0)");
+        assert_eq!(starrocks_type(1700, numeric_typmod(60, 10)), "DECIMAL(38,9)");
+        assert_eq!(starrocks_type(1700, -1), "DECIMAL(38,9)");
+        assert_eq!(starrocks_type(1043, 50 + VARHDRSZ), "VARCHAR(200)");
+        assert_eq!(starrocks_type(1042, 1 + VARHDRSZ), "VARCHAR(4)");
+        assert_eq!(starrocks_type(1043, -1), "STRING");
+        assert_eq!(starrocks_type(1114, 3), "DATETIME");
+        assert_eq!(starrocks_type(1184, -1), "DATETIME");
+        assert_eq!(starrocks_type(1184, 6), "DATETIME");
+        assert_eq!(starrocks_type(17, -1), "VARBINARY");
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mapping = TypeMapping::parse("orders.amount:DECIMAL(18,4); audit.events.payload:STRING").unwrap();
+        assert_eq!(mapping.column_type("public", "orders", "amount", 1700, -1), "DECIMAL(18,4)");
+        assert_eq!(mapping.column_type("audit", "events", "payload", 3802, -1), "STRING");
+        assert_eq!(mapping.column_type("public", "orders", "total", 1700, -1), "DECIMAL(38,9)");
+        assert!(TypeMapping::parse("amount:INT").is_err());
+        assert!(TypeMapping::parse("orders.amount").is_err());
+    }
+}
@@ -0,0 +1,324 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Decodificación de columnas en formato binario de `pgoutput` (`binary 'true'`, PG14+).
+//!
+//! Con formato binario PostgreSQL envía cada valor con la función `typsend` de su
+//! tipo, así que solo se activa si todas las columnas publicadas son de un tipo
+//! soportado aquí (ver `BINARY_TYPES`). Un Relation con otro tipo reinicia el
+//! stream en texto (ver `WalDecoder::text_required`).
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime};
+use sonic_rs::{json, Value};
+use std::fmt::{self, Write};
+
+/// OIDs que se decodifican desde binario
+pub const BINARY_TYPES: &[u32] = &[
+    16,   // bool
+    17,   // bytea
+    19,   // name
+    20,   // int8
+    21,   // int2
+    23,   // int4
+    25,   // text
+    26,   // oid
+    114,  // json
+    700,  // float4
+    701,  // float8
+    1042, // bpchar
+    1043, // varchar
+    1082, // date
+    1083, // time
+    1114, // timestamp
+    1184, // timestamptz
+    1700, // numeric
+    2950, // uuid
+    3802, // jsonb
+];
+
+/// Microsegundos entre 1970-01-01 y 2000-01-01 (epoch de PostgreSQL)
+const PG_EPOCH_USEC: i64 = 946_684_800_000_000;
+/// Días entre 1970-01-01 y 2000-01-01
+const PG_EPOCH_DAYS: i64 = 10_957;
+
+/// Valor decodificado, antes de elegir su representación
+enum Decoded {
+    Bool(bool),
+    Int(i64),
+    /// float4/float8 ya en el texto de PostgreSQL
+    Float(String),
+    Text(String),
+}
+
+/// Convierte un valor binario al JSON que espera StarRocks (json/jsonb como JSON
+/// anidado, igual que en texto). Un valor mal formado se envía como hex (`\x..`).
+pub fn to_json(type_id: u32, data: &[u8]) -> Value {
+    match decode(type_id, data) {
+        Some(Decoded::Bool(b)) => json!(b),
+        Some(Decoded::Int(n)) => json!(n),
+        // NaN/Infinity no existen en JSON: se envían como el texto de PostgreSQL
+        Some(Decoded::Float(s)) => match s.parse::<f64>() {
+            Ok(f) if f.is_finite() => json!(f),
+            _ => json!(s),
+        },
+        Some(Decoded::Text(s)) if matches!(type_id, 114 | 3802) => {
+            sonic_rs::from_str::<Value>(&s).unwrap_or_else(|_| json!(s))
+        }
+        Some(Decoded::Text(s)) => json!(s),
+        None => json!(hex(data)),
+    }
+}
+
+/// Representación de texto de PostgreSQL (para comparar con filas de COPY)
+pub fn to_text(type_id: u32, data: &[u8]) -> Option<String> {
+    Some(match decode(type_id, data)? {
+        Decoded::Bool(b) => if b { "t".to_string() } else { "f".to_string() },
+        Decoded::Int(n) => n.to_string(),
+        Decoded::Float(s) | Decoded::Text(s) => s,
+    })
+}
+
+fn decode(type_id: u32, data: &[u8]) -> Option<Decoded> {
+    Some(match type_id {
+        16 => Decoded::Bool(*data.first()? != 0),
+        21 => Decoded::Int(i16::from_be_bytes(data.try_into().ok()?) as i64),
+        23 => Decoded::Int(i32::from_be_bytes(data.try_into().ok()?) as i64),
+        26 => Decoded::Int(u32::from_be_bytes(data.try_into().ok()?) as i64),
+        20 => Decoded::Int(i64::from_be_bytes(data.try_into().ok()?)),
+        // Precisión de `%g` que usa PostgreSQL: FLT_DIG / DBL_DIG
+        700 => Decoded::Float(float_text(f32::from_be_bytes(data.try_into().ok()?), 6)),
+        701 => Decoded::Float(float_text(f64::from_be_bytes(data.try_into().ok()?), 15)),
+        1700 => Decoded::Text(numeric(data)?),
+        1082 => Decoded::Text(date(i32::from_be_bytes(data.try_into().ok()?))?),
+        1083 => Decoded::Text(time(i64::from_be_bytes(data.try_into().ok()?))?),
+        1114 => Decoded::Text(timestamp(i64::from_be_bytes(data.try_into().ok()?), "")?),
+        // timestamptz en UTC, como lo imprime una sesión con TimeZone = UTC
+        1184 => Decoded::Text(timestamp(i64::from_be_bytes(data.try_into().ok()?), "+00")?),
+        2950 => Decoded::Text(uuid(data)?),
+        17 => Decoded::Text(hex(data)),
+        25 | 1043 | 1042 | 19 | 114 => Decoded::Text(String::from_utf8_lossy(data).into_owned()),
+        // jsonb binario: byte de versión (1) + texto
+        3802 => Decoded::Text(String::from_utf8_lossy(data.get(1..)?).into_owned()),
+        _ => return None,
+    })
+}
+
+/// NUMERIC: dígitos en base 10000 con peso, signo y escala
+/// Salida de float4/float8 de PostgreSQL 12+: los dígitos mínimos que reconstruyen
+/// el valor en su propio tipo, con notación exponencial (`1e+20`, `1.5e-07`) cuando
+/// el exponente sale de `[-4, precision)`, igual que `%g`.
+fn float_text<F: fmt::Display + fmt::LowerExp>(f: F, precision: i32) -> String {
+    let sci = format!("{:e}", f);
+    match sci.as_str() {
+        "NaN" => return sci,
+        "inf" => return "Infinity".to_string(),
+        "-inf" => return "-Infinity".to_string(),
+        _ => {}
+    }
+    let Some((mantissa, exp)) = sci.split_once('e') else {
+        return f.to_string();
+    };
+    let exp: i32 = exp.parse().unwrap_or(0);
+    if (-4..precision).contains(&exp) {
+        f.to_string()
+    } else {
+        format!("{}e{}{:02}", mantissa, if exp < 0 { '-' } else { '+' }, exp.abs())
+    }
+}
+
+fn numeric(data: &[u8]) -> Option<String> {
+    let u16_at = |i: usize| Some(u16::from_be_bytes([*data.get(i)?, *data.get(i + 1)?]));
+    let ndigits = u16_at(0)? as usize;
+    let weight = u16_at(2)? as i16 as i64;
+    let sign = u16_at(4)?;
+    let dscale = u16_at(6)? as usize;
+
+    match sign {
+        0xC000 => return Some("NaN".to_string()),
+        0xD000 => return Some("Infinity".to_string()),
+        0xF000 => return Some("-Infinity".to_string()),
+        _ => {}
+    }
+
+    let digits = (0..ndigits).map(|i| u16_at(8 + i * 2)).collect::<Option<Vec<u16>>>()?;
+    let digit = |i: i64| if i >= 0 { digits.get(i as usize).copied().unwrap_or(0) } else { 0 };
+
+    let mut out = String::new();
+    if sign == 0x4000 {
+        out.push('-');
+    }
+    if weight < 0 {
+        out.push('0');
+    } else {
+        for i in 0..=weight {
+            if i == 0 {
+                write!(out, "{}", digit(i)).ok()?;
+            } else {
+                write!(out, "{:04}", digit(i)).ok()?;
+            }
+        }
+    }
+
+    if dscale > 0 {
+        let mut frac = String::with_capacity(dscale + 4);
+        let mut i = weight + 1;
+        while frac.len() < dscale {
+            write!(frac, "{:04}", digit(i)).ok()?;
+            i += 1;
+        }
+        frac.truncate(dscale);
+        out.push('.');
+        out.push_str(&frac);
+    }
+    Some(out)
+}
+
+fn date(days: i32) -> Option<String> {
+    match days {
+        i32::MAX => Some("infinity".to_string()),
+        i32::MIN => Some("-infinity".to_string()),
+        _ => {
+            let date = NaiveDate::from_ymd_opt(1970, 1, 1)?
+                .checked_add_signed(chrono::Duration::days(days as i64 + PG_EPOCH_DAYS))?;
+            Some(era(date.year(), &date.format("-%m-%d").to_string()))
+        }
+    }
+}
+
+fn time(usec: i64) -> Option<String> {
+    let secs = usec.div_euclid(1_000_000);
+    let micros = usec.rem_euclid(1_000_000);
+    let time = NaiveTime::from_num_seconds_from_midnight_opt(secs as u32, 0)?;
+    Some(format!("{}{}", time.format("%H:%M:%S"), fraction(micros)))
+}
+
+/// timestamp y timestamptz (en UTC, con `offset` después de la hora)
+fn timestamp(usec: i64, offset: &str) -> Option<String> {
+    match usec {
+        i64::MAX => Some("infinity".to_string()),
+        i64::MIN => Some("-infinity".to_string()),
+        _ => {
+            let unix = usec.checked_add(PG_EPOCH_USEC)?;
+            let dt = DateTime::from_timestamp(unix.div_euclid(1_000_000), 0)?;
+            let rest = format!("{}{}{}", dt.format("-%m-%d %H:%M:%S"), fraction(unix.rem_euclid(1_000_000)), offset);
+            Some(era(dt.year(), &rest))
+        }
+    }
+}
+
+/// Año como lo imprime PostgreSQL: los años <= 0 (astronómicos) son `N BC`
+fn era(year: i32, rest: &str) -> String {
+    if year <= 0 {
+        format!("{:04}{} BC", 1 - year, rest)
+    } else {
+        format!("{:04}{}", year, rest)
+    }
+}
+
+/// Fracción de segundo como la imprime PostgreSQL (sin ceros finales)
+fn fraction(micros: i64) -> String {
+    if micros == 0 {
+        return String::new();
+    }
+    let frac = format!(".{:06}", micros);
+    frac.trim_end_matches('0').to_string()
+}
+
+fn uuid(data: &[u8]) -> Option<String> {
+    if data.len() != 16 {
+        return None;
+    }
+    let h = hex_digits(data);
+    Some(format!("{}-{}-{}-{}-{}", &h[0..8], &h[8..12], &h[12..16], &h[16..20], &h[20..32]))
+}
+
+/// Formato hex de bytea (`\x0102...`)
+fn hex(data: &[u8]) -> String {
+    format!("\\x{}", hex_digits(data))
+}
+
+fn hex_digits(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len() * 2);
+    for b in data {
+        let _ = write!(out, "{:02x}", b);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numeric_bytes(weight: i16, sign: u16, dscale: u16, digits: &[u16]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&(digits.len() as u16).to_be_bytes());
+        buf.extend_from_slice(&weight.to_be_bytes());
+        buf.extend_from_slice(&sign.to_be_bytes());
+        buf.extend_from_slice(&dscale.to_be_bytes());
+        for d in digits {
+            buf.extend_from_slice(&d.to_be_bytes());
+        }
+        buf
+    }
+
+    #[test]
+    fn test_numeric() {
+        // 12345.678 = [1, 2345, 6780], weight 1
+        assert_eq!(numeric(&numeric_bytes(1, 0, 3, &[1, 2345, 6780])).unwrap(), "12345.678");
+        // -0.00001234 = [1234], weight -2
+        assert_eq!(numeric(&numeric_bytes(-2, 0x4000, 8, &[1234])).unwrap(), "-0.00001234");
+        // 10000 = [1], weight 1, sin dígitos para el grupo 0000
+        assert_eq!(numeric(&numeric_bytes(1, 0, 0, &[1])).unwrap(), "10000");
+        assert_eq!(numeric(&numeric_bytes(0, 0xC000, 0, &[])).unwrap(), "NaN");
+    }
+
+    #[test]
+    fn test_scalars() {
+        assert_eq!(to_text(23, &(-42i32).to_be_bytes()).unwrap(), "-42");
+        assert_eq!(to_text(16, &[1]).unwrap(), "t");
+        assert_eq!(to_json(701, &1.5f64.to_be_bytes()), json!(1.5));
+        assert_eq!(
+            to_text(2950, &[0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0, 0, 1, 2, 3, 4, 5, 6, 7]).unwrap(),
+            "12345678-9abc-def0-0001-020304050607"
+        );
+        assert_eq!(to_text(17, &[0xde, 0xad]).unwrap(), "\\xdead");
+    }
+
+    #[test]
+    fn test_floats() {
+        // float4 con sus propios dígitos, no los del f64 ensanchado
+        assert_eq!(to_text(700, &0.1f32.to_be_bytes()).unwrap(), "0.1");
+        assert_eq!(to_json(700, &0.1f32.to_be_bytes()), json!(0.1));
+        assert_eq!(to_text(700, &123456f32.to_be_bytes()).unwrap(), "123456");
+        assert_eq!(to_text(700, &1e6f32.to_be_bytes()).unwrap(), "1e+06");
+        assert_eq!(to_text(701, &1e14f64.to_be_bytes()).unwrap(), "100000000000000");
+        assert_eq!(to_text(701, &1e20f64.to_be_bytes()).unwrap(), "1e+20");
+        assert_eq!(to_text(701, &0.0001f64.to_be_bytes()).unwrap(), "0.0001");
+        assert_eq!(to_text(701, &1.5e-7f64.to_be_bytes()).unwrap(), "1.5e-07");
+        assert_eq!(to_text(701, &(-0.0f64).to_be_bytes()).unwrap(), "-0");
+        assert_eq!(to_text(701, &f64::INFINITY.to_be_bytes()).unwrap(), "Infinity");
+        assert_eq!(to_json(700, &f32::NEG_INFINITY.to_be_bytes()), json!("-Infinity"));
+        assert_eq!(to_json(701, &f64::NAN.to_be_bytes()), json!("NaN"));
+    }
+
+    #[test]
+    fn test_json() {
+        // jsonb: byte de versión + texto
+        assert_eq!(to_json(3802, b"\x01{\"a\": [1, 2]}"), json!({"a": [1, 2]}));
+        assert_eq!(to_json(114, b"[true]"), json!([true]));
+        assert_eq!(to_text(3802, b"\x01{\"a\": 1}").unwrap(), r#"{"a": 1}"#);
+    }
+
+    #[test]
+    fn test_datetime() {
+        // 2000-01-02 00:00:01.5
+        let usec: i64 = 86_400_000_000 + 1_500_000;
+        assert_eq!(to_text(1114, &usec.to_be_bytes()).unwrap(), "2000-01-02 00:00:01.5");
+        assert_eq!(to_text(1184, &usec.to_be_bytes()).unwrap(), "2000-01-02 00:00:01.5+00");
+        assert_eq!(to_text(1184, &i64::MAX.to_be_bytes()).unwrap(), "infinity");
+        assert_eq!(to_text(1082, &(-1i32).to_be_bytes()).unwrap(), "1999-12-31");
+        // 0001-01-01 BC = -730485 días desde 2000-01-01
+        assert_eq!(to_text(1082, &(-730_485i32).to_be_bytes()).unwrap(), "0001-01-01 BC");
+        assert_eq!(to_text(1083, &3_723_000_001i64.to_be_bytes()).unwrap(), "01:02:03.000001");
+    }
+}
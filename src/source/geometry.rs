@@ -0,0 +1,390 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Decodificación de `geometry` / `geography` de PostGIS.
+//!
+//! PostGIS imprime sus valores como EWKB en hex (`0101000020E6100000...`). Los
+//! OIDs de sus tipos dependen de la instalación, así que se detectan por nombre
+//! en el catálogo de tipos y aquí se convierten a WKT (lo que esperan las
+//! funciones `ST_*` de StarRocks) o a GeoJSON.
+
+use anyhow::{anyhow, Result};
+use sonic_rs::{json, Object as Map, Value};
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+/// Flags de EWKB en el tipo de geometría
+const EWKB_Z: u32 = 0x8000_0000;
+const EWKB_M: u32 = 0x4000_0000;
+const EWKB_SRID: u32 = 0x2000_0000;
+
+/// Formato en que se replican las columnas espaciales
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GeometryFormat {
+    /// `POINT(1 2)`: columnas STRING, usables con `ST_GeometryFromText`
+    #[default]
+    Wkt,
+    /// `{"type": "Point", "coordinates": [1, 2]}`: columnas JSON
+    GeoJson,
+    /// EWKB en hex tal como lo imprime PostGIS
+    Wkb,
+}
+
+impl FromStr for GeometryFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "wkt" => Ok(Self::Wkt),
+            "geojson" => Ok(Self::GeoJson),
+            "wkb" => Ok(Self::Wkb),
+            other => Err(anyhow!("Invalid geometry format '{}' (expected wkt, geojson or wkb)", other)),
+        }
+    }
+}
+
+impl fmt::Display for GeometryFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Wkt => write!(f, "wkt"),
+            Self::GeoJson => write!(f, "geojson"),
+            Self::Wkb => write!(f, "wkb"),
+        }
+    }
+}
+
+impl GeometryFormat {
+    /// Convierte el texto de una columna geometry/geography (EWKB en hex).
+    /// Un valor que no se puede decodificar se envía sin cambios.
+    pub fn to_json(self, text: &str) -> Value {
+        if self == Self::Wkb {
+            return json!(text);
+        }
+        let Some(geometry) = hex::decode(text).ok().and_then(|bytes| Geometry::parse(&bytes)) else {
+            return json!(text);
+        };
+        match self {
+            Self::GeoJson => geometry.to_geojson(),
+            _ => json!(geometry.to_wkt()),
+        }
+    }
+}
+
+/// Geometría decodificada (el SRID no se conserva: WKT y GeoJSON no lo llevan)
+#[derive(Debug, Clone, PartialEq)]
+struct Geometry {
+    dims: Dims,
+    shape: Shape,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Shape {
+    /// Un punto vacío (`POINT EMPTY`) no tiene coordenadas
+    Point(Option<Vec<f64>>),
+    LineString(Vec<Vec<f64>>),
+    Polygon(Vec<Vec<Vec<f64>>>),
+    MultiPoint(Vec<Geometry>),
+    MultiLineString(Vec<Geometry>),
+    MultiPolygon(Vec<Geometry>),
+    Collection(Vec<Geometry>),
+}
+
+/// Coordenadas además de X Y: Z (altura) y M (medida)
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Dims {
+    z: bool,
+    m: bool,
+}
+
+impl Dims {
+    /// Sufijo del tipo en WKT (`POINT Z`, `POINT M`, `POINT ZM`)
+    fn tag(self) -> &'static str {
+        match (self.z, self.m) {
+            (true, true) => " ZM",
+            (true, false) => " Z",
+            (false, true) => " M",
+            (false, false) => "",
+        }
+    }
+}
+
+/// Lector de bytes con el orden indicado por cada geometría
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let bytes = self.data.get(self.pos..self.pos + N)?.try_into().ok()?;
+        self.pos += N;
+        Some(bytes)
+    }
+
+    fn u32(&mut self, little: bool) -> Option<u32> {
+        let b = self.take::<4>()?;
+        Some(if little { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) })
+    }
+
+    fn f64(&mut self, little: bool) -> Option<f64> {
+        let b = self.take::<8>()?;
+        Some(if little { f64::from_le_bytes(b) } else { f64::from_be_bytes(b) })
+    }
+}
+
+impl Geometry {
+    fn parse(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader { data, pos: 0 };
+        let geometry = Self::read(&mut reader)?;
+        (reader.pos == data.len()).then_some(geometry)
+    }
+
+    fn read(r: &mut Reader) -> Option<Self> {
+        let little = r.take::<1>()?[0] == 1;
+        let raw_type = r.u32(little)?;
+        if raw_type & EWKB_SRID != 0 {
+            r.u32(little)?;
+        }
+
+        // EWKB usa flags; ISO WKB suma 1000 (Z), 2000 (M) o 3000 (ZM) al tipo
+        let base = raw_type & 0x0fff_ffff;
+        let (kind, iso_dims) = (base % 1000, base / 1000);
+        let has_z = raw_type & EWKB_Z != 0 || iso_dims == 1 || iso_dims == 3;
+        let has_m = raw_type & EWKB_M != 0 || iso_dims == 2 || iso_dims == 3;
+        let dims = Dims { z: has_z, m: has_m };
+        let len = 2 + has_z as usize + has_m as usize;
+
+        let coord = |r: &mut Reader| (0..len).map(|_| r.f64(little)).collect::<Option<Vec<f64>>>();
+        let points = |r: &mut Reader| {
+            let n = r.u32(little)?;
+            (0..n).map(|_| coord(r)).collect::<Option<Vec<_>>>()
+        };
+        let children = |r: &mut Reader| {
+            let n = r.u32(little)?;
+            (0..n).map(|_| Self::read(r)).collect::<Option<Vec<_>>>()
+        };
+
+        let shape = match kind {
+            1 => {
+                let c = coord(r)?;
+                // PostGIS codifica POINT EMPTY con coordenadas NaN
+                Shape::Point((!c.iter().all(|v| v.is_nan())).then_some(c))
+            }
+            2 => Shape::LineString(points(r)?),
+            3 => {
+                let rings = r.u32(little)?;
+                Shape::Polygon((0..rings).map(|_| points(r)).collect::<Option<Vec<_>>>()?)
+            }
+            4 => Shape::MultiPoint(children(r)?),
+            5 => Shape::MultiLineString(children(r)?),
+            6 => Shape::MultiPolygon(children(r)?),
+            7 => Shape::Collection(children(r)?),
+            _ => return None,
+        };
+        Some(Self { dims, shape })
+    }
+
+    fn to_wkt(&self) -> String {
+        let mut out = String::new();
+        self.write_wkt(&mut out, true);
+        out
+    }
+
+    /// Con `tagged` se escribe el nombre del tipo con sus dimensiones (no dentro
+    /// de MULTI*), como `ST_AsText`: `POINT(1 2)`, `POINT Z (1 2 3)`
+    fn write_wkt(&self, out: &mut String, tagged: bool) {
+        let (name, empty) = match &self.shape {
+            Shape::Point(c) => ("POINT", c.is_none()),
+            Shape::LineString(c) => ("LINESTRING", c.is_empty()),
+            Shape::Polygon(r) => ("POLYGON", r.is_empty()),
+            Shape::MultiPoint(g) => ("MULTIPOINT", g.is_empty()),
+            Shape::MultiLineString(g) => ("MULTILINESTRING", g.is_empty()),
+            Shape::MultiPolygon(g) => ("MULTIPOLYGON", g.is_empty()),
+            Shape::Collection(g) => ("GEOMETRYCOLLECTION", g.is_empty()),
+        };
+        if tagged {
+            out.push_str(name);
+            out.push_str(self.dims.tag());
+            if empty {
+                out.push_str(" EMPTY");
+                return;
+            }
+            if !self.dims.tag().is_empty() {
+                out.push(' ');
+            }
+        }
+
+        match &self.shape {
+            Shape::Point(c) => {
+                out.push('(');
+                write_coord(out, c.as_deref().unwrap_or_default());
+                out.push(')');
+            }
+            Shape::LineString(c) => write_coords(out, c),
+            Shape::Polygon(rings) => {
+                out.push('(');
+                for (i, ring) in rings.iter().enumerate() {
+                    if i > 0 { out.push(','); }
+                    write_coords(out, ring);
+                }
+                out.push(')');
+            }
+            Shape::MultiPoint(g) | Shape::MultiLineString(g) | Shape::MultiPolygon(g) | Shape::Collection(g) => {
+                // Dentro de una colección cada geometría lleva su tipo
+                let tagged = matches!(self.shape, Shape::Collection(_));
+                out.push('(');
+                for (i, child) in g.iter().enumerate() {
+                    if i > 0 { out.push(','); }
+                    child.write_wkt(out, tagged);
+                }
+                out.push(')');
+            }
+        }
+    }
+
+    fn to_geojson(&self) -> Value {
+        // GeoJSON solo admite X Y y una altura: la M se descarta
+        let len = 2 + self.dims.z as usize;
+        let coords = |c: &[f64]| Value::from(c.iter().take(len).map(|v| json!(*v)).collect::<Vec<Value>>());
+        let line = |c: &[Vec<f64>]| Value::from(c.iter().map(|p| coords(p)).collect::<Vec<Value>>());
+        let polygon = |r: &[Vec<Vec<f64>>]| Value::from(r.iter().map(|l| line(l)).collect::<Vec<Value>>());
+
+        let (kind, coordinates) = match &self.shape {
+            Shape::Point(c) => ("Point", c.as_deref().map_or_else(|| json!([]), coords)),
+            Shape::LineString(c) => ("LineString", line(c)),
+            Shape::Polygon(r) => ("Polygon", polygon(r)),
+            Shape::MultiPoint(g) => ("MultiPoint", Value::from(g.iter().map(|p| match &p.shape {
+                Shape::Point(Some(c)) => coords(c),
+                _ => json!([]),
+            }).collect::<Vec<Value>>())),
+            Shape::MultiLineString(g) => ("MultiLineString", Value::from(g.iter().map(|l| match &l.shape {
+                Shape::LineString(c) => line(c),
+                _ => json!([]),
+            }).collect::<Vec<Value>>())),
+            Shape::MultiPolygon(g) => ("MultiPolygon", Value::from(g.iter().map(|p| match &p.shape {
+                Shape::Polygon(r) => polygon(r),
+                _ => json!([]),
+            }).collect::<Vec<Value>>())),
+            Shape::Collection(g) => {
+                let mut obj = Map::with_capacity(2);
+                obj.insert("type", json!("GeometryCollection"));
+                obj.insert("geometries", Value::from(g.iter().map(Self::to_geojson).collect::<Vec<Value>>()));
+                return Value::from(obj);
+            }
+        };
+
+        let mut obj = Map::with_capacity(2);
+        obj.insert("type", json!(kind));
+        obj.insert("coordinates", coordinates);
+        Value::from(obj)
+    }
+}
+
+fn write_coord(out: &mut String, coord: &[f64]) {
+    for (i, v) in coord.iter().enumerate() {
+        if i > 0 { out.push(' '); }
+        let _ = write!(out, "{}", v);
+    }
+}
+
+fn write_coords(out: &mut String, coords: &[Vec<f64>]) {
+    out.push('(');
+    for (i, c) in coords.iter().enumerate() {
+        if i > 0 { out.push(','); }
+        write_coord(out, c);
+    }
+    out.push(')');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// WKB little endian de un tipo sin SRID
+    fn wkb(kind: u32, body: &[u8]) -> Vec<u8> {
+        let mut buf = vec![1];
+        buf.extend_from_slice(&kind.to_le_bytes());
+        buf.extend_from_slice(body);
+        buf
+    }
+
+    fn points(coords: &[(f64, f64)]) -> Vec<u8> {
+        let mut buf = (coords.len() as u32).to_le_bytes().to_vec();
+        for (x, y) in coords {
+            buf.extend_from_slice(&x.to_le_bytes());
+            buf.extend_from_slice(&y.to_le_bytes());
+        }
+        buf
+    }
+
+    #[test]
+    fn test_point_with_srid() {
+        // ST_AsEWKB('SRID=4326;POINT(1 2)')
+        let hex = "0101000020E6100000000000000000F03F0000000000000040";
+        assert_eq!(GeometryFormat::Wkt.to_json(hex), json!("POINT(1 2)"));
+        assert_eq!(GeometryFormat::GeoJson.to_json(hex), json!({"type": "Point", "coordinates": [1.0, 2.0]}));
+        assert_eq!(GeometryFormat::Wkb.to_json(hex), json!(hex));
+        // Hex inválido: sin cambios
+        assert_eq!(GeometryFormat::Wkt.to_json("zz"), json!("zz"));
+    }
+
+    #[test]
+    fn test_polygon_and_multi() {
+        let ring = points(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 0.0)]);
+        let mut body = 1u32.to_le_bytes().to_vec();
+        body.extend_from_slice(&ring);
+        let polygon = wkb(3, &body);
+        assert_eq!(Geometry::parse(&polygon).unwrap().to_wkt(), "POLYGON((0 0,1 0,1 1,0 0))");
+
+        let mut multi = 2u32.to_le_bytes().to_vec();
+        multi.extend_from_slice(&wkb(1, &[1.5f64.to_le_bytes(), 2.5f64.to_le_bytes()].concat()));
+        multi.extend_from_slice(&wkb(1, &[3f64.to_le_bytes(), 4f64.to_le_bytes()].concat()));
+        let multi = wkb(4, &multi);
+        assert_eq!(Geometry::parse(&multi).unwrap().to_wkt(), "MULTIPOINT((1.5 2.5),(3 4))");
+        assert_eq!(
+            GeometryFormat::GeoJson.to_json(&hex::encode(&multi)),
+            json!({"type": "MultiPoint", "coordinates": [[1.5, 2.5], [3.0, 4.0]]})
+        );
+
+        let mut collection = 2u32.to_le_bytes().to_vec();
+        collection.extend_from_slice(&wkb(2, &points(&[(0.0, 0.0), (1.0, 1.0)])));
+        collection.extend_from_slice(&wkb(1, &[f64::NAN.to_le_bytes(), f64::NAN.to_le_bytes()].concat()));
+        assert_eq!(
+            Geometry::parse(&wkb(7, &collection)).unwrap().to_wkt(),
+            "GEOMETRYCOLLECTION(LINESTRING(0 0,1 1),POINT EMPTY)"
+        );
+    }
+
+    #[test]
+    fn test_point_z_m() {
+        let body = [1f64, 2f64, 3f64].map(f64::to_le_bytes).concat();
+        let point = wkb(1 | EWKB_Z, &body);
+        assert_eq!(Geometry::parse(&point).unwrap().to_wkt(), "POINT Z (1 2 3)");
+        assert!(Geometry::parse(&point[..point.len() - 1]).is_none());
+
+        // EWKB con flag M e ISO WKB 2001 (M) / 3001 (ZM)
+        let point_m = wkb(1 | EWKB_M, &body);
+        assert_eq!(Geometry::parse(&point_m).unwrap().to_wkt(), "POINT M (1 2 3)");
+        assert_eq!(Geometry::parse(&wkb(2001, &body)).unwrap().to_wkt(), "POINT M (1 2 3)");
+        let body_zm = [1f64, 2f64, 3f64, 4f64].map(f64::to_le_bytes).concat();
+        assert_eq!(Geometry::parse(&wkb(3001, &body_zm)).unwrap().to_wkt(), "POINT ZM (1 2 3 4)");
+        let empty = [f64::NAN; 3].map(f64::to_le_bytes).concat();
+        assert_eq!(Geometry::parse(&wkb(1001, &empty)).unwrap().to_wkt(), "POINT Z EMPTY");
+
+        // GeoJSON no tiene M: solo X Y (y Z en ZM)
+        assert_eq!(
+            GeometryFormat::GeoJson.to_json(&hex::encode(&point_m)),
+            json!({"type": "Point", "coordinates": [1.0, 2.0]})
+        );
+        assert_eq!(
+            GeometryFormat::GeoJson.to_json(&hex::encode(wkb(3001, &body_zm))),
+            json!({"type": "Point", "coordinates": [1.0, 2.0, 3.0]})
+        );
+
+        // Las dimensiones van en el tipo de la colección, no en cada punto
+        let mut multi = 1u32.to_le_bytes().to_vec();
+        multi.extend_from_slice(&point);
+        assert_eq!(Geometry::parse(&wkb(4 | EWKB_Z, &multi)).unwrap().to_wkt(), "MULTIPOINT Z ((1 2 3))");
+        assert_eq!(Geometry::parse(&wkb(7 | EWKB_Z, &multi)).unwrap().to_wkt(), "GEOMETRYCOLLECTION Z (POINT Z (1 2 3))");
+    }
+}
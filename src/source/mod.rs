@@ -1,5 +1,11 @@
 pub mod postgres;
 pub mod parser;
+pub mod pg_value;
+pub mod binary;
+pub mod geometry;
+pub mod origin;
+pub mod snapshot;
+pub mod type_catalog;
 
 
 
@@ -0,0 +1,85 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+use std::collections::HashSet;
+use std::fmt;
+
+/// Filtro por origen de replicación (`pg_replication_origin`), para evitar loops
+/// cuando datos derivados de StarRocks se escriben de vuelta en PostgreSQL
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum OriginFilter {
+    /// Replicar todos los cambios (default de PostgreSQL, `origin 'any'`)
+    #[default]
+    Any,
+    /// Solo cambios locales, sin origen: `origin 'none'` en PG16+
+    None,
+    /// Descartar los cambios de estos orígenes
+    Skip(HashSet<String>),
+}
+
+impl OriginFilter {
+    /// Parsear `ORIGIN_FILTER`: vacío o `any`, `none`, o una lista de orígenes
+    pub fn parse(value: &str) -> Self {
+        match value.trim() {
+            "" | "any" => Self::Any,
+            "none" => Self::None,
+            list => Self::Skip(
+                list.split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Indica si se replican los cambios de una transacción con este origen
+    pub fn accepts(&self, origin: Option<&str>) -> bool {
+        match (self, origin) {
+            (_, None) | (Self::Any, _) => true,
+            (Self::None, Some(_)) => false,
+            (Self::Skip(origins), Some(origin)) => !origins.contains(origin),
+        }
+    }
+
+    /// Valor de la opción `origin` de pgoutput (el servidor filtra, PG16+)
+    pub fn server_option(&self) -> Option<&'static str> {
+        match self {
+            Self::None => Some("none"),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for OriginFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => write!(f, "any"),
+            Self::None => write!(f, "none"),
+            Self::Skip(origins) => {
+                let mut origins: Vec<_> = origins.iter().map(String::as_str).collect();
+                origins.sort_unstable();
+                write!(f, "skip {}", origins.join(","))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin_filter() {
+        let skip = OriginFilter::parse("starrocks_writeback, other");
+        assert!(skip.accepts(None));
+        assert!(!skip.accepts(Some("starrocks_writeback")));
+        assert!(skip.accepts(Some("pg_16384")));
+
+        let none = OriginFilter::parse("none");
+        assert!(none.accepts(None));
+        assert!(!none.accepts(Some("pg_16384")));
+        assert_eq!(none.server_option(), Some("none"));
+
+        assert!(OriginFilter::parse("").accepts(Some("pg_16384")));
+    }
+}
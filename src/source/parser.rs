@@ -2,12 +2,15 @@ use anyhow::{Result, anyhow};
 use bytes::{Buf, Bytes};
 use memchr::memchr;
 use simdutf8::basic::from_utf8;
+use std::sync::Arc;
 
 /// Wrapper que incluye LSN del WAL para checkpointing
 #[derive(Debug, Clone)]
 pub struct CdcEvent {
     pub lsn: u64,  // LSN del WAL donde ocurrió este evento
     pub message: CdcMessage,
+    /// Origen de replicación de la transacción (None = cambio local)
+    pub origin: Option<Arc<str>>,
 }
 
 #[derive(Debug, Clone)]
@@ -23,6 +26,11 @@ pub enum CdcMessage {
         end_lsn: u64,
         timestamp: u64,
     },
+    /// Origen de replicación de la transacción en curso (después del Begin)
+    Origin {
+        commit_lsn: u64,
+        name: String,
+    },
     Relation {
         id: u32,
         namespace: String,
@@ -30,6 +38,12 @@ pub enum CdcMessage {
         replica_identity: u8,
         columns: Vec<Column>,
     },
+    /// Tipo definido por el usuario usado por una columna (se envía antes del Relation)
+    Type {
+        id: u32,
+        namespace: String,
+        name: String,
+    },
     Insert {
         relation_id: u32,
         tuple: Tuple,
@@ -43,11 +57,78 @@ pub enum CdcMessage {
         relation_id: u32,
         old_tuple: Option<Tuple>,
     },
+    /// `TRUNCATE` de una o más tablas (incluye las alcanzadas por CASCADE)
+    Truncate {
+        relation_ids: Vec<u32>,
+        cascade: bool,
+        restart_identity: bool,
+    },
+    /// Mensaje de `pg_logical_emit_message` (requiere `messages 'true'`)
+    Message {
+        lsn: u64,
+        prefix: String,
+        content: Bytes,
+        /// Emitido dentro de la transacción (se entrega con su commit)
+        transactional: bool,
+    },
     KeepAlive {
         wal_end: u64,
         timestamp: u64,
         reply_requested: bool,
     },
+    /// Inicio de un bloque de una transacción en curso (protocolo >= 2, `streaming 'on'`)
+    StreamStart {
+        xid: u32,
+        first_segment: bool,
+    },
+    StreamStop,
+    StreamCommit {
+        xid: u32,
+        flags: u8,
+        commit_lsn: u64,
+        end_lsn: u64,
+        timestamp: u64,
+    },
+    /// Abort de la transacción (`subxid == xid`) o de una subtransacción
+    StreamAbort {
+        xid: u32,
+        subxid: u32,
+    },
+    /// Two-phase commit (protocolo >= 3, `two_phase 'on'`): inicio de una transacción preparada
+    BeginPrepare {
+        prepare_lsn: u64,
+        end_lsn: u64,
+        timestamp: u64,
+        xid: u32,
+        gid: String,
+    },
+    /// `PREPARE TRANSACTION`; con `streamed = true` cierra una transacción en streaming
+    Prepare {
+        flags: u8,
+        prepare_lsn: u64,
+        end_lsn: u64,
+        timestamp: u64,
+        xid: u32,
+        gid: String,
+        streamed: bool,
+    },
+    CommitPrepared {
+        flags: u8,
+        commit_lsn: u64,
+        end_lsn: u64,
+        timestamp: u64,
+        xid: u32,
+        gid: String,
+    },
+    RollbackPrepared {
+        flags: u8,
+        prepare_end_lsn: u64,
+        rollback_end_lsn: u64,
+        prepare_timestamp: u64,
+        rollback_timestamp: u64,
+        xid: u32,
+        gid: String,
+    },
     Unknown,
 }
 
@@ -62,51 +143,112 @@ pub struct Column {
 #[derive(Debug, Clone)]
 pub struct Tuple {
     pub cols: Vec<TupleData>,
-    pub toast_bitmap: u64,  // Bitmap SIMD-friendly (hasta 64 columnas)
+    pub toast_bitmap: ToastBitmap,
 }
 
 impl Tuple {
     /// O(1) - single CPU instruction (test bitmap != 0)
     #[inline]
     pub fn has_toast(&self) -> bool {
-        self.toast_bitmap != 0
+        !self.toast_bitmap.is_empty()
     }
     
     /// POPCNT instruction - cuenta bits en 1 ciclo
     #[inline]
     pub fn toast_count(&self) -> u32 {
-        self.toast_bitmap.count_ones()
+        self.toast_bitmap.count()
     }
     
     /// Verifica si columna especifica es TOAST - O(1)
     #[inline]
     pub fn is_toast_column(&self, idx: usize) -> bool {
-        if idx >= 64 { return false; }
-        self.toast_bitmap & (1u64 << idx) != 0
+        self.toast_bitmap.contains(idx)
     }
     
     /// Itera solo columnas TOAST usando trailing_zeros (CTZ) - O(k) donde k = columnas TOAST
-    pub fn toast_indices(&self) -> ToastIterator {
-        ToastIterator { bitmap: self.toast_bitmap }
+    pub fn toast_indices(&self) -> ToastIterator<'_> {
+        self.toast_bitmap.iter()
+    }
+}
+
+/// Bitmap de columnas TOAST. Las primeras 64 columnas van en un u64 inline
+/// (fast path SIMD-friendly, sin allocations); las tablas más anchas agregan
+/// palabras en el heap solo cuando una columna >= 64 es TOAST.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ToastBitmap {
+    low: u64,
+    high: Option<Box<[u64]>>,
+}
+
+impl ToastBitmap {
+    /// Marca la columna `idx` como TOAST
+    pub fn set(&mut self, idx: usize) {
+        if idx < 64 {
+            self.low |= 1u64 << idx;
+            return;
+        }
+        let word = (idx - 64) / 64;
+        let high = self.high.get_or_insert_with(|| vec![0; word + 1].into_boxed_slice());
+        if high.len() <= word {
+            let mut words = high.to_vec();
+            words.resize(word + 1, 0);
+            *high = words.into_boxed_slice();
+        }
+        high[word] |= 1u64 << (idx % 64);
+    }
+
+    /// O(1) para tablas de hasta 64 columnas (`high` solo existe con un bit marcado)
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.low == 0 && self.high.is_none()
+    }
+
+    #[inline]
+    pub fn contains(&self, idx: usize) -> bool {
+        if idx < 64 {
+            return self.low & (1u64 << idx) != 0;
+        }
+        self.high
+            .as_ref()
+            .and_then(|high| high.get((idx - 64) / 64))
+            .is_some_and(|word| word & (1u64 << (idx % 64)) != 0)
+    }
+
+    pub fn count(&self) -> u32 {
+        self.low.count_ones() + self.high.iter().flat_map(|h| h.iter()).map(|w| w.count_ones()).sum::<u32>()
+    }
+
+    pub fn iter(&self) -> ToastIterator<'_> {
+        ToastIterator { bitmap: self, word: 0, bits: self.low }
+    }
+
+    fn word(&self, word: usize) -> Option<u64> {
+        match word {
+            0 => Some(self.low),
+            n => self.high.as_ref()?.get(n - 1).copied(),
+        }
     }
 }
 
 /// Iterator que usa CTZ (Count Trailing Zeros) para encontrar bits eficientemente
-pub struct ToastIterator {
-    bitmap: u64,
+pub struct ToastIterator<'a> {
+    bitmap: &'a ToastBitmap,
+    word: usize,
+    bits: u64,
 }
 
-impl Iterator for ToastIterator {
+impl Iterator for ToastIterator<'_> {
     type Item = usize;
     
     fn next(&mut self) -> Option<usize> {
-        if self.bitmap == 0 {
-            return None;
+        while self.bits == 0 {
+            self.word += 1;
+            self.bits = self.bitmap.word(self.word)?;
         }
         // CTZ: encuentra el bit mas bajo en O(1) con instruccion SIMD
-        let idx = self.bitmap.trailing_zeros() as usize;
+        let idx = self.word * 64 + self.bits.trailing_zeros() as usize;
         // Clear lowest bit: x & (x-1) elimina el bit mas bajo
-        self.bitmap &= self.bitmap - 1;
+        self.bits &= self.bits - 1;
         Some(idx)
     }
 }
@@ -115,25 +257,155 @@ impl Iterator for ToastIterator {
 pub enum TupleData {
     Null,
     Text(Bytes), // Zero-copy: Holds reference to original buffer if possible (Bytes is RefCounted)
+    Binary(Bytes), // Formato binario (`binary 'true'`), se decodifica en `source::binary`
     Toast,
 }
 
-pub struct PgOutputParser;
+/// Transacción en curso del bloque de streaming actual
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamContext {
+    /// xid top-level (del Stream Start)
+    pub top_xid: u32,
+    /// xid del último cambio (puede ser una subtransacción)
+    pub xid: u32,
+}
+
+/// Parser de `pgoutput`. Es stateful solo para el streaming de transacciones en
+/// curso: entre Stream Start y Stream Stop los cambios llevan un prefijo de xid.
+#[derive(Debug, Default)]
+pub struct PgOutputParser {
+    stream: Option<StreamContext>,
+}
 
 impl PgOutputParser {
-    pub fn parse(tag: u8, mut body: Bytes) -> Result<Option<CdcMessage>> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Contexto de streaming del último mensaje parseado (None fuera de un bloque)
+    pub fn stream_context(&self) -> Option<StreamContext> {
+        self.stream
+    }
+
+    pub fn parse(&mut self, tag: u8, mut body: Bytes) -> Result<Option<CdcMessage>> {
+        match tag {
+            b'S' => return self.parse_stream_start(&mut body),
+            b'E' => {
+                self.stream = None;
+                return Ok(Some(CdcMessage::StreamStop));
+            }
+            b'c' => return Self::parse_stream_commit(&mut body),
+            b'A' => return Self::parse_stream_abort(&mut body),
+            b'b' => return Self::parse_begin_prepare(&mut body),
+            b'P' => return Self::parse_prepare(&mut body, false),
+            b'p' => return Self::parse_prepare(&mut body, true),
+            b'K' => return Self::parse_commit_prepared(&mut body),
+            b'r' => return Self::parse_rollback_prepared(&mut body),
+            _ => {}
+        }
+
+        // Dentro de un bloque de streaming los cambios traen el xid (sub)transacción
+        if let Some(ref mut stream) = self.stream {
+            if matches!(tag, b'R' | b'I' | b'U' | b'D' | b'Y' | b'T' | b'M') {
+                if body.remaining() < 4 { return Err(anyhow!("EOF in stream xid")); }
+                stream.xid = body.get_u32();
+            }
+        }
+
         match tag {
             b'B' => Self::parse_begin(&mut body),
             b'C' => Self::parse_commit(&mut body),
+            b'O' => Self::parse_origin(&mut body),
             b'R' => Self::parse_relation(&mut body),
+            b'Y' => Self::parse_type(&mut body),
             b'I' => Self::parse_insert(&mut body),
             b'U' => Self::parse_update(&mut body),
             b'D' => Self::parse_delete(&mut body),
+            b'T' => Self::parse_truncate(&mut body),
+            b'M' => Self::parse_message(&mut body),
             b'k' => Self::parse_keepalive(&mut body),
             _ => Ok(Some(CdcMessage::Unknown)),
         }
     }
 
+    fn parse_stream_start(&mut self, data: &mut Bytes) -> Result<Option<CdcMessage>> {
+        if data.remaining() < 5 { return Err(anyhow!("EOF in stream start")); }
+        let xid = data.get_u32();
+        let first_segment = data.get_u8() == 1;
+        self.stream = Some(StreamContext { top_xid: xid, xid });
+        Ok(Some(CdcMessage::StreamStart { xid, first_segment }))
+    }
+
+    fn parse_stream_commit(data: &mut Bytes) -> Result<Option<CdcMessage>> {
+        if data.remaining() < 29 { return Err(anyhow!("EOF in stream commit")); }
+        let xid = data.get_u32();
+        let flags = data.get_u8();
+        let commit_lsn = data.get_u64();
+        let end_lsn = data.get_u64();
+        let timestamp = data.get_u64();
+        Ok(Some(CdcMessage::StreamCommit { xid, flags, commit_lsn, end_lsn, timestamp }))
+    }
+
+    fn parse_stream_abort(data: &mut Bytes) -> Result<Option<CdcMessage>> {
+        // Con `streaming 'parallel'` siguen abort_lsn y timestamp; no se usan
+        if data.remaining() < 8 { return Err(anyhow!("EOF in stream abort")); }
+        let xid = data.get_u32();
+        let subxid = data.get_u32();
+        Ok(Some(CdcMessage::StreamAbort { xid, subxid }))
+    }
+
+    fn parse_begin_prepare(data: &mut Bytes) -> Result<Option<CdcMessage>> {
+        if data.remaining() < 28 { return Err(anyhow!("EOF in begin prepare")); }
+        let prepare_lsn = data.get_u64();
+        let end_lsn = data.get_u64();
+        let timestamp = data.get_u64();
+        let xid = data.get_u32();
+        let gid = Self::read_string(data)?;
+        Ok(Some(CdcMessage::BeginPrepare { prepare_lsn, end_lsn, timestamp, xid, gid }))
+    }
+
+    fn parse_prepare(data: &mut Bytes, streamed: bool) -> Result<Option<CdcMessage>> {
+        if data.remaining() < 29 { return Err(anyhow!("EOF in prepare")); }
+        let flags = data.get_u8();
+        let prepare_lsn = data.get_u64();
+        let end_lsn = data.get_u64();
+        let timestamp = data.get_u64();
+        let xid = data.get_u32();
+        let gid = Self::read_string(data)?;
+        Ok(Some(CdcMessage::Prepare { flags, prepare_lsn, end_lsn, timestamp, xid, gid, streamed }))
+    }
+
+    fn parse_commit_prepared(data: &mut Bytes) -> Result<Option<CdcMessage>> {
+        if data.remaining() < 29 { return Err(anyhow!("EOF in commit prepared")); }
+        let flags = data.get_u8();
+        let commit_lsn = data.get_u64();
+        let end_lsn = data.get_u64();
+        let timestamp = data.get_u64();
+        let xid = data.get_u32();
+        let gid = Self::read_string(data)?;
+        Ok(Some(CdcMessage::CommitPrepared { flags, commit_lsn, end_lsn, timestamp, xid, gid }))
+    }
+
+    fn parse_rollback_prepared(data: &mut Bytes) -> Result<Option<CdcMessage>> {
+        if data.remaining() < 37 { return Err(anyhow!("EOF in rollback prepared")); }
+        let flags = data.get_u8();
+        let prepare_end_lsn = data.get_u64();
+        let rollback_end_lsn = data.get_u64();
+        let prepare_timestamp = data.get_u64();
+        let rollback_timestamp = data.get_u64();
+        let xid = data.get_u32();
+        let gid = Self::read_string(data)?;
+        Ok(Some(CdcMessage::RollbackPrepared {
+            flags,
+            prepare_end_lsn,
+            rollback_end_lsn,
+            prepare_timestamp,
+            rollback_timestamp,
+            xid,
+            gid,
+        }))
+    }
+
     fn parse_begin(data: &mut Bytes) -> Result<Option<CdcMessage>> {
         if data.len() < 20 { return Ok(None); }
         let final_lsn = data.get_u64();
@@ -151,6 +423,21 @@ impl PgOutputParser {
         Ok(Some(CdcMessage::Commit { flags, commit_lsn, end_lsn, timestamp }))
     }
 
+    fn parse_origin(data: &mut Bytes) -> Result<Option<CdcMessage>> {
+        if data.remaining() < 9 { return Err(anyhow!("EOF in origin")); }
+        let commit_lsn = data.get_u64();
+        let name = Self::read_string(data)?;
+        Ok(Some(CdcMessage::Origin { commit_lsn, name }))
+    }
+
+    fn parse_type(data: &mut Bytes) -> Result<Option<CdcMessage>> {
+        if data.remaining() < 4 { return Err(anyhow!("EOF in type")); }
+        let id = data.get_u32();
+        let namespace = Self::read_string(data)?;
+        let name = Self::read_string(data)?;
+        Ok(Some(CdcMessage::Type { id, namespace, name }))
+    }
+
     fn parse_relation(data: &mut Bytes) -> Result<Option<CdcMessage>> {
         if data.remaining() < 4 { return Err(anyhow!("EOF in relation")); }
         let id = data.get_u32();
@@ -203,6 +490,32 @@ impl PgOutputParser {
         Ok(Some(CdcMessage::Delete { relation_id, old_tuple }))
     }
 
+    fn parse_truncate(data: &mut Bytes) -> Result<Option<CdcMessage>> {
+        if data.remaining() < 5 { return Err(anyhow!("EOF in truncate")); }
+        let num_relations = data.get_u32() as usize;
+        // Bits de opciones: 1 = CASCADE, 2 = RESTART IDENTITY
+        let options = data.get_u8();
+        if data.remaining() < num_relations * 4 { return Err(anyhow!("EOF in truncate relations")); }
+        let relation_ids = (0..num_relations).map(|_| data.get_u32()).collect();
+        Ok(Some(CdcMessage::Truncate {
+            relation_ids,
+            cascade: options & 1 != 0,
+            restart_identity: options & 2 != 0,
+        }))
+    }
+
+    fn parse_message(data: &mut Bytes) -> Result<Option<CdcMessage>> {
+        if data.remaining() < 9 { return Err(anyhow!("EOF in message")); }
+        let transactional = data.get_u8() & 1 != 0;
+        let lsn = data.get_u64();
+        let prefix = Self::read_string(data)?;
+        if data.remaining() < 4 { return Err(anyhow!("EOF in message content")); }
+        let len = data.get_u32() as usize;
+        if data.remaining() < len { return Err(anyhow!("EOF in message content")); }
+        let content = data.split_to(len); // Zero-copy slice
+        Ok(Some(CdcMessage::Message { lsn, prefix, content, transactional }))
+    }
+
     fn parse_keepalive(data: &mut Bytes) -> Result<Option<CdcMessage>> {
         let wal_end = data.get_u64();
         let timestamp = data.get_u64();
@@ -228,7 +541,7 @@ impl PgOutputParser {
     fn read_tuple(data: &mut Bytes) -> Result<Tuple> {
         let num_cols = data.get_u16();
         let mut cols = Vec::with_capacity(num_cols as usize);
-        let mut toast_bitmap: u64 = 0;
+        let mut toast_bitmap = ToastBitmap::default();
         
         for idx in 0..num_cols {
             let tag = data.get_u8();
@@ -236,16 +549,18 @@ impl PgOutputParser {
                 b'n' => cols.push(TupleData::Null),
                 b'u' => {
                     cols.push(TupleData::Toast);
-                    // Marcar bit en bitmap si idx < 64 (limite de u64)
-                    if idx < 64 {
-                        toast_bitmap |= 1u64 << idx;
-                    }
+                    toast_bitmap.set(idx as usize);
                 },
                 b't' => {
                     let len = data.get_u32() as usize;
                     let val = data.split_to(len); // Zero-copy slice
                     cols.push(TupleData::Text(val));
                 }
+                b'b' => {
+                    let len = data.get_u32() as usize;
+                    let val = data.split_to(len); // Zero-copy slice
+                    cols.push(TupleData::Binary(val));
+                }
                 _ => return Err(anyhow!("Unknown column tag {}", tag)),
             }
         }
@@ -254,3 +569,164 @@ impl PgOutputParser {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toast_bitmap_beyond_64_columns() {
+        let mut bitmap = ToastBitmap::default();
+        assert!(bitmap.is_empty());
+
+        bitmap.set(3);
+        bitmap.set(70);
+        bitmap.set(140);
+        assert!(bitmap.contains(70) && bitmap.contains(140));
+        assert!(!bitmap.contains(64) && !bitmap.contains(200));
+        assert_eq!(bitmap.count(), 3);
+        assert_eq!(bitmap.iter().collect::<Vec<_>>(), vec![3, 70, 140]);
+
+        // Solo columnas anchas: el u64 inline queda vacío pero el bitmap no
+        let mut wide = ToastBitmap::default();
+        wide.set(89);
+        assert!(!wide.is_empty());
+        assert_ne!(wide, ToastBitmap::default());
+    }
+
+    #[test]
+    fn test_stream_messages_and_xid_prefix() {
+        use bytes::{BufMut, BytesMut};
+
+        let mut parser = PgOutputParser::new();
+
+        // S: xid + first_segment
+        let mut start = BytesMut::new();
+        start.put_u32(700);
+        start.put_u8(1);
+        let msg = parser.parse(b'S', start.freeze()).unwrap();
+        assert!(matches!(msg, Some(CdcMessage::StreamStart { xid: 700, first_segment: true })));
+
+        // Dentro del bloque los cambios traen el xid de la (sub)transacción antes del cuerpo
+        let mut insert = BytesMut::new();
+        insert.put_u32(701);
+        insert.put_u32(16384);
+        insert.put_u8(b'N');
+        insert.put_u16(1);
+        insert.put_u8(b't');
+        insert.put_u32(2);
+        insert.put_slice(b"42");
+        let msg = parser.parse(b'I', insert.freeze()).unwrap();
+        let Some(CdcMessage::Insert { relation_id: 16384, tuple }) = msg else {
+            panic!("expected insert, got {:?}", msg);
+        };
+        assert!(matches!(&tuple.cols[0], TupleData::Text(b) if &b[..] == b"42"));
+        assert_eq!(parser.stream_context(), Some(StreamContext { top_xid: 700, xid: 701 }));
+
+        // E: fin del bloque, los cambios siguientes no llevan prefijo
+        assert!(matches!(parser.parse(b'E', Bytes::new()).unwrap(), Some(CdcMessage::StreamStop)));
+        assert_eq!(parser.stream_context(), None);
+        let mut insert = BytesMut::new();
+        insert.put_u32(16384);
+        insert.put_u8(b'N');
+        insert.put_u16(0);
+        assert!(matches!(parser.parse(b'I', insert.freeze()).unwrap(), Some(CdcMessage::Insert { relation_id: 16384, .. })));
+
+        // A: xid + subxid (abort_lsn y timestamp de streaming 'parallel' se ignoran)
+        let mut abort = BytesMut::new();
+        abort.put_u32(700);
+        abort.put_u32(701);
+        abort.put_u64(0x500);
+        abort.put_u64(0);
+        assert!(matches!(
+            parser.parse(b'A', abort.freeze()).unwrap(),
+            Some(CdcMessage::StreamAbort { xid: 700, subxid: 701 })
+        ));
+
+        // c: xid, flags, commit_lsn, end_lsn, timestamp
+        let mut commit = BytesMut::new();
+        commit.put_u32(700);
+        commit.put_u8(0);
+        commit.put_u64(0x1000);
+        commit.put_u64(0x1010);
+        commit.put_u64(99);
+        assert!(matches!(
+            parser.parse(b'c', commit.freeze()).unwrap(),
+            Some(CdcMessage::StreamCommit { xid: 700, commit_lsn: 0x1000, end_lsn: 0x1010, timestamp: 99, .. })
+        ));
+
+        // Mensajes truncados: error en lugar de panic
+        assert!(parser.parse(b'c', Bytes::from_static(&[0, 0, 2, 188])).is_err());
+        assert!(parser.parse(b'A', Bytes::from_static(&[0, 0, 2, 188])).is_err());
+    }
+
+    #[test]
+    fn test_two_phase_messages() {
+        use bytes::{BufMut, BytesMut};
+
+        let mut parser = PgOutputParser::new();
+
+        // b: prepare_lsn, end_lsn, timestamp, xid, gid
+        let mut begin = BytesMut::new();
+        begin.put_u64(0x2000);
+        begin.put_u64(0x2100);
+        begin.put_u64(7);
+        begin.put_u32(800);
+        begin.put_slice(b"g1\0");
+        let msg = parser.parse(b'b', begin.freeze()).unwrap();
+        let Some(CdcMessage::BeginPrepare { prepare_lsn: 0x2000, end_lsn: 0x2100, timestamp: 7, xid: 800, gid }) = msg else {
+            panic!("expected begin prepare, got {:?}", msg);
+        };
+        assert_eq!(gid, "g1");
+
+        // P y p comparten formato: flags, prepare_lsn, end_lsn, timestamp, xid, gid.
+        // 'p' cierra una transacción en streaming
+        let prepare = |gid: &[u8]| {
+            let mut body = BytesMut::new();
+            body.put_u8(0);
+            body.put_u64(0x2000);
+            body.put_u64(0x2100);
+            body.put_u64(7);
+            body.put_u32(800);
+            body.put_slice(gid);
+            body.freeze()
+        };
+        let msg = parser.parse(b'P', prepare(b"g1\0")).unwrap();
+        assert!(matches!(msg, Some(CdcMessage::Prepare { prepare_lsn: 0x2000, xid: 800, streamed: false, ref gid, .. }) if gid == "g1"));
+        let msg = parser.parse(b'p', prepare(b"g2\0")).unwrap();
+        assert!(matches!(msg, Some(CdcMessage::Prepare { end_lsn: 0x2100, streamed: true, ref gid, .. }) if gid == "g2"));
+
+        // K: flags, commit_lsn, end_lsn, timestamp, xid, gid
+        let mut commit = BytesMut::new();
+        commit.put_u8(0);
+        commit.put_u64(0x3000);
+        commit.put_u64(0x3100);
+        commit.put_u64(8);
+        commit.put_u32(800);
+        commit.put_slice(b"g1\0");
+        let msg = parser.parse(b'K', commit.freeze()).unwrap();
+        assert!(matches!(
+            msg,
+            Some(CdcMessage::CommitPrepared { commit_lsn: 0x3000, end_lsn: 0x3100, timestamp: 8, xid: 800, ref gid, .. }) if gid == "g1"
+        ));
+
+        // r: flags, prepare_end_lsn, rollback_end_lsn, prepare_ts, rollback_ts, xid, gid
+        let mut rollback = BytesMut::new();
+        rollback.put_u8(0);
+        rollback.put_u64(0x2100);
+        rollback.put_u64(0x4100);
+        rollback.put_u64(7);
+        rollback.put_u64(9);
+        rollback.put_u32(801);
+        rollback.put_slice(b"g2\0");
+        let msg = parser.parse(b'r', rollback.freeze()).unwrap();
+        assert!(matches!(
+            msg,
+            Some(CdcMessage::RollbackPrepared { prepare_end_lsn: 0x2100, rollback_end_lsn: 0x4100, xid: 801, ref gid, .. }) if gid == "g2"
+        ));
+
+        // Mensajes truncados y gid sin terminador
+        assert!(parser.parse(b'b', Bytes::from_static(&[0; 10])).is_err());
+        assert!(parser.parse(b'r', Bytes::from_static(&[0; 36])).is_err());
+        assert!(parser.parse(b'K', prepare(b"no-terminator")).is_err());
+    }
+}
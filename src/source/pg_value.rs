@@ -0,0 +1,428 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Decodificación tipada de valores de PostgreSQL en formato texto (el de
+//! `pgoutput` y `COPY`), por OID, al JSON que espera StarRocks.
+
+use sonic_rs::{json, Object as Map, Value};
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Valor de PostgreSQL decodificado
+#[derive(Debug, Clone, PartialEq)]
+pub enum PgValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    /// NUMERIC y MONEY como texto decimal, para no perder precisión
+    Numeric(String),
+    /// Texto y tipos sin representación JSON propia (fechas, interval, inet, bit, ...)
+    Text(String),
+    /// bytea decodificado (se envía como hex, ver `to_binary` en el sink)
+    Bytes(Vec<u8>),
+    /// json/jsonb ya parseado (se envía como JSON anidado)
+    Json(Value),
+    Array(Vec<PgValue>),
+    Range(Range),
+}
+
+/// Rango (`int4range`, `tstzrange`, ...). Un bound None es infinito.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Range {
+    Empty,
+    Bounds {
+        lower: Option<Box<PgValue>>,
+        upper: Option<Box<PgValue>>,
+        lower_inc: bool,
+        upper_inc: bool,
+    },
+}
+
+impl PgValue {
+    /// Decodifica la representación de texto de un valor según el OID de su tipo
+    pub fn from_text(type_id: u32, text: &str) -> Self {
+        if let Some(elem) = array_element_type(type_id) {
+            return parse_array(text, elem).unwrap_or_else(|| Self::Text(text.to_string()));
+        }
+        if let Some(elem) = range_element_type(type_id) {
+            return parse_range(text, elem).unwrap_or_else(|| Self::Text(text.to_string()));
+        }
+
+        match type_id {
+            // bool
+            16 => Self::Bool(matches!(text, "t" | "true" | "1")),
+            // int2, int4, int8, oid
+            21 | 23 | 20 | 26 => text
+                .parse::<i64>()
+                .map(Self::Int)
+                .unwrap_or_else(|_| Self::Text(text.to_string())),
+            // float4, float8 (NaN/Infinity no existen en JSON: quedan como texto)
+            700 | 701 => match text.parse::<f64>() {
+                Ok(f) if f.is_finite() => Self::Float(f),
+                _ => Self::Text(text.to_string()),
+            },
+            // numeric
+            1700 => Self::Numeric(text.to_string()),
+            // money: "$1,234.56" / "-1.234,56 €" según lc_monetary (texto si es ambiguo)
+            790 => parse_money(text)
+                .map(Self::Numeric)
+                .unwrap_or_else(|| Self::Text(text.to_string())),
+            // bytea: `\x0102...` (o formato escape con bytea_output = escape)
+            17 => decode_bytea(text)
+                .map(Self::Bytes)
+                .unwrap_or_else(|| Self::Text(text.to_string())),
+            // json, jsonb
+            114 | 3802 => sonic_rs::from_str::<Value>(text)
+                .map(Self::Json)
+                .unwrap_or_else(|_| Self::Text(text.to_string())),
+            // text, varchar, bpchar, name, char, uuid, date/time, interval, inet, cidr,
+            // macaddr, bit, varbit, xml y tipos desconocidos
+            _ => Self::Text(text.to_string()),
+        }
+    }
+
+    pub fn into_json(self) -> Value {
+        match self {
+            Self::Null => json!(null),
+            Self::Bool(b) => json!(b),
+            Self::Int(n) => json!(n),
+            Self::Float(f) => json!(f),
+            Self::Numeric(s) | Self::Text(s) => json!(s),
+            Self::Bytes(b) => json!(hex::encode(b)),
+            Self::Json(v) => v,
+            Self::Array(items) => Value::from(items.into_iter().map(Self::into_json).collect::<Vec<Value>>()),
+            Self::Range(Range::Empty) => json!({"empty": true}),
+            Self::Range(Range::Bounds { lower, upper, lower_inc, upper_inc }) => {
+                let bound = |b: Option<Box<PgValue>>| b.map_or_else(|| json!(null), |v| v.into_json());
+                let mut obj = Map::with_capacity(4);
+                obj.insert("lower", bound(lower));
+                obj.insert("upper", bound(upper));
+                obj.insert("lower_inc", json!(lower_inc));
+                obj.insert("upper_inc", json!(upper_inc));
+                Value::from(obj)
+            }
+        }
+    }
+}
+
+/// OID del elemento de los tipos array built-in
+fn array_element_type(type_id: u32) -> Option<u32> {
+    Some(match type_id {
+        1000 => 16,   // bool[]
+        1001 => 17,   // bytea[]
+        1002 => 18,   // char[]
+        1003 => 19,   // name[]
+        1005 => 21,   // int2[]
+        1007 => 23,   // int4[]
+        1016 => 20,   // int8[]
+        1028 => 26,   // oid[]
+        1009 => 25,   // text[]
+        1014 => 1042, // bpchar[]
+        1015 => 1043, // varchar[]
+        1021 => 700,  // float4[]
+        1022 => 701,  // float8[]
+        1231 => 1700, // numeric[]
+        791 => 790,   // money[]
+        199 => 114,   // json[]
+        3807 => 3802, // jsonb[]
+        2951 => 2950, // uuid[]
+        1182 => 1082, // date[]
+        1183 => 1083, // time[]
+        1270 => 1266, // timetz[]
+        1115 => 1114, // timestamp[]
+        1185 => 1184, // timestamptz[]
+        1187 => 1186, // interval[]
+        1041 => 869,  // inet[]
+        651 => 650,   // cidr[]
+        1040 => 829,  // macaddr[]
+        1561 => 1560, // bit[]
+        1563 => 1562, // varbit[]
+        143 => 142,   // xml[]
+        _ => return None,
+    })
+}
+
+/// OID del elemento de los tipos range built-in
+fn range_element_type(type_id: u32) -> Option<u32> {
+    Some(match type_id {
+        3904 => 23,   // int4range
+        3926 => 20,   // int8range
+        3906 => 1700, // numrange
+        3908 => 1114, // tsrange
+        3910 => 1184, // tstzrange
+        3912 => 1082, // daterange
+        _ => return None,
+    })
+}
+
+/// Array literal: `{1,2,NULL}`, `{"a b","c\"d"}`, `{{1,2},{3,4}}`, `[0:1]={1,2}`
+fn parse_array(text: &str, elem: u32) -> Option<PgValue> {
+    // Decoración de dimensiones cuando el límite inferior no es 1
+    let text = match (text.starts_with('['), text.find('=')) {
+        (true, Some(idx)) => &text[idx + 1..],
+        _ => text,
+    };
+    let mut chars = text.chars().peekable();
+    let array = parse_array_level(&mut chars, elem)?;
+    chars.next().is_none().then_some(array)
+}
+
+fn parse_array_level(chars: &mut Peekable<Chars>, elem: u32) -> Option<PgValue> {
+    if chars.next()? != '{' {
+        return None;
+    }
+    let mut items = Vec::new();
+    if chars.peek() == Some(&'}') {
+        chars.next();
+        return Some(PgValue::Array(items));
+    }
+
+    loop {
+        let item = match chars.peek()? {
+            '{' => parse_array_level(chars, elem)?,
+            '"' => PgValue::from_text(elem, &read_quoted(chars)?),
+            _ => {
+                let mut raw = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c == ',' || c == '}' {
+                        break;
+                    }
+                    raw.push(c);
+                    chars.next();
+                }
+                if raw.eq_ignore_ascii_case("NULL") {
+                    PgValue::Null
+                } else {
+                    PgValue::from_text(elem, &raw)
+                }
+            }
+        };
+        items.push(item);
+
+        match chars.next()? {
+            ',' => continue,
+            '}' => return Some(PgValue::Array(items)),
+            _ => return None,
+        }
+    }
+}
+
+/// Valor entre comillas dobles con escapes `\`
+fn read_quoted(chars: &mut Peekable<Chars>) -> Option<String> {
+    if chars.next()? != '"' {
+        return None;
+    }
+    let mut out = String::new();
+    loop {
+        match chars.next()? {
+            '\\' => out.push(chars.next()?),
+            '"' => return Some(out),
+            c => out.push(c),
+        }
+    }
+}
+
+/// Range literal: `empty`, `[1,10)`, `(,5]`, `["2025-01-01 00:00:00","2025-02-01 00:00:00")`
+fn parse_range(text: &str, elem: u32) -> Option<PgValue> {
+    if text == "empty" {
+        return Some(PgValue::Range(Range::Empty));
+    }
+    let lower_inc = match text.chars().next()? {
+        '[' => true,
+        '(' => false,
+        _ => return None,
+    };
+    let upper_inc = match text.chars().last()? {
+        ']' => true,
+        ')' => false,
+        _ => return None,
+    };
+
+    let mut chars = text[1..text.len() - 1].chars().peekable();
+    let lower = read_range_bound(&mut chars, elem)?;
+    if chars.next()? != ',' {
+        return None;
+    }
+    let upper = read_range_bound(&mut chars, elem)?;
+
+    Some(PgValue::Range(Range::Bounds {
+        lower: lower.map(Box::new),
+        upper: upper.map(Box::new),
+        lower_inc,
+        upper_inc,
+    }))
+}
+
+/// Bound de un range; Some(None) = infinito (vacío)
+fn read_range_bound(chars: &mut Peekable<Chars>, elem: u32) -> Option<Option<PgValue>> {
+    if chars.peek() == Some(&'"') {
+        return Some(Some(PgValue::from_text(elem, &read_quoted(chars)?)));
+    }
+    let mut raw = String::new();
+    while let Some(&c) = chars.peek() {
+        if c == ',' {
+            break;
+        }
+        raw.push(c);
+        chars.next();
+    }
+    Some((!raw.is_empty()).then(|| PgValue::from_text(elem, &raw)))
+}
+
+/// Texto de un bytea: formato hex (`\xdeadbeef`, el default) o escape (`abc\000\\`)
+pub fn decode_bytea(text: &str) -> Option<Vec<u8>> {
+    if let Some(digits) = text.strip_prefix("\\x") {
+        return hex::decode(digits).ok();
+    }
+
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' {
+            out.push(bytes[i]);
+            i += 1;
+        } else if bytes.get(i + 1) == Some(&b'\\') {
+            out.push(b'\\');
+            i += 2;
+        } else {
+            // Octal de 3 dígitos: \ooo
+            let octal = std::str::from_utf8(bytes.get(i + 1..i + 4)?).ok()?;
+            out.push(u8::from_str_radix(octal, 8).ok()?);
+            i += 4;
+        }
+    }
+    Some(out)
+}
+
+/// `$1,234.56`, `-1.234,56 €` o `($1,234.56)` → `1234.56` / `-1234.56`.
+///
+/// El formato depende de `lc_monetary` del servidor: el separador decimal es el
+/// último `.` o `,` seguido de 1 o 2 dígitos y el otro carácter agrupa de a 3.
+/// Con 3 dígitos después del último separador (`1,234`: ¿miles o dinar con 3
+/// decimales?) o grupos irregulares el valor es ambiguo y se retorna None.
+fn parse_money(text: &str) -> Option<String> {
+    let negative = text.contains('-') || text.starts_with('(');
+    // Símbolos de moneda, signos y espacios / apóstrofes de agrupación se descartan
+    let body: String = text.chars().filter(|c| c.is_ascii_digit() || matches!(c, '.' | ',')).collect();
+    let body = body.trim_matches(['.', ',']);
+    if body.is_empty() {
+        return None;
+    }
+
+    let (int, frac, group) = match body.rfind(['.', ',']) {
+        Some(idx) if (1..=2).contains(&(body.len() - idx - 1)) => {
+            let group = if &body[idx..=idx] == "." { ',' } else { '.' };
+            (&body[..idx], &body[idx + 1..], group)
+        }
+        Some(_) => return None,
+        None => (body, "", ','),
+    };
+
+    let mut groups = int.split(group);
+    let first = groups.next()?;
+    let valid = !first.is_empty()
+        && first.chars().all(|c| c.is_ascii_digit())
+        && (first.len() <= 3 || !int.contains(group))
+        && groups.all(|g| g.len() == 3 && g.chars().all(|c| c.is_ascii_digit()));
+    if !valid {
+        return None;
+    }
+
+    let mut out = String::with_capacity(body.len() + 1);
+    if negative {
+        out.push('-');
+    }
+    out.extend(int.chars().filter(char::is_ascii_digit));
+    if !frac.is_empty() {
+        out.push('.');
+        out.push_str(frac);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_json(type_id: u32, text: &str) -> Value {
+        PgValue::from_text(type_id, text).into_json()
+    }
+
+    #[test]
+    fn test_scalars() {
+        assert_eq!(to_json(16, "t"), json!(true));
+        assert_eq!(to_json(16, "f"), json!(false));
+        assert_eq!(to_json(21, "-7"), json!(-7));
+        assert_eq!(to_json(20, "9223372036854775807"), json!(9223372036854775807i64));
+        assert_eq!(to_json(26, "16384"), json!(16384));
+        assert_eq!(to_json(701, "1.5"), json!(1.5));
+        assert_eq!(to_json(701, "NaN"), json!("NaN"));
+        assert_eq!(to_json(700, "-Infinity"), json!("-Infinity"));
+        assert_eq!(to_json(1700, "12345.678900"), json!("12345.678900"));
+        assert_eq!(to_json(790, "-$1,234.56"), json!("-1234.56"));
+        assert_eq!(to_json(790, "($5.00)"), json!("-5.00"));
+        assert_eq!(to_json(790, "-1.234,56 €"), json!("-1234.56"));
+        assert_eq!(to_json(790, "Fr. 1'234.5"), json!("1234.5"));
+        assert_eq!(to_json(790, "￥1235"), json!("1235"));
+        // ¿Miles o 3 decimales? Se envía el texto original
+        assert_eq!(to_json(790, "1,234 KD"), json!("1,234 KD"));
+        assert_eq!(to_json(790, "$1,23,456.00"), json!("$1,23,456.00"));
+    }
+
+    #[test]
+    fn test_text_like_types() {
+        assert_eq!(to_json(2950, "a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11"), json!("a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11"));
+        assert_eq!(to_json(1082, "2025-01-31"), json!("2025-01-31"));
+        assert_eq!(to_json(1083, "10:00:00.5"), json!("10:00:00.5"));
+        assert_eq!(to_json(1186, "1 day 02:00:00"), json!("1 day 02:00:00"));
+        assert_eq!(to_json(869, "192.168.0.1/24"), json!("192.168.0.1/24"));
+        assert_eq!(to_json(650, "10.0.0.0/8"), json!("10.0.0.0/8"));
+        assert_eq!(to_json(829, "08:00:2b:01:02:03"), json!("08:00:2b:01:02:03"));
+        assert_eq!(to_json(1560, "1010"), json!("1010"));
+        assert_eq!(to_json(1562, "101"), json!("101"));
+        assert_eq!(to_json(17, "\\xDEADbeef"), json!("deadbeef"));
+        assert_eq!(to_json(17, "a\\000\\\\b"), json!("61005c62"));
+        assert_eq!(to_json(1001, r#"{"\\x0102",NULL}"#), json!(["0102", null]));
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(to_json(3802, r#"{"a": [1, 2], "b": null}"#), json!({"a": [1, 2], "b": null}));
+        assert_eq!(to_json(114, "[true]"), json!([true]));
+        // JSON inválido se conserva como texto
+        assert_eq!(to_json(114, "{oops"), json!("{oops"));
+    }
+
+    #[test]
+    fn test_arrays() {
+        assert_eq!(to_json(1007, "{1,2}"), json!([1, 2]));
+        assert_eq!(to_json(1007, "{}"), json!([]));
+        assert_eq!(to_json(1007, "{{1,2},{3,NULL}}"), json!([[1, 2], [3, null]]));
+        assert_eq!(to_json(1007, "[0:1]={5,6}"), json!([5, 6]));
+        assert_eq!(to_json(1009, r#"{plain,"with space","quo\"te",NULL,"NULL"}"#),
+            json!(["plain", "with space", "quo\"te", null, "NULL"]));
+        assert_eq!(to_json(1000, "{t,f}"), json!([true, false]));
+        assert_eq!(to_json(3807, r#"{"{\"a\": 1}"}"#), json!([{"a": 1}]));
+        // Literal mal formado: texto
+        assert_eq!(to_json(1007, "{1,2"), json!("{1,2"));
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(
+            to_json(3904, "[1,10)"),
+            json!({"lower": 1, "upper": 10, "lower_inc": true, "upper_inc": false})
+        );
+        assert_eq!(
+            to_json(3906, "(,5.5]"),
+            json!({"lower": null, "upper": "5.5", "lower_inc": false, "upper_inc": true})
+        );
+        assert_eq!(
+            to_json(3908, r#"["2025-01-01 00:00:00","2025-02-01 00:00:00")"#),
+            json!({"lower": "2025-01-01 00:00:00", "upper": "2025-02-01 00:00:00", "lower_inc": true, "upper_inc": false})
+        );
+        assert_eq!(to_json(3912, "empty"), json!({"empty": true}));
+    }
+}
@@ -1,8 +1,11 @@
 use anyhow::{Context, Result};
-use tokio_postgres::{Client, NoTls, Config, CopyBothDuplex};
+use tokio_postgres::{Client, NoTls, Config, CopyBothDuplex, SimpleQueryMessage};
 use bytes::{Bytes, BytesMut, BufMut};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::source::binary::BINARY_TYPES;
+use crate::source::origin::OriginFilter;
+
 /// PostgreSQL epoch: 2000-01-01 00:00:00 UTC
 /// Difference from Unix epoch in microseconds
 const PG_EPOCH_OFFSET_USEC: i64 = 946_684_800_000_000;
@@ -36,10 +39,50 @@ pub fn build_standby_status_update(lsn: u64) -> Bytes {
     buf.freeze()
 }
 
+/// Convierte un LSN a formato PostgreSQL (X/Y)
+pub fn format_lsn(lsn: u64) -> String {
+    format!("{:X}/{:X}", lsn >> 32, lsn & 0xFFFFFFFF)
+}
+
+/// Parsea un LSN en formato PostgreSQL (X/Y)
+pub fn parse_lsn(lsn: &str) -> Result<u64> {
+    let (hi, lo) = lsn
+        .split_once('/')
+        .ok_or_else(|| anyhow::anyhow!("Invalid LSN format: {}", lsn))?;
+    let hi = u64::from_str_radix(hi, 16).with_context(|| format!("Invalid LSN: {}", lsn))?;
+    let lo = u64::from_str_radix(lo, 16).with_context(|| format!("Invalid LSN: {}", lsn))?;
+    Ok((hi << 32) | lo)
+}
+
+/// Versión del protocolo `pgoutput` soportada por el servidor:
+/// 1 (PG10+), 2 (PG14+, streaming), 3 (PG15+, two-phase), 4 (PG16+, streaming paralelo)
+pub fn protocol_version(server_version: u32) -> u32 {
+    match server_version {
+        v if v >= 160000 => 4,
+        v if v >= 150000 => 3,
+        v if v >= 140000 => 2,
+        _ => 1,
+    }
+}
+
+/// Slot creado con snapshot exportado (para la carga inicial)
+#[derive(Debug, Clone)]
+pub struct SlotSnapshot {
+    /// LSN desde el cual el stream es consistente con el snapshot
+    pub consistent_point: u64,
+    /// Nombre para `SET TRANSACTION SNAPSHOT`
+    pub snapshot_name: String,
+}
+
 pub struct PostgresSource {
     client: Client,
     slot_name: String,
     publication_name: String,
+    two_phase: bool,
+    origin_filter: OriginFilter,
+    binary_tuples: bool,
+    /// El último START_REPLICATION pidió tuplas binarias
+    binary_active: bool,
 }
 
 impl PostgresSource {
@@ -47,6 +90,7 @@ impl PostgresSource {
         pg_config: &str,
         slot_name: String,
         publication_name: String,
+        two_phase: bool,
     ) -> Result<Self> {
         // Limpiar URL de parámetros de replicación si existen
         let clean_url = pg_config
@@ -54,28 +98,7 @@ impl PostgresSource {
             .replace("&replication=database", "")
             .replace("replication=database&", "");
         
-        // Paso 1: Crear slot de replicación en conexión normal (sin modo replicación)
-        {
-            let (slot_client, slot_connection) = tokio_postgres::connect(&clean_url, NoTls).await?;
-            let slot_handle = tokio::spawn(async move {
-                if let Err(e) = slot_connection.await {
-                    eprintln!("Slot connection error: {}", e);
-                }
-            });
-
-            // Intentar crear el slot (ignorar si ya existe)
-            let _ = slot_client
-                .simple_query(&format!(
-                    "SELECT pg_create_logical_replication_slot('{}', 'pgoutput')",
-                    slot_name
-                ))
-                .await; // Ignorar errores (slot puede ya existir)
-            
-            drop(slot_client);
-            let _ = slot_handle.await;
-        }
-        
-        // Paso 2: Crear conexión de replicación
+        // El slot lo crea el setup (o el snapshot inicial, con snapshot exportado)
         let mut config: Config = clean_url.parse()?;
         
         // ✅ El fork de Materialize SÍ tiene este método
@@ -93,27 +116,43 @@ impl PostgresSource {
             client,
             slot_name,
             publication_name,
+            two_phase,
+            origin_filter: OriginFilter::Any,
+            binary_tuples: false,
+            binary_active: false,
         })
     }
 
-    pub async fn start_replication(&self) -> Result<CopyBothDuplex<Bytes>> {
+    /// Configura el filtro de orígenes de replicación (`origin 'none'` en PG16+)
+    pub fn with_origin_filter(mut self, origin_filter: OriginFilter) -> Self {
+        self.origin_filter = origin_filter;
+        self
+    }
+
+    /// Pide columnas en formato binario (PG14+) si todos los tipos publicados lo soportan
+    pub fn with_binary_tuples(mut self, binary_tuples: bool) -> Self {
+        self.binary_tuples = binary_tuples;
+        self
+    }
+
+    pub async fn start_replication(&mut self) -> Result<CopyBothDuplex<Bytes>> {
         self.start_replication_from(0).await
     }
 
-    pub async fn start_replication_from(&self, start_lsn: u64) -> Result<CopyBothDuplex<Bytes>> {
+    pub async fn start_replication_from(&mut self, start_lsn: u64) -> Result<CopyBothDuplex<Bytes>> {
         // Convertir LSN a formato PostgreSQL (X/Y)
-        let lsn_str = if start_lsn == 0 {
-            "0/0".to_string()
-        } else {
-            format!("{:X}/{:X}", start_lsn >> 32, start_lsn & 0xFFFFFFFF)
-        };
+        let lsn_str = format_lsn(start_lsn);
         
+        let server_version = self.server_version_num().await?;
+        let binary = self.binary_tuples && server_version >= 140000 && self.binary_supported().await?;
+        self.binary_active = binary;
         let query = format!(
-            "START_REPLICATION SLOT {} LOGICAL {} (proto_version '1', publication_names '{}')",
-            self.slot_name, lsn_str, self.publication_name
+            "START_REPLICATION SLOT {} LOGICAL {} ({})",
+            self.slot_name, lsn_str, self.replication_options(server_version, binary)
         );
 
-        println!("Starting replication from LSN: {}", lsn_str);
+        println!("Starting replication from LSN: {} (proto_version {}, {} tuples)",
+            lsn_str, protocol_version(server_version), if binary { "binary" } else { "text" });
 
         let stream = self
             .client
@@ -124,6 +163,152 @@ impl PostgresSource {
         Ok(stream)
     }
 
+    /// Indica si el stream iniciado recibe las columnas en formato binario
+    pub fn binary_active(&self) -> bool {
+        self.binary_active
+    }
+
+    /// Versión del servidor (`server_version_num`, p.ej. 160002)
+    pub async fn server_version_num(&self) -> Result<u32> {
+        let messages = self.client.simple_query("SHOW server_version_num").await?;
+        messages
+            .iter()
+            .find_map(|m| match m {
+                SimpleQueryMessage::Row(row) => row.get(0).and_then(|v| v.parse().ok()),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow::anyhow!("Could not read server_version_num"))
+    }
+
+    /// Indica si todas las columnas de la publication tienen decodificación binaria.
+    /// Con `binary 'true'` PostgreSQL envía en binario cualquier tipo con `typsend`,
+    /// así que un solo tipo no soportado obliga a quedarse en texto.
+    async fn binary_supported(&self) -> Result<bool> {
+        let query = format!(
+            "SELECT DISTINCT a.atttypid, format_type(a.atttypid, NULL)
+             FROM pg_publication_tables pt
+             JOIN pg_namespace n ON n.nspname = pt.schemaname
+             JOIN pg_class c ON c.relnamespace = n.oid AND c.relname = pt.tablename
+             JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped
+             WHERE pt.pubname = '{}'",
+            self.publication_name.replace('\'', "''")
+        );
+        let messages = self.client.simple_query(&query).await?;
+
+        let unsupported: Vec<&str> = messages
+            .iter()
+            .filter_map(|m| match m {
+                SimpleQueryMessage::Row(row) => Some(row),
+                _ => None,
+            })
+            .filter(|row| {
+                row.get(0)
+                    .and_then(|oid| oid.parse::<u32>().ok())
+                    .is_none_or(|oid| !BINARY_TYPES.contains(&oid))
+            })
+            .filter_map(|row| row.get(1))
+            .collect();
+
+        if !unsupported.is_empty() {
+            println!("ℹ️  Binary tuples disabled, types without binary decoding: {}", unsupported.join(", "));
+        }
+        Ok(unsupported.is_empty())
+    }
+
+    /// Opciones de pgoutput para START_REPLICATION según la versión del servidor
+    fn replication_options(&self, server_version: u32, binary: bool) -> String {
+        let proto_version = protocol_version(server_version);
+        let mut options = format!(
+            "proto_version '{}', publication_names '{}'",
+            proto_version, self.publication_name
+        );
+        // Transacciones grandes se envían en bloques antes del commit
+        if proto_version >= 2 {
+            options.push_str(", streaming 'on'");
+        }
+        // Columnas en binario: evita parsear texto en el sink (PG14+)
+        if binary {
+            options.push_str(", binary 'true'");
+        }
+        // Mensajes de pg_logical_emit_message (PG14+)
+        if server_version >= 140000 {
+            options.push_str(", messages 'true'");
+        }
+        // Transacciones preparadas se decodifican en el PREPARE (habilita two_phase en el slot)
+        if self.two_phase {
+            if proto_version >= 3 {
+                options.push_str(", two_phase 'on'");
+            } else {
+                eprintln!("⚠️  TWO_PHASE requires PostgreSQL 15+, prepared transactions will be sent at COMMIT PREPARED");
+            }
+        }
+        // Con PG < 16 el filtro de orígenes se aplica solo en el pipeline
+        if let Some(origin) = self.origin_filter.server_option() {
+            if server_version >= 160000 {
+                options.push_str(&format!(", origin '{}'", origin));
+            } else {
+                eprintln!("⚠️  ORIGIN_FILTER=none requires PostgreSQL 16+ server-side, filtering in the pipeline instead");
+            }
+        }
+        options
+    }
+
+    /// Verifica si el slot de replicación ya existe
+    pub async fn slot_exists(&self) -> Result<bool> {
+        let query = format!(
+            "SELECT 1 FROM pg_replication_slots WHERE slot_name = '{}'",
+            self.slot_name.replace('\'', "''")
+        );
+        let messages = self.client.simple_query(&query).await?;
+        Ok(messages.iter().any(|m| matches!(m, SimpleQueryMessage::Row(_))))
+    }
+
+    /// Crea el slot exportando un snapshot consistente con su punto de inicio.
+    ///
+    /// El snapshot solo es válido mientras esta conexión no ejecute otro comando,
+    /// así que la carga inicial debe completarse antes de `start_replication_from`.
+    pub async fn create_slot_with_snapshot(&self) -> Result<SlotSnapshot> {
+        let query = format!(
+            "CREATE_REPLICATION_SLOT {} LOGICAL pgoutput EXPORT_SNAPSHOT{}",
+            self.slot_name,
+            if self.two_phase { " TWO_PHASE" } else { "" }
+        );
+
+        let messages = self.client
+            .simple_query(&query)
+            .await
+            .context("Failed to create replication slot with exported snapshot")?;
+
+        let row = messages
+            .iter()
+            .find_map(|m| match m {
+                SimpleQueryMessage::Row(row) => Some(row),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow::anyhow!("CREATE_REPLICATION_SLOT returned no rows"))?;
+
+        let consistent_point = row
+            .get("consistent_point")
+            .ok_or_else(|| anyhow::anyhow!("Missing consistent_point in slot creation"))?;
+        let snapshot_name = row
+            .get("snapshot_name")
+            .ok_or_else(|| anyhow::anyhow!("Missing snapshot_name in slot creation"))?;
+
+        Ok(SlotSnapshot {
+            consistent_point: parse_lsn(consistent_point)?,
+            snapshot_name: snapshot_name.to_string(),
+        })
+    }
+
+    /// Elimina el slot de replicación
+    pub async fn drop_slot(&self) -> Result<()> {
+        self.client
+            .simple_query(&format!("DROP_REPLICATION_SLOT {}", self.slot_name))
+            .await
+            .context("Failed to drop replication slot")?;
+        Ok(())
+    }
+
     /// Valida que las tablas tengan REPLICA IDENTITY FULL
     /// 
     /// Esto es crítico para StarRocks/ClickHouse porque necesitan todas las columnas
@@ -0,0 +1,501 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+use anyhow::{Context, Result, anyhow};
+use bytes::{Buf, Bytes, BytesMut};
+use futures::StreamExt;
+use memchr::memchr;
+use std::pin::Pin;
+use tokio_postgres::{Client, CopyOutStream, NoTls};
+
+use crate::pipeline::projection::ColumnProjection;
+use crate::source::parser::{CdcMessage, Column, ToastBitmap, Tuple, TupleData};
+
+/// Tabla de señales (en la publication) usada para watermarks y snapshots ad-hoc
+pub const SIGNAL_TABLE: &str = "dbmazz_signals";
+
+/// Schema de la tabla de señales
+pub const SIGNAL_SCHEMA: &str = "public";
+
+/// Tipos de señal de los watermarks (mismo esquema que Debezium)
+pub const SIGNAL_WINDOW_OPEN: &str = "snapshot-window-open";
+pub const SIGNAL_WINDOW_CLOSE: &str = "snapshot-window-close";
+
+/// Snapshot ad-hoc desde SQL:
+/// `INSERT INTO dbmazz_signals (id, type, data) VALUES ('fix-1', 'execute-snapshot', '{"tables": ["orders"]}')`
+pub const SIGNAL_EXECUTE_SNAPSHOT: &str = "execute-snapshot";
+
+/// Schema de una tabla leído desde el catálogo para hacer snapshot.
+///
+/// El `relation_id` es el OID de `pg_class`, el mismo que usa `pgoutput`
+/// en los mensajes Relation, así que las filas del snapshot y las del WAL
+/// comparten entrada en el `SchemaCache`.
+#[derive(Debug, Clone)]
+pub struct SnapshotTable {
+    pub relation_id: u32,
+    pub namespace: String,
+    pub name: String,
+    pub replica_identity: u8,
+    pub columns: Vec<Column>,
+    pub primary_key: Vec<String>,
+}
+
+impl SnapshotTable {
+    /// Nombre calificado `schema.tabla`
+    pub fn qualified_name(&self) -> String {
+        format!("{}.{}", self.namespace, self.name)
+    }
+
+    /// Conserva solo las columnas replicadas: las excluidas ni se leen
+    pub fn project(&mut self, projection: &ColumnProjection) {
+        let (namespace, name) = (&self.namespace, &self.name);
+        self.columns.retain(|c| projection.keeps(namespace, name, &c.name));
+    }
+
+    /// Mensaje Relation equivalente al que enviaría `pgoutput`
+    pub fn relation_message(&self) -> CdcMessage {
+        CdcMessage::Relation {
+            id: self.relation_id,
+            namespace: self.namespace.clone(),
+            name: self.name.clone(),
+            replica_identity: self.replica_identity,
+            columns: self.columns.clone(),
+        }
+    }
+
+    /// SELECT de todas las columnas en el orden de `pgoutput`
+    pub fn select_sql(&self) -> String {
+        let columns: Vec<String> = self.columns.iter().map(|c| quote_ident(&c.name)).collect();
+        format!(
+            "SELECT {} FROM {}.{}",
+            columns.join(", "),
+            quote_ident(&self.namespace),
+            quote_ident(&self.name)
+        )
+    }
+
+    /// SELECT de un chunk por rango de PK (keyset pagination):
+    /// `WHERE [filter AND] (pk) > (last_key) ORDER BY pk LIMIT n`
+    pub fn chunk_sql(&self, filter: Option<&str>, last_key: Option<&[String]>, limit: usize) -> String {
+        let pk: Vec<String> = self.primary_key.iter().map(|c| quote_ident(c)).collect();
+        let pk = pk.join(", ");
+
+        let mut conditions = Vec::new();
+        if let Some(filter) = filter {
+            conditions.push(format!("({})", filter));
+        }
+        if let Some(key) = last_key {
+            let values: Vec<String> = key.iter().map(|v| quote_literal(v)).collect();
+            conditions.push(format!("({}) > ({})", pk, values.join(", ")));
+        }
+
+        let mut sql = self.select_sql();
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(&format!(" ORDER BY {} LIMIT {}", pk, limit));
+        sql
+    }
+
+    /// Índices de las columnas de la PK dentro de `columns`
+    pub fn key_indices(&self) -> Vec<usize> {
+        self.primary_key
+            .iter()
+            .filter_map(|pk| self.columns.iter().position(|c| &c.name == pk))
+            .collect()
+    }
+}
+
+/// Conexión normal (no replicación) para leer snapshots de tablas via COPY
+pub struct SnapshotReader {
+    client: Client,
+}
+
+impl SnapshotReader {
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        let clean_url = database_url
+            .replace("?replication=database", "")
+            .replace("&replication=database", "")
+            .replace("replication=database&", "");
+
+        let (client, connection) = tokio_postgres::connect(&clean_url, NoTls).await?;
+
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                eprintln!("Snapshot connection error: {}", e);
+            }
+        });
+
+        Ok(Self { client })
+    }
+
+    /// Abre una transacción REPEATABLE READ, importando el snapshot exportado
+    /// por el slot si se indica. Todas las lecturas posteriores ven el mismo
+    /// estado de la base de datos.
+    pub async fn begin(&self, snapshot_name: Option<&str>) -> Result<()> {
+        self.client
+            .batch_execute("BEGIN ISOLATION LEVEL REPEATABLE READ READ ONLY")
+            .await
+            .context("Failed to open snapshot transaction")?;
+
+        if let Some(name) = snapshot_name {
+            self.client
+                .batch_execute(&format!("SET TRANSACTION SNAPSHOT {}", quote_literal(name)))
+                .await
+                .with_context(|| format!("Failed to import snapshot '{}'", name))?;
+        }
+        Ok(())
+    }
+
+    pub async fn commit(&self) -> Result<()> {
+        self.client.batch_execute("COMMIT").await?;
+        Ok(())
+    }
+
+    /// Lee el schema de una tabla (`tabla` o `schema.tabla`) desde `pg_catalog`
+    pub async fn load_table(&self, table: &str) -> Result<SnapshotTable> {
+        let parts: Vec<&str> = table.split('.').collect();
+        let schema = if parts.len() > 1 { parts[0] } else { "public" };
+        let table_name = if parts.len() > 1 { parts[1] } else { parts[0] };
+
+        let row = self.client
+            .query_opt(
+                "SELECT c.oid, c.relreplident
+                 FROM pg_class c
+                 JOIN pg_namespace n ON c.relnamespace = n.oid
+                 WHERE n.nspname = $1 AND c.relname = $2",
+                &[&schema, &table_name],
+            )
+            .await?
+            .ok_or_else(|| anyhow!("Table '{}' not found in PostgreSQL", table))?;
+
+        let relation_id: u32 = row.get(0);
+        let replica_identity: i8 = row.get(1);
+
+        // Mismo orden y filtro de columnas que pgoutput (sin dropped ni generated)
+        let rows = self.client
+            .query(
+                "SELECT a.attname, a.atttypid, a.atttypmod,
+                        COALESCE(a.attnum = ANY(i.indkey), false) AS is_pk
+                 FROM pg_attribute a
+                 LEFT JOIN pg_index i ON i.indrelid = a.attrelid AND i.indisprimary
+                 WHERE a.attrelid = $1 AND a.attnum > 0
+                   AND NOT a.attisdropped AND a.attgenerated = ''
+                 ORDER BY a.attnum",
+                &[&relation_id],
+            )
+            .await?;
+
+        let mut columns = Vec::with_capacity(rows.len());
+        let mut primary_key = Vec::new();
+        for row in rows {
+            let name: String = row.get(0);
+            let is_pk: bool = row.get(3);
+            if is_pk {
+                primary_key.push(name.clone());
+            }
+            columns.push(Column {
+                flags: is_pk as u8,
+                name,
+                type_id: row.get(1),
+                type_mod: row.get(2),
+            });
+        }
+
+        Ok(SnapshotTable {
+            relation_id,
+            namespace: schema.to_string(),
+            name: table_name.to_string(),
+            replica_identity: replica_identity as u8,
+            columns,
+            primary_key,
+        })
+    }
+
+    /// Inserta una fila en la tabla de señales. Se escribe en autocommit para
+    /// que quede en el WAL en el orden exacto respecto a las lecturas.
+    pub async fn write_signal(&self, id: &str, kind: &str, data: &str) -> Result<()> {
+        self.client
+            .execute(
+                &format!("INSERT INTO {} (id, type, data) VALUES ($1, $2, $3)", SIGNAL_TABLE),
+                &[&id, &kind, &data],
+            )
+            .await
+            .with_context(|| format!("Failed to write signal '{}' ({})", id, kind))?;
+        Ok(())
+    }
+
+    /// Borra una señal ya procesada (los watermarks no se acumulan en la tabla)
+    pub async fn delete_signal(&self, id: &str) -> Result<()> {
+        self.client
+            .execute(&format!("DELETE FROM {} WHERE id = $1", SIGNAL_TABLE), &[&id])
+            .await
+            .with_context(|| format!("Failed to delete signal '{}'", id))?;
+        Ok(())
+    }
+
+    /// Ejecuta `COPY (<select>) TO STDOUT` y retorna un lector de tuplas
+    pub async fn copy_query(&self, select_sql: &str) -> Result<CopyTupleReader> {
+        let stream = self.client
+            .copy_out(&format!("COPY ({}) TO STDOUT", select_sql))
+            .await
+            .with_context(|| format!("COPY failed for query: {}", select_sql))?;
+
+        Ok(CopyTupleReader {
+            stream: Box::pin(stream),
+            buf: BytesMut::new(),
+            done: false,
+        })
+    }
+}
+
+/// Decodifica el stream de COPY en formato texto a `Tuple`s.
+///
+/// El formato texto de COPY usa la misma representación que `pgoutput`
+/// en modo texto, por lo que el sink convierte los valores sin cambios.
+pub struct CopyTupleReader {
+    stream: Pin<Box<CopyOutStream>>,
+    buf: BytesMut,
+    done: bool,
+}
+
+impl CopyTupleReader {
+    /// Retorna hasta `max` tuplas; un Vec vacío indica fin del COPY
+    pub async fn next_tuples(&mut self, max: usize) -> Result<Vec<Tuple>> {
+        let mut tuples = Vec::with_capacity(max.min(4096));
+
+        while tuples.len() < max {
+            // Consumir las líneas completas que ya están en el buffer
+            if let Some(pos) = memchr(b'\n', &self.buf) {
+                let line = self.buf.split_to(pos).freeze();
+                self.buf.advance(1); // skip '\n'
+                tuples.push(decode_copy_line(line));
+                continue;
+            }
+
+            if self.done {
+                if !self.buf.is_empty() {
+                    let line = self.buf.split().freeze();
+                    tuples.push(decode_copy_line(line));
+                }
+                break;
+            }
+
+            match self.stream.next().await {
+                Some(chunk) => self.buf.extend_from_slice(&chunk?),
+                None => self.done = true,
+            }
+        }
+
+        Ok(tuples)
+    }
+}
+
+/// Decodifica una línea de COPY texto (campos separados por TAB, `\N` = NULL)
+fn decode_copy_line(line: Bytes) -> Tuple {
+    let mut cols = Vec::new();
+    let mut rest = line;
+
+    loop {
+        let field = match memchr(b'\t', &rest) {
+            Some(pos) => {
+                let field = rest.split_to(pos);
+                rest.advance(1);
+                Some(field)
+            }
+            None => None,
+        };
+
+        let (field, last) = match field {
+            Some(f) => (f, false),
+            None => (std::mem::take(&mut rest), true),
+        };
+
+        if field.as_ref() == b"\\N" {
+            cols.push(TupleData::Null);
+        } else {
+            cols.push(TupleData::Text(unescape_copy_field(field)));
+        }
+
+        if last {
+            break;
+        }
+    }
+
+    Tuple { cols, toast_bitmap: ToastBitmap::default() }
+}
+
+/// Resuelve los escapes con backslash del formato texto de COPY.
+/// Zero-copy cuando el campo no contiene escapes (caso más común).
+fn unescape_copy_field(field: Bytes) -> Bytes {
+    if memchr(b'\\', &field).is_none() {
+        return field;
+    }
+
+    let mut out = Vec::with_capacity(field.len());
+    let mut i = 0;
+    while i < field.len() {
+        let b = field[i];
+        if b != b'\\' || i + 1 >= field.len() {
+            out.push(b);
+            i += 1;
+            continue;
+        }
+
+        let next = field[i + 1];
+        i += 2;
+        match next {
+            b'b' => out.push(0x08),
+            b'f' => out.push(0x0C),
+            b'n' => out.push(b'\n'),
+            b'r' => out.push(b'\r'),
+            b't' => out.push(b'\t'),
+            b'v' => out.push(0x0B),
+            b'0'..=b'7' => {
+                // Octal: hasta 3 dígitos
+                let mut value = (next - b'0') as u32;
+                let mut digits = 1;
+                while digits < 3 && i < field.len() && (b'0'..=b'7').contains(&field[i]) {
+                    value = value * 8 + (field[i] - b'0') as u32;
+                    i += 1;
+                    digits += 1;
+                }
+                out.push(value as u8);
+            }
+            b'x' if i < field.len() && field[i].is_ascii_hexdigit() => {
+                // Hex: hasta 2 dígitos
+                let mut value = 0u32;
+                let mut digits = 0;
+                while digits < 2 && i < field.len() && field[i].is_ascii_hexdigit() {
+                    value = value * 16 + (field[i] as char).to_digit(16).unwrap_or(0);
+                    i += 1;
+                    digits += 1;
+                }
+                out.push(value as u8);
+            }
+            other => out.push(other),
+        }
+    }
+
+    Bytes::from(out)
+}
+
+/// Valida el `filter` de un snapshot ad-hoc antes de usarlo en el `WHERE`.
+///
+/// El filtro es una condición SQL del usuario: se rechazan `;`, comentarios y
+/// paréntesis desbalanceados fuera de literales, que permitirían cerrar la
+/// condición y agregar otra sentencia o cláusula. Solo se aceptan literales
+/// estándar: en `E'...'` una comilla escapada con `\` no cierra el literal y
+/// en `$tag$...$tag$` no hay comillas, así que ocultarían un `;` o un `--`.
+pub fn validate_filter(filter: &str) -> Result<()> {
+    if filter.trim().is_empty() {
+        return Err(anyhow!("Snapshot filter is empty"));
+    }
+
+    let bytes = filter.as_bytes();
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            // Literales 'texto' e identificadores "citados" (comilla doble = escape)
+            quote @ (b'\'' | b'"') => {
+                i += 1;
+                loop {
+                    match bytes.get(i) {
+                        None => return Err(anyhow!("Unterminated quote in snapshot filter")),
+                        Some(&b) if b == quote && bytes.get(i + 1) == Some(&quote) => i += 2,
+                        Some(&b) if b == quote => break,
+                        Some(_) => i += 1,
+                    }
+                }
+            }
+            // E'...' al inicio de un token (no `name'...'` ni `type'...'`)
+            b'E' | b'e'
+                if bytes.get(i + 1) == Some(&b'\'')
+                    && (i == 0 || !(bytes[i - 1].is_ascii_alphanumeric() || bytes[i - 1] == b'_')) =>
+            {
+                return Err(anyhow!("Snapshot filter must not contain escape strings (E'...')"));
+            }
+            b';' => return Err(anyhow!("Snapshot filter must not contain ';'")),
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                return Err(anyhow!("Snapshot filter must not contain comments"));
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                return Err(anyhow!("Snapshot filter must not contain comments"));
+            }
+            b'$' => return Err(anyhow!("Snapshot filter must not contain dollar quotes or parameters")),
+            b'(' => depth += 1,
+            b')' => {
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or_else(|| anyhow!("Unbalanced parentheses in snapshot filter"))?;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if depth != 0 {
+        return Err(anyhow!("Unbalanced parentheses in snapshot filter"));
+    }
+    Ok(())
+}
+
+/// Cita un identificador SQL (`"nombre"`)
+pub fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+/// Cita un literal SQL (`'valor'`)
+pub fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(data: &TupleData) -> &[u8] {
+        match data {
+            TupleData::Text(b) => b,
+            other => panic!("expected text, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_copy_line_nulls_and_escapes() {
+        let tuple = decode_copy_line(Bytes::from_static(b"1\t\\N\tline\\nbreak\ttab\\there\t\\\\"));
+        assert_eq!(tuple.cols.len(), 5);
+        assert_eq!(text(&tuple.cols[0]), b"1");
+        assert!(matches!(tuple.cols[1], TupleData::Null));
+        assert_eq!(text(&tuple.cols[2]), b"line\nbreak");
+        assert_eq!(text(&tuple.cols[3]), b"tab\there");
+        assert_eq!(text(&tuple.cols[4]), b"\\");
+    }
+
+    #[test]
+    fn test_validate_filter() {
+        assert!(validate_filter("created_at > '2024-01-01' AND (status = 'a;b' OR note = 'it''s')").is_ok());
+        assert!(validate_filter("\"odd;name\" = 1").is_ok());
+        assert!(validate_filter("1=1); DROP TABLE orders").is_err());
+        assert!(validate_filter("id > 0) OR (true").is_err());
+        assert!(validate_filter("id > 0 -- comment").is_err());
+        assert!(validate_filter("id > 0 /* x */").is_err());
+        assert!(validate_filter("name = 'open").is_err());
+        // Escape strings: la comilla escapada ocultaría el `;`
+        assert!(validate_filter("note = E'\\'; DROP TABLE orders; --'").is_err());
+        assert!(validate_filter("note = e'x'").is_err());
+        assert!(validate_filter("type='E' AND name = 'e'").is_ok());
+        assert!(validate_filter("name = $$x$$").is_err());
+        assert!(validate_filter("  ").is_err());
+    }
+
+    #[test]
+    fn test_unescape_octal_and_hex() {
+        assert_eq!(&unescape_copy_field(Bytes::from_static(b"\\101\\x42"))[..], b"AB");
+        // Campo vacío = string vacío (no NULL)
+        let tuple = decode_copy_line(Bytes::from_static(b"a\t"));
+        assert_eq!(text(&tuple.cols[1]), b"");
+    }
+}
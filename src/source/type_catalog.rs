@@ -0,0 +1,342 @@
+// Copyright 2025
+// Licensed under the Elastic License v2.0
+
+//! Catálogo de tipos definidos por el usuario (domains, enums, composites y los
+//! tipos espaciales de PostGIS).
+//!
+//! Los mensajes Relation solo traen el OID del tipo de cada columna; para los
+//! tipos propios de la base el catálogo se carga de `pg_type` al iniciar y se
+//! refresca cuando llega un mensaje Type ('Y') de `pgoutput`.
+
+use anyhow::Result;
+use sonic_rs::{json, Object as Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use tokio::sync::Mutex;
+use tokio_postgres::{Client, NoTls};
+
+use crate::source::geometry::GeometryFormat;
+use crate::source::pg_value::PgValue;
+
+/// Primer OID asignable a objetos del usuario (los menores son built-in)
+const FIRST_NORMAL_OID: u32 = 16384;
+/// Niveles máximos de domains sobre domains
+const MAX_DOMAIN_DEPTH: usize = 16;
+
+/// OIDs built-in con los que se representan enums y composites
+const TEXT_OID: u32 = 25;
+const JSONB_OID: u32 = 3802;
+
+const TYPES_QUERY: &str = "
+    SELECT t.oid, t.typtype::text AS kind, t.typbasetype, t.typtypmod
+    FROM pg_type t
+    JOIN pg_namespace n ON n.oid = t.typnamespace
+    LEFT JOIN pg_class c ON c.oid = t.typrelid
+    WHERE (t.typtype IN ('d', 'e', 'c') OR t.typname IN ('geometry', 'geography'))
+      AND (t.typtype <> 'c' OR c.relkind = 'c')
+      AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+      AND ($1::oid IS NULL OR t.oid = $1)";
+
+const ENUM_LABELS_QUERY: &str = "
+    SELECT enumtypid, enumlabel::text
+    FROM pg_enum
+    WHERE enumtypid = ANY($1)
+    ORDER BY enumtypid, enumsortorder";
+
+const COMPOSITE_FIELDS_QUERY: &str = "
+    SELECT t.oid, a.attname::text, a.atttypid
+    FROM pg_type t
+    JOIN pg_attribute a ON a.attrelid = t.typrelid
+    WHERE t.oid = ANY($1) AND a.attnum > 0 AND NOT a.attisdropped
+    ORDER BY t.oid, a.attnum";
+
+/// Tipo definido por el usuario
+#[derive(Debug, Clone, PartialEq)]
+pub enum UserType {
+    /// Domain: se replica como su tipo base
+    Domain { base: u32, base_type_mod: i32 },
+    /// Enum: string, validado contra sus labels
+    Enum { labels: HashSet<String> },
+    /// Composite: objeto JSON con un campo por atributo
+    Composite { fields: Vec<(String, u32)> },
+    /// geometry / geography de PostGIS (EWKB en hex)
+    Spatial,
+}
+
+/// Catálogo compartido entre el pipeline (que lo refresca) y el sink (que lo lee)
+#[derive(Clone, Default)]
+pub struct TypeCatalog {
+    client: Option<Arc<Mutex<Client>>>,
+    types: Arc<RwLock<HashMap<u32, UserType>>>,
+    geometry_format: GeometryFormat,
+}
+
+impl TypeCatalog {
+    /// Conectar y cargar todos los tipos del usuario
+    pub async fn load(database_url: &str, geometry_format: GeometryFormat) -> Result<Self> {
+        // Conexión regular (no replicación), como StateStore
+        let clean_url = database_url
+            .replace("?replication=database", "")
+            .replace("&replication=database", "")
+            .replace("replication=database&", "");
+
+        let (client, connection) = tokio_postgres::connect(&clean_url, NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                eprintln!("TypeCatalog connection error: {}", e);
+            }
+        });
+
+        let types = query_types(&client, None).await?;
+        println!("Type catalog: {} user-defined types", types.len());
+
+        Ok(Self {
+            client: Some(Arc::new(Mutex::new(client))),
+            types: Arc::new(RwLock::new(types)),
+            geometry_format,
+        })
+    }
+
+    /// Volver a leer un tipo de `pg_type` (al recibir un mensaje Type)
+    pub async fn refresh(&self, type_id: u32) -> Result<()> {
+        let Some(ref client) = self.client else {
+            return Ok(());
+        };
+        let fresh = query_types(&*client.lock().await, Some(type_id)).await?;
+
+        let mut types = self.types.write().unwrap_or_else(|e| e.into_inner());
+        match fresh.get(&type_id) {
+            Some(user_type) => {
+                types.insert(type_id, user_type.clone());
+            }
+            None => {
+                types.remove(&type_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Tipo built-in que representa una columna: base de un domain, text para
+    /// enums, jsonb para composites y text o jsonb para geometrías (según el
+    /// formato). Los built-in se retornan sin cambios.
+    pub fn resolve(&self, type_id: u32, type_mod: i32) -> (u32, i32) {
+        if type_id < FIRST_NORMAL_OID {
+            return (type_id, type_mod);
+        }
+        let types = self.types.read().unwrap_or_else(|e| e.into_inner());
+
+        let (mut type_id, mut type_mod) = (type_id, type_mod);
+        for _ in 0..MAX_DOMAIN_DEPTH {
+            match types.get(&type_id) {
+                Some(UserType::Domain { base, base_type_mod }) => {
+                    // El typmod de la columna de un domain es -1: vale el del domain
+                    if type_mod < 0 {
+                        type_mod = *base_type_mod;
+                    }
+                    type_id = *base;
+                }
+                Some(UserType::Enum { .. }) => return (TEXT_OID, -1),
+                Some(UserType::Composite { .. }) => return (JSONB_OID, -1),
+                Some(UserType::Spatial) if self.geometry_format == GeometryFormat::GeoJson => {
+                    return (JSONB_OID, -1)
+                }
+                Some(UserType::Spatial) => return (TEXT_OID, -1),
+                None => break,
+            }
+        }
+        (type_id, type_mod)
+    }
+
+    /// Convierte un valor en texto al JSON de StarRocks (ver `PgValue` para los built-in)
+    pub fn to_json(&self, type_id: u32, text: &str) -> Value {
+        if type_id < FIRST_NORMAL_OID {
+            return PgValue::from_text(type_id, text).into_json();
+        }
+        let types = self.types.read().unwrap_or_else(|e| e.into_inner());
+        user_value(&types, self.geometry_format, type_id, text, 0)
+    }
+}
+
+fn user_value(
+    types: &HashMap<u32, UserType>,
+    geometry_format: GeometryFormat,
+    type_id: u32,
+    text: &str,
+    depth: usize,
+) -> Value {
+    if depth > MAX_DOMAIN_DEPTH {
+        return json!(text);
+    }
+    match types.get(&type_id) {
+        Some(UserType::Domain { base, .. }) => user_value(types, geometry_format, *base, text, depth + 1),
+        Some(UserType::Enum { labels }) => {
+            if !labels.contains(text) {
+                // Label agregado con ALTER TYPE después de la última carga: se conserva el valor
+                eprintln!("⚠️  Enum value '{}' is not a known label of type {}", text, type_id);
+            }
+            json!(text)
+        }
+        Some(UserType::Composite { fields }) => match parse_record(text) {
+            Some(values) if values.len() == fields.len() => {
+                let mut obj = Map::with_capacity(fields.len());
+                for ((name, field_type), value) in fields.iter().zip(values) {
+                    let value = match value {
+                        Some(v) => user_value(types, geometry_format, *field_type, &v, depth + 1),
+                        None => json!(null),
+                    };
+                    obj.insert(name.as_str(), value);
+                }
+                Value::from(obj)
+            }
+            _ => json!(text),
+        },
+        Some(UserType::Spatial) => geometry_format.to_json(text),
+        None => PgValue::from_text(type_id, text).into_json(),
+    }
+}
+
+/// Tipos del usuario en `pg_type` (todos o uno solo)
+async fn query_types(client: &Client, type_id: Option<u32>) -> Result<HashMap<u32, UserType>> {
+    let rows = client.query(TYPES_QUERY, &[&type_id]).await?;
+
+    let mut types = HashMap::with_capacity(rows.len());
+    let mut enums = Vec::new();
+    let mut composites = Vec::new();
+    for row in rows {
+        let oid: u32 = row.get("oid");
+        let kind: String = row.get("kind");
+        match kind.as_str() {
+            "d" => {
+                types.insert(oid, UserType::Domain {
+                    base: row.get("typbasetype"),
+                    base_type_mod: row.get("typtypmod"),
+                });
+            }
+            "e" => enums.push(oid),
+            "c" => composites.push(oid),
+            _ => {
+                types.insert(oid, UserType::Spatial);
+            }
+        }
+    }
+
+    if !enums.is_empty() {
+        let mut labels: HashMap<u32, HashSet<String>> = enums.iter().map(|oid| (*oid, HashSet::new())).collect();
+        for row in client.query(ENUM_LABELS_QUERY, &[&enums]).await? {
+            labels.entry(row.get(0)).or_default().insert(row.get(1));
+        }
+        types.extend(labels.into_iter().map(|(oid, labels)| (oid, UserType::Enum { labels })));
+    }
+
+    if !composites.is_empty() {
+        let mut fields: HashMap<u32, Vec<(String, u32)>> = composites.iter().map(|oid| (*oid, Vec::new())).collect();
+        for row in client.query(COMPOSITE_FIELDS_QUERY, &[&composites]).await? {
+            fields.entry(row.get(0)).or_default().push((row.get(1), row.get(2)));
+        }
+        types.extend(fields.into_iter().map(|(oid, fields)| (oid, UserType::Composite { fields })));
+    }
+
+    Ok(types)
+}
+
+/// Record literal: `(1,"a b",,"x""y")`. Un campo vacío sin comillas es NULL.
+fn parse_record(text: &str) -> Option<Vec<Option<String>>> {
+    let inner = text.strip_prefix('(')?.strip_suffix(')')?;
+    let mut chars = inner.chars().peekable();
+    let mut fields = Vec::new();
+
+    loop {
+        let field = if chars.peek() == Some(&'"') {
+            chars.next();
+            let mut out = String::new();
+            loop {
+                match chars.next()? {
+                    '\\' => out.push(chars.next()?),
+                    // Comilla doble duplicada = comilla literal
+                    '"' if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        out.push('"');
+                    }
+                    '"' => break,
+                    c => out.push(c),
+                }
+            }
+            Some(out)
+        } else {
+            let mut raw = String::new();
+            while let Some(&c) = chars.peek() {
+                if c == ',' {
+                    break;
+                }
+                raw.push(c);
+                chars.next();
+            }
+            (!raw.is_empty()).then_some(raw)
+        };
+        fields.push(field);
+
+        match chars.next() {
+            Some(',') => continue,
+            None => return Some(fields),
+            Some(_) => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalog() -> TypeCatalog {
+        let catalog = TypeCatalog::default();
+        let mut types = catalog.types.write().unwrap();
+        // CREATE DOMAIN positive_int AS int4; CREATE DOMAIN price AS numeric(10,2)
+        types.insert(20000, UserType::Domain { base: 23, base_type_mod: -1 });
+        types.insert(20001, UserType::Domain { base: 1700, base_type_mod: ((10 << 16) | 2) + 4 });
+        // CREATE TYPE mood AS ENUM ('sad', 'happy')
+        types.insert(20002, UserType::Enum {
+            labels: ["sad", "happy"].iter().map(|s| s.to_string()).collect(),
+        });
+        // CREATE TYPE address AS (street text, number positive_int, mood mood)
+        types.insert(20003, UserType::Composite {
+            fields: vec![("street".to_string(), 25), ("number".to_string(), 20000), ("mood".to_string(), 20002)],
+        });
+        // CREATE EXTENSION postgis
+        types.insert(20004, UserType::Spatial);
+        drop(types);
+        catalog
+    }
+
+    #[test]
+    fn test_resolve() {
+        let catalog = catalog();
+        assert_eq!(catalog.resolve(20000, -1), (23, -1));
+        assert_eq!(catalog.resolve(20001, -1), (1700, ((10 << 16) | 2) + 4));
+        assert_eq!(catalog.resolve(20002, -1), (TEXT_OID, -1));
+        assert_eq!(catalog.resolve(20003, -1), (JSONB_OID, -1));
+        assert_eq!(catalog.resolve(20004, -1), (TEXT_OID, -1));
+        assert_eq!(catalog.resolve(23, -1), (23, -1));
+        assert_eq!(catalog.resolve(99999, -1), (99999, -1));
+    }
+
+    #[test]
+    fn test_values() {
+        let catalog = catalog();
+        assert_eq!(catalog.to_json(20000, "42"), json!(42));
+        assert_eq!(catalog.to_json(20002, "happy"), json!("happy"));
+        assert_eq!(
+            catalog.to_json(20004, "0101000020E6100000000000000000F03F0000000000000040"),
+            json!("POINT(1 2)")
+        );
+        assert_eq!(
+            catalog.to_json(20003, r#"("Main St, 1",7,sad)"#),
+            json!({"street": "Main St, 1", "number": 7, "mood": "sad"})
+        );
+        assert_eq!(
+            catalog.to_json(20003, r#"("say ""hi""",,)"#),
+            json!({"street": "say \"hi\"", "number": null, "mood": null})
+        );
+        // Cantidad de campos distinta: texto sin cambios
+        assert_eq!(catalog.to_json(20003, "(a,1)"), json!("(a,1)"));
+    }
+}
@@ -6,6 +6,17 @@ use anyhow::Result;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+/// Progreso de un snapshot incremental de una tabla
+#[derive(Debug, Clone)]
+pub struct SnapshotProgress {
+    pub table: String,
+    /// Condición WHERE adicional (snapshot parcial)
+    pub filter: Option<String>,
+    /// Valores (texto) de la PK de la última fila copiada
+    pub last_key: Option<Vec<String>>,
+    pub rows_copied: u64,
+}
+
 #[derive(Clone)]
 pub struct StateStore {
     client: Arc<Mutex<Client>>,
@@ -36,6 +47,31 @@ impl StateStore {
             )", &[]
         ).await?;
         
+        // Posición de la última acción de control ejecutada (no se repite en un
+        // replay del WAL): LSN del commit y orden dentro de la transacción
+        client.execute(
+            "ALTER TABLE dbmazz_checkpoints ADD COLUMN IF NOT EXISTS control_lsn BIGINT NOT NULL DEFAULT 0",
+            &[]
+        ).await?;
+        client.execute(
+            "ALTER TABLE dbmazz_checkpoints ADD COLUMN IF NOT EXISTS control_seq INTEGER NOT NULL DEFAULT 0",
+            &[]
+        ).await?;
+        
+        // Progreso de snapshots incrementales (para reanudar tras una caída)
+        client.execute(
+            "CREATE TABLE IF NOT EXISTS dbmazz_snapshot_progress (
+                slot_name TEXT NOT NULL,
+                table_name TEXT NOT NULL,
+                filter TEXT,
+                last_key TEXT,
+                rows_copied BIGINT NOT NULL DEFAULT 0,
+                completed BOOLEAN NOT NULL DEFAULT FALSE,
+                updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+                PRIMARY KEY (slot_name, table_name)
+            )", &[]
+        ).await?;
+        
         Ok(Self { client: Arc::new(Mutex::new(client)) })
     }
 
@@ -58,5 +94,82 @@ impl StateStore {
         
         Ok(row.map(|r| r.get::<_, i64>(0) as u64))
     }
-}
 
+    /// Guarda el LSN de la última acción de control (`dbmazz.*`, `execute-snapshot`) ejecutada
+    pub async fn save_control_position(&self, slot: &str, lsn: u64, seq: u32) -> Result<()> {
+        let client = self.client.lock().await;
+        client.execute(
+            "INSERT INTO dbmazz_checkpoints (slot_name, lsn, control_lsn, control_seq) VALUES ($1, 0, $2, $3)
+             ON CONFLICT (slot_name) DO UPDATE SET control_lsn = $2, control_seq = $3, updated_at = NOW()",
+            &[&slot, &(lsn as i64), &(seq as i32)]
+        ).await?;
+        Ok(())
+    }
+
+    /// LSN del commit y orden de la última acción de control ejecutada
+    pub async fn load_control_position(&self, slot: &str) -> Result<(u64, u32)> {
+        let client = self.client.lock().await;
+        let row = client.query_opt(
+            "SELECT control_lsn, control_seq FROM dbmazz_checkpoints WHERE slot_name = $1",
+            &[&slot]
+        ).await?;
+        
+        Ok(row
+            .map(|r| (r.get::<_, i64>(0) as u64, r.get::<_, i32>(1) as u32))
+            .unwrap_or((0, 0)))
+    }
+
+    /// Guarda el progreso de un snapshot incremental (lo marca como pendiente)
+    pub async fn save_snapshot_progress(&self, slot: &str, progress: &SnapshotProgress) -> Result<()> {
+        let last_key = match &progress.last_key {
+            Some(key) => Some(serde_json::to_string(key)?),
+            None => None,
+        };
+
+        let client = self.client.lock().await;
+        client.execute(
+            "INSERT INTO dbmazz_snapshot_progress (slot_name, table_name, filter, last_key, rows_copied, completed)
+             VALUES ($1, $2, $3, $4, $5, FALSE)
+             ON CONFLICT (slot_name, table_name) DO UPDATE
+             SET filter = $3, last_key = $4, rows_copied = $5, completed = FALSE, updated_at = NOW()",
+            &[&slot, &progress.table, &progress.filter, &last_key, &(progress.rows_copied as i64)]
+        ).await?;
+        Ok(())
+    }
+
+    /// Marca un snapshot incremental como completado
+    pub async fn complete_snapshot(&self, slot: &str, table: &str) -> Result<()> {
+        let client = self.client.lock().await;
+        client.execute(
+            "UPDATE dbmazz_snapshot_progress SET completed = TRUE, updated_at = NOW()
+             WHERE slot_name = $1 AND table_name = $2",
+            &[&slot, &table]
+        ).await?;
+        Ok(())
+    }
+
+    /// Snapshots incrementales interrumpidos que deben reanudarse
+    pub async fn load_pending_snapshots(&self, slot: &str) -> Result<Vec<SnapshotProgress>> {
+        let client = self.client.lock().await;
+        let rows = client.query(
+            "SELECT table_name, filter, last_key, rows_copied FROM dbmazz_snapshot_progress
+             WHERE slot_name = $1 AND NOT completed ORDER BY updated_at",
+            &[&slot]
+        ).await?;
+
+        let mut pending = Vec::with_capacity(rows.len());
+        for row in rows {
+            let last_key: Option<String> = row.get(2);
+            pending.push(SnapshotProgress {
+                table: row.get(0),
+                filter: row.get(1),
+                last_key: match last_key {
+                    Some(key) => Some(serde_json::from_str(&key)?),
+                    None => None,
+                },
+                rows_copied: row.get::<_, i64>(3) as u64,
+            });
+        }
+        Ok(pending)
+    }
+}